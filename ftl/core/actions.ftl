@@ -36,6 +36,12 @@ actions-select = Select
 actions-shortcut-key = Shortcut key: { $val }
 actions-suspend-card = Suspend Card
 actions-set-due-date = Set Due Date
+actions-reschedule-by-retention = Reschedule by Retention
+actions-spread-overdue-reviews = Spread Overdue Reviews
+actions-restore-sync-conflicts = Restore Overwritten Changes
+actions-deduplicate-media = Merge Duplicate Media
+actions-optimize-media = Optimize Media
+actions-repair-media-references = Fix Media References
 actions-answer-card = Answer Card
 actions-unbury-unsuspend = Unbury/Unsuspend
 actions-add-deck = Add Deck
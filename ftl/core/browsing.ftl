@@ -8,6 +8,7 @@ browsing-answer = Answer
 browsing-any-flag = Any Flag
 browsing-average-ease = Avg. Ease
 browsing-average-interval = Avg. Interval
+browsing-average-retrievability = Avg. Retrievability
 browsing-browser-appearance = Browser Appearance
 browsing-browser-options = Browser Options
 browsing-buried = Buried
@@ -53,6 +54,7 @@ browsing-last-card = Last Card
 browsing-learning = (learning)
 browsing-line-size = <b>Line Size</b>:
 browsing-manage-note-types = Manage Note Types
+browsing-media-size = Media Size
 browsing-move-cards = Move Cards
 browsing-move-cards-to-deck = Move cards to deck:
 browsing-new = (new)
@@ -78,6 +80,7 @@ browsing-replace-with = <b>Replace With</b>:
 browsing-reposition = Reposition...
 browsing-reposition-new-cards = Reposition New Cards
 browsing-reschedule = Reschedule
+browsing-retrievability = Retrievability
 browsing-search-bar-hint = Search cards/notes (type text, then press Enter)
 browsing-search-in = Search in:
 browsing-search-within-formatting-slow = Search within formatting (slow)
@@ -142,6 +145,8 @@ browsing-tooltip-cards = The number of cards a note has
 browsing-tooltip-notetype = The name of a note's notetype
 browsing-tooltip-question = The front side of a card, customisable in the card template editor
 browsing-tooltip-answer = The back side of a card, customisable in the card template editor
+browsing-tooltip-retrievability = The predicted chance of recalling a review card today
+browsing-tooltip-media-size = The total size of the media files a note refers to
 browsing-studied-today = Studied
 browsing-added-today = Added
 browsing-again-today = Again
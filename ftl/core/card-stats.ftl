@@ -3,6 +3,7 @@ card-stats-first-review = First Review
 card-stats-latest-review = Latest Review
 card-stats-interval = Interval
 card-stats-ease = Ease
+card-stats-retrievability = Retrievability
 card-stats-review-count = Reviews
 card-stats-lapse-count = Lapses
 card-stats-average-time = Average Time
@@ -22,6 +23,7 @@ card-stats-review-log-type-review = Review
 card-stats-review-log-type-relearn = Relearn
 card-stats-review-log-type-filtered = Filtered
 card-stats-review-log-type-manual = Manual
+card-stats-review-log-type-rescheduled = Rescheduled
 card-stats-no-card = (No card to display.)
 
 ## Window Titles
@@ -108,6 +108,11 @@ deck-config-bury-review-tooltip = Whether other `review` cards of the same note
 deck-config-bury-interday-learning-tooltip =
     Whether other `learning` cards of the same note with intervals > 1 day
     will be delayed until the next day.
+deck-config-sibling-spacing-days = Sibling spacing (days)
+deck-config-sibling-spacing-days-tooltip =
+    When a card is answered, other `review` cards of the same note that would be
+    due within this many days are postponed, so that you don't see a card shortly
+    after its reverse. 0 disables this.
 
 ## Ordering section
 
@@ -186,6 +191,7 @@ deck-config-sort-order-descending-intervals = Descending intervals
 deck-config-sort-order-ascending-ease = Ascending ease
 deck-config-sort-order-descending-ease = Descending ease
 deck-config-sort-order-relative-overdueness = Relative overdueness
+deck-config-sort-order-retrievability-ascending = Ascending retrievability
 deck-config-display-order-will-use-current-deck =
     Anki will use the display order from the deck you 
     select to study, and not any subdecks it may have.
@@ -234,6 +240,31 @@ deck-config-interval-modifier-tooltip =
 deck-config-hard-interval-tooltip = The multiplier applied to a review interval when answering `Hard`.
 deck-config-new-interval-tooltip = The multiplier applied to a review interval when answering `Again`.
 deck-config-minimum-interval-tooltip = The minimum interval given to a review card after answering `Again`.
+deck-config-scheduling-algorithm = Scheduling algorithm
+deck-config-scheduling-algorithm-tooltip =
+    The algorithm used to calculate review intervals. `SM-2` bases intervals on
+    each card's ease, and uses the ease and interval options above. The memory model
+    tracks how well you know each card, and schedules reviews for when you're
+    predicted to recall it with the desired retention.
+deck-config-scheduling-algorithm-sm2 = SM-2
+deck-config-scheduling-algorithm-memory-model = Memory model
+deck-config-desired-retention = Desired retention
+deck-config-desired-retention-tooltip =
+    The chance of recalling a card the memory model aims for when scheduling its
+    next review. Higher values mean shorter intervals and more reviews.
+deck-config-load-balancer = Load balancer
+deck-config-load-balancer-tooltip =
+    Instead of picking a random day within the small range a review's interval is
+    varied by, pick the day with the fewest reviews already due, so that your
+    workload stays even.
+deck-config-easy-days = Easy days
+deck-config-easy-days-tooltip =
+    How many reviews to schedule on each day of the week, compared to a normal day.
+    At 0, reviews are moved to other days where possible; at 0.5, a day gets half
+    as many reviews as usual. Specific dates, such as holidays, can be added below
+    the weekdays, and take the place of their weekday's setting. Applies when
+    reviews are answered and when a due date range is set in the browser.
+deck-config-easy-days-add-date = Add date
 deck-config-custom-scheduling = Custom scheduling
 deck-config-custom-scheduling-tooltip = Affects the entire collection. Use at your own risk!
 
@@ -14,6 +14,7 @@ decks-import-file = Import File
 decks-increasing-intervals = Increasing intervals
 decks-latest-added-first = Latest added first
 decks-limit-to = Limit to
+decks-lowest-retrievability = Lowest retrievability
 decks-minutes = minutes
 decks-most-lapses = Most lapses
 decks-new-deck-name = New deck name:
@@ -12,6 +12,7 @@ media-check-trash-count =
 media-check-missing-count = Missing files: { $count }
 media-check-unused-count = Unused files: { $count }
 media-check-renamed-count = Renamed files: { $count }
+media-check-repair-count = Fixable references: { $count }
 media-check-oversize-count = Over 100MB: { $count }
 media-check-subfolder-count = Subfolders: { $count }
 
@@ -21,6 +22,7 @@ media-check-renamed-header = Some files have been renamed for compatibility:
 media-check-oversize-header = Files over 100MB can not be synced with AnkiWeb.
 media-check-subfolder-header = Folders inside the media folder are not supported.
 media-check-missing-header = The following files are referenced by cards, but were not found in the media folder:
+media-check-repair-header = The following missing files appear to have been renamed, and references to them can be fixed:
 media-check-unused-header = The following files were found in the media folder, but do not appear to be used on any cards:
 media-check-template-references-field-header =
     Anki can not detect used files when you use { "{{Field}}" } references in media/LaTeX tags. The media/LaTeX tags should be placed on individual notes instead.
@@ -33,6 +35,7 @@ media-check-renamed-file = Renamed: { $old } -> { $new }
 media-check-oversize-file = Over 100MB: { $filename }
 media-check-subfolder-file = Folder: { $filename }
 media-check-missing-file = Missing: { $filename }
+media-check-repair-file = Fixable: { $missing } -> { $found }
 media-check-unused-file = Unused: { $filename }
 
 ##
@@ -68,6 +71,7 @@ media-check-all-latex-rendered = All LaTeX rendered.
 
 media-check-delete-unused = Delete Unused
 media-check-render-latex = Render LaTeX
+media-check-repair-references = Fix References
 # button to permanently delete media files from the trash folder
 media-check-empty-trash = Empty Trash
 # button to move deleted files from the trash back into the media folder
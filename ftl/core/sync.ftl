@@ -45,6 +45,10 @@ sync-upload-too-large =
     Your collection file is too large to send to AnkiWeb. You can reduce its
     size by removing any unwanted decks (optionally exporting them first), and
     then using Check Database to shrink the file size down. ({ $details })
+sync-quota-exceeded =
+    Your account on the sync server has used up its storage allowance. Please remove
+    unused media or decks, or ask the server's administrator to raise the limit.
+sync-rate-limited = Too many requests have been sent to the sync server. Please wait a minute, then try again.
 
 ## Buttons
 
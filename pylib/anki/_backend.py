@@ -86,6 +86,10 @@ class RustBackend(RustBackendGenerated):
     def syncserver() -> None:
         _rsbridge.syncserver()
 
+    @staticmethod
+    def syncserver_users(args: list[str], password: str | None) -> None:
+        _rsbridge.syncserver_users(args, password)
+
     def db_query(
         self, sql: str, args: Sequence[ValueForDB], first_row_only: bool
     ) -> list[DBRow]:
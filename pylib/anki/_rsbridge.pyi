@@ -2,6 +2,7 @@ def buildhash() -> str: ...
 def open_backend(data: bytes) -> Backend: ...
 def initialize_logging(log_file: str | None) -> Backend: ...
 def syncserver() -> None: ...
+def syncserver_users(args: list[str], password: str | None) -> None: ...
 
 class Backend:
     @classmethod
@@ -65,7 +65,7 @@ from anki.notes import Note, NoteId
 from anki.scheduler.v1 import Scheduler as V1Scheduler
 from anki.scheduler.v2 import Scheduler as V2Scheduler
 from anki.scheduler.v3 import Scheduler as V3Scheduler
-from anki.sync import SyncAuth, SyncOutput, SyncStatus
+from anki.sync import SyncAuth, SyncConflicts, SyncOutput, SyncStatus
 from anki.tags import TagManager
 from anki.types import assert_exhaustive
 from anki.utils import (
@@ -1200,12 +1200,30 @@ class Collection(DeprecatedNamesMixin):
     def sync_collection(self, auth: SyncAuth) -> SyncOutput:
         return self._backend.sync_collection(auth)
 
+    def restore_sync_conflicts(self, conflicts: SyncConflicts) -> OpChangesWithCount:
+        "Put back the versions a sync discarded. Pass the subset the user chose."
+        return self._backend.restore_sync_conflicts(conflicts)
+
     def sync_media(self, auth: SyncAuth) -> None:
         self._backend.sync_media(auth)
 
     def sync_status(self, auth: SyncAuth) -> SyncStatus:
         return self._backend.sync_status(auth)
 
+    def host_peer_sync(self, address: str, pairing_key: str, backup_folder: str) -> str:
+        """Let another device on the network sync with this collection, by
+        logging in with the pairing key as its password and using this device's
+        address as its endpoint. Short pairing keys are refused, as is pairing
+        after repeated failed logins. Returns the address being listened on.
+
+        The collection can't be used until stop_peer_sync() is called."""
+        return self._backend.host_peer_sync(
+            address=address, pairing_key=pairing_key, backup_folder=backup_folder
+        )
+
+    def stop_peer_sync(self) -> None:
+        self._backend.stop_peer_sync()
+
     def get_preferences(self) -> Preferences:
         return self._backend.get_preferences()
 
@@ -62,6 +62,7 @@ DYN_ADDED = 5
 DYN_DUE = 6
 DYN_REVADDED = 7
 DYN_DUEPRIORITY = 8
+DYN_RETRIEVABILITY = 9
 
 DYN_MAX_SIZE = 99999
 
@@ -90,6 +91,8 @@ REVLOG_REV = 1
 REVLOG_RELRN = 2
 REVLOG_CRAM = 3
 REVLOG_RESCHED = 4
+# due date recomputed from the card's memory state, eg for a new target retention
+REVLOG_RETENTION_RESCHED = 5
 
 # Labels
 ##########################################################################
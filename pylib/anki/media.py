@@ -10,7 +10,7 @@ import sys
 import time
 from typing import Callable
 
-from anki import media_pb2
+from anki import collection_pb2, media_pb2
 from anki._legacy import DeprecatedNamesMixin, deprecated_keywords
 from anki.consts import *
 from anki.latex import render_latex, render_latex_returning_errors
@@ -73,6 +73,38 @@ class MediaManager(DeprecatedNamesMixin):
     def restore_trash(self) -> None:
         self.col._backend.restore_trash()
 
+    def deduplicate(self) -> collection_pb2.OpChangesWithCount:
+        "Point notes at a single copy of identical files, and trash the rest. Undoable."
+        return self.col._backend.deduplicate_media()
+
+    def optimize(
+        self,
+        search: str = "",
+        min_bytes: int | None = None,
+        max_dimension: int | None = None,
+        target_bytes: int | None = None,
+    ) -> collection_pb2.OpChangesWithCount:
+        """Recompress large images used by notes matching `search`, updating
+        references to them. Unset options use the backend's defaults. Undoable."""
+        return self.col._backend.optimize_media(
+            search=search,
+            min_bytes=min_bytes,
+            max_dimension=max_dimension,
+            target_bytes=target_bytes,
+        )
+
+    def repair_references(
+        self, repairs: dict[str, str]
+    ) -> collection_pb2.OpChangesWithCount:
+        """Point references to missing files at the files suggested by check().
+        Undoable."""
+        return self.col._backend.repair_media_references(repairs=repairs)
+
+    def usage(self) -> media_pb2.MediaUsage:
+        """The size of every media file and the notes referring to it, and the
+        total media size of each note and deck."""
+        return self.col._backend.get_media_usage()
+
     def strip_av_tags(self, text: str) -> str:
         return self.col._backend.strip_av_tags(text)
 
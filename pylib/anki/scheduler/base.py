@@ -202,6 +202,24 @@ class SchedulerBase(DeprecatedNamesMixin):
             config_key=key,  # type: ignore
         )
 
+    def reschedule_cards_by_retention(
+        self, search: str, target_retention: float | None = None
+    ) -> OpChangesWithCount:
+        """Move review cards matching `search` to the day their predicted recall
+        drops to `target_retention`, or their preset's desired retention if None."""
+        return self.col._backend.reschedule_cards_by_retention(
+            search=search, target_retention=target_retention
+        )
+
+    def spread_overdue_reviews(
+        self, search: str, days: int, max_per_day: int = 0
+    ) -> OpChangesWithCount:
+        """Spread overdue review cards matching `search` over the next `days` days,
+        most overdue relative to their interval first. 0 means no daily limit."""
+        return self.col._backend.spread_overdue_reviews(
+            search=search, days=days, max_per_day=max_per_day
+        )
+
     def reset_cards(self, ids: list[CardId]) -> None:
         "Completely reset cards for export."
         sids = ids2str(ids)
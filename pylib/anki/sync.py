@@ -7,6 +7,7 @@ from anki import sync_pb2
 SyncAuth = sync_pb2.SyncAuth
 SyncOutput = sync_pb2.SyncCollectionResponse
 SyncStatus = sync_pb2.SyncStatusResponse
+SyncConflicts = sync_pb2.SyncConflicts
 
 
 # Legacy attributes some add-ons may be using
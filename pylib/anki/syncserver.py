@@ -1,6 +1,8 @@
 # Copyright: Ankitects Pty Ltd and contributors
 # License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+from __future__ import annotations
+
 
 def run_sync_server() -> None:
     import sys
@@ -12,13 +14,35 @@ def run_sync_server() -> None:
     env["SYNC_BASE"] = env.get("SYNC_BASE", expanduser("~/.syncserver"))
     env["RUST_LOG"] = env.get("RUST_LOG", "anki=info")
 
+    if "--syncserver" in sys.argv:
+        args = sys.argv[sys.argv.index("--syncserver") + 1 :]
+    else:
+        args = sys.argv[1:]
+
     try:
-        RustBackend.syncserver()
+        if args[:1] == ["users"]:
+            RustBackend.syncserver_users(args[1:], _password_for(args[1:]))
+        else:
+            RustBackend.syncserver()
     except Exception as exc:
         print("Sync server failed:", exc)
         sys.exit(1)
     sys.exit(0)
 
 
+def _password_for(args: list[str]) -> str | None:
+    "Prompt for a password if the users command needs one."
+    from getpass import getpass
+
+    if len(args) != 2 or args[0] not in ("add", "passwd"):
+        return None
+    password = getpass(f"Password for {args[1]}: ")
+    if getpass("Repeat password: ") != password:
+        raise SystemExit("Passwords do not match.")
+    if not password:
+        raise SystemExit("Password must not be empty.")
+    return password
+
+
 if __name__ == "__main__":
     run_sync_server()
@@ -34,6 +34,12 @@ fn syncserver() -> PyResult<()> {
     SimpleServer::run().map_err(|e| PyException::new_err(format!("{e:?}")))
 }
 
+#[pyfunction]
+fn syncserver_users(args: Vec<String>, password: Option<String>) -> PyResult<()> {
+    SimpleServer::run_users_command(&args, password)
+        .map_err(|e| PyException::new_err(format!("{e:?}")))
+}
+
 #[pyfunction]
 fn open_backend(init_msg: &PyBytes) -> PyResult<Backend> {
     match init_backend(init_msg.as_bytes()) {
@@ -85,6 +91,7 @@ fn _rsbridge(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_wrapped(wrap_pyfunction!(open_backend)).unwrap();
     m.add_wrapped(wrap_pyfunction!(initialize_logging)).unwrap();
     m.add_wrapped(wrap_pyfunction!(syncserver)).unwrap();
+    m.add_wrapped(wrap_pyfunction!(syncserver_users)).unwrap();
 
     Ok(())
 }
@@ -14,7 +14,7 @@ from anki.collection import Collection, SearchNode
 from anki.errors import Interrupted
 from anki.media import CheckMediaResponse
 from aqt import gui_hooks
-from aqt.operations import QueryOp
+from aqt.operations import CollectionOp, QueryOp
 from aqt.qt import *
 from aqt.utils import (
     askUser,
@@ -127,6 +127,15 @@ class MediaChecker:
                 box.addButton(b, QDialogButtonBox.ButtonRole.RejectRole)
                 qconnect(b.clicked, self._on_render_latex)
 
+        if output.repairs:
+            b = QPushButton(tr.media_check_repair_references())
+            b.setAutoDefault(False)
+            box.addButton(b, QDialogButtonBox.ButtonRole.RejectRole)
+            qconnect(
+                b.clicked,
+                lambda c: self._on_repair_references(dict(output.repairs)),
+            )
+
         if output.have_trash:
             b = QPushButton(tr.media_check_empty_trash())
             b.setAutoDefault(False)
@@ -145,6 +154,14 @@ class MediaChecker:
         diag.exec()
         saveGeom(diag, "checkmediadb")
 
+    def _on_repair_references(self, repairs: dict[str, str]) -> None:
+        CollectionOp(
+            parent=self.mw,
+            op=lambda col: col.media.repair_references(repairs),
+        ).success(
+            lambda out: tooltip(tr.browsing_notes_updated(count=out.count))
+        ).run_in_background()
+
     def _on_render_latex(self) -> None:
         self.progress_dialog = self.mw.progress.start()
         try:
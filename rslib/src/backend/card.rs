@@ -7,6 +7,7 @@ use crate::card::CardType;
 use crate::pb;
 pub(super) use crate::pb::cards::cards_service::Service as CardsService;
 use crate::prelude::*;
+use crate::scheduler::states::MemoryState;
 
 impl CardsService for Backend {
     fn get_card(&self, input: pb::cards::CardId) -> Result<pb::cards::Card> {
@@ -98,6 +99,7 @@ impl TryFrom<pb::cards::Card> for Card {
             flags: c.flags as u8,
             original_position: c.original_position,
             custom_data: c.custom_data,
+            memory_state: c.memory_state.map(Into::into),
         })
     }
 }
@@ -124,6 +126,25 @@ impl From<Card> for pb::cards::Card {
             flags: c.flags as u32,
             original_position: c.original_position.map(Into::into),
             custom_data: c.custom_data,
+            memory_state: c.memory_state.map(Into::into),
+        }
+    }
+}
+
+impl From<pb::cards::MemoryState> for MemoryState {
+    fn from(state: pb::cards::MemoryState) -> Self {
+        MemoryState {
+            stability: state.stability,
+            difficulty: state.difficulty,
+        }
+    }
+}
+
+impl From<MemoryState> for pb::cards::MemoryState {
+    fn from(state: MemoryState) -> Self {
+        pb::cards::MemoryState {
+            stability: state.stability,
+            difficulty: state.difficulty,
         }
     }
 }
@@ -7,7 +7,17 @@ use crate::deckconfig::DeckConfig;
 use crate::deckconfig::UpdateDeckConfigsRequest;
 use crate::pb;
 pub(super) use crate::pb::deckconfig::deckconfig_service::Service as DeckConfigService;
+use crate::pb::deckconfig::optimize_deck_config_response::Parameters;
+use crate::pb::deckconfig::optimize_deck_config_response::Preset;
+use crate::pb::deckconfig::simulate_reviews_request::RecallModel as RecallModelProto;
+use crate::pb::deckconfig::simulate_reviews_response::Day;
 use crate::prelude::*;
+use crate::scheduler::optimizer::PresetOptimization;
+use crate::scheduler::optimizer::Sm2Parameters;
+use crate::scheduler::simulator::RecallModel;
+use crate::scheduler::simulator::SimulatedDay;
+use crate::scheduler::simulator::SimulatorConfig;
+use crate::scheduler::simulator::DEFAULT_SECONDS_PER_REVIEW;
 
 impl DeckConfigService for Backend {
     fn add_or_update_deck_config_legacy(
@@ -85,6 +95,37 @@ impl DeckConfigService for Backend {
         self.with_col(|col| col.update_deck_configs(input.into()))
             .map(Into::into)
     }
+
+    fn optimize_deck_config(
+        &self,
+        input: pb::deckconfig::OptimizeDeckConfigRequest,
+    ) -> Result<pb::deckconfig::OptimizeDeckConfigResponse> {
+        self.with_col(|col| {
+            Ok(pb::deckconfig::OptimizeDeckConfigResponse {
+                presets: col
+                    .optimize_deck_config(&input.search)?
+                    .into_iter()
+                    .map(Into::into)
+                    .collect(),
+            })
+        })
+    }
+
+    fn simulate_reviews(
+        &self,
+        input: pb::deckconfig::SimulateReviewsRequest,
+    ) -> Result<pb::deckconfig::SimulateReviewsResponse> {
+        let config = SimulatorConfig::from(&input);
+        self.with_col(|col| {
+            Ok(pb::deckconfig::SimulateReviewsResponse {
+                days: col
+                    .simulate_reviews(&config, &input.search)?
+                    .into_iter()
+                    .map(Into::into)
+                    .collect(),
+            })
+        })
+    }
 }
 
 impl From<DeckConfig> for pb::deckconfig::DeckConfig {
@@ -123,3 +164,63 @@ impl From<pb::deckconfig::DeckConfig> for DeckConfig {
         }
     }
 }
+
+impl From<PresetOptimization> for Preset {
+    fn from(p: PresetOptimization) -> Self {
+        Preset {
+            config_id: p.config_id.0,
+            current: Some(p.current.into()),
+            suggested: Some(p.suggested.into()),
+            current_log_loss: p.current_log_loss,
+            suggested_log_loss: p.suggested_log_loss,
+            review_count: p.review_count,
+        }
+    }
+}
+
+impl From<Sm2Parameters> for Parameters {
+    fn from(p: Sm2Parameters) -> Self {
+        Parameters {
+            initial_ease: p.initial_ease,
+            interval_multiplier: p.interval_multiplier,
+            easy_multiplier: p.easy_multiplier,
+            hard_multiplier: p.hard_multiplier,
+        }
+    }
+}
+
+impl From<&pb::deckconfig::SimulateReviewsRequest> for SimulatorConfig {
+    fn from(r: &pb::deckconfig::SimulateReviewsRequest) -> Self {
+        SimulatorConfig {
+            deck_config: r
+                .config
+                .clone()
+                .unwrap_or_else(|| DeckConfig::default().inner),
+            days: r.days,
+            new_cards_per_day: r.new_cards_per_day,
+            recall_model: match r.recall_model() {
+                RecallModelProto::ForgettingCurve => RecallModel::ForgettingCurve,
+                RecallModelProto::Fixed => RecallModel::Fixed(r.fixed_recall),
+            },
+            seconds_per_review: if r.seconds_per_review > 0.0 {
+                r.seconds_per_review
+            } else {
+                DEFAULT_SECONDS_PER_REVIEW
+            },
+            seed: r.seed,
+        }
+    }
+}
+
+impl From<SimulatedDay> for Day {
+    fn from(d: SimulatedDay) -> Self {
+        Day {
+            review_count: d.review_count,
+            new_count: d.new_count,
+            learning_count: d.learning_count,
+            seconds: d.seconds,
+            retention: d.retention,
+            average_retrievability: d.average_retrievability,
+        }
+    }
+}
@@ -4,6 +4,8 @@
 use super::progress::Progress;
 use super::Backend;
 use crate::media::check::MediaChecker;
+use crate::media::optimize::MediaOptimizeOptions;
+use crate::media::usage::MediaUsage;
 use crate::pb;
 pub(super) use crate::pb::media::media_service::Service as MediaService;
 use crate::prelude::*;
@@ -30,6 +32,7 @@ impl MediaService for Backend {
                     missing: output.missing,
                     report,
                     have_trash: output.trash_count > 0,
+                    repairs: output.repairs,
                 })
             })
         })
@@ -80,4 +83,77 @@ impl MediaService for Backend {
         })
         .map(Into::into)
     }
+
+    fn deduplicate_media(
+        &self,
+        _input: pb::generic::Empty,
+    ) -> Result<pb::collection::OpChangesWithCount> {
+        self.with_col(|col| col.deduplicate_media().map(Into::into))
+    }
+
+    fn optimize_media(
+        &self,
+        input: pb::media::OptimizeMediaRequest,
+    ) -> Result<pb::collection::OpChangesWithCount> {
+        let mut handler = self.new_progress_handler();
+        let progress_fn =
+            move |progress| handler.update(Progress::MediaCheck(progress as u32), true);
+        let defaults = MediaOptimizeOptions::default();
+        let options = MediaOptimizeOptions {
+            min_bytes: input.min_bytes.unwrap_or(defaults.min_bytes),
+            max_dimension: input.max_dimension.unwrap_or(defaults.max_dimension),
+            target_bytes: input.target_bytes.unwrap_or(defaults.target_bytes),
+        };
+        self.with_col(|col| {
+            col.optimize_media(&input.search, options, progress_fn)
+                .map(Into::into)
+        })
+    }
+
+    fn repair_media_references(
+        &self,
+        input: pb::media::RepairMediaReferencesRequest,
+    ) -> Result<pb::collection::OpChangesWithCount> {
+        self.with_col(|col| col.repair_media_references(&input.repairs).map(Into::into))
+    }
+
+    fn get_media_usage(&self, _input: pb::generic::Empty) -> Result<pb::media::MediaUsage> {
+        let mut handler = self.new_progress_handler();
+        let progress_fn =
+            move |progress| handler.update(Progress::MediaCheck(progress as u32), true);
+        self.with_col(|col| col.media_usage(progress_fn).map(Into::into))
+    }
+}
+
+impl From<MediaUsage> for pb::media::MediaUsage {
+    fn from(usage: MediaUsage) -> Self {
+        pb::media::MediaUsage {
+            files: usage
+                .files
+                .into_iter()
+                .map(|file| pb::media::media_usage::File {
+                    fname: file.fname,
+                    size: file.size,
+                    references: file
+                        .references
+                        .into_iter()
+                        .map(|reference| pb::media::media_usage::Reference {
+                            note_id: reference.note_id.0,
+                            field: reference.field,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            note_bytes: usage
+                .note_bytes
+                .into_iter()
+                .map(|(nid, bytes)| (nid.0, bytes))
+                .collect(),
+            deck_bytes: usage
+                .deck_bytes
+                .into_iter()
+                .map(|(did, bytes)| (did.0, bytes))
+                .collect(),
+        }
+    }
 }
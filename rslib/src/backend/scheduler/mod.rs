@@ -165,6 +165,26 @@ impl SchedulerService for Backend {
         self.with_col(|col| col.set_due_date(&cids, &days, config).map(Into::into))
     }
 
+    fn reschedule_cards_by_retention(
+        &self,
+        input: pb::scheduler::RescheduleCardsByRetentionRequest,
+    ) -> Result<pb::collection::OpChangesWithCount> {
+        self.with_col(|col| {
+            col.reschedule_cards_by_retention(&input.search, input.target_retention)
+                .map(Into::into)
+        })
+    }
+
+    fn spread_overdue_reviews(
+        &self,
+        input: pb::scheduler::SpreadOverdueReviewsRequest,
+    ) -> Result<pb::collection::OpChangesWithCount> {
+        self.with_col(|col| {
+            col.spread_overdue_reviews(&input.search, input.days, input.max_per_day)
+                .map(Into::into)
+        })
+    }
+
     fn sort_cards(
         &self,
         input: pb::scheduler::SortCardsRequest,
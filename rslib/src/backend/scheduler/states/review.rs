@@ -12,6 +12,7 @@ impl From<pb::scheduler::scheduling_state::Review> for ReviewState {
             ease_factor: state.ease_factor,
             lapses: state.lapses,
             leeched: state.leeched,
+            memory_state: state.memory_state.map(Into::into),
         }
     }
 }
@@ -24,6 +25,7 @@ impl From<ReviewState> for pb::scheduler::scheduling_state::Review {
             ease_factor: state.ease_factor,
             lapses: state.lapses,
             leeched: state.leeched,
+            memory_state: state.memory_state.map(Into::into),
         }
     }
 }
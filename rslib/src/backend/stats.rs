@@ -40,6 +40,7 @@ impl From<RevlogReviewKind> for i32 {
             RevlogReviewKind::Relearning => pb::stats::revlog_entry::ReviewKind::Relearning,
             RevlogReviewKind::Filtered => pb::stats::revlog_entry::ReviewKind::Filtered,
             RevlogReviewKind::Manual => pb::stats::revlog_entry::ReviewKind::Manual,
+            RevlogReviewKind::Rescheduled => pb::stats::revlog_entry::ReviewKind::Rescheduled,
         }) as i32
     }
 }
@@ -8,14 +8,19 @@ use futures::future::AbortRegistration;
 use futures::future::Abortable;
 use pb::sync::sync_status_response::Required;
 use reqwest::Url;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
 use tracing::warn;
 
 use super::progress::AbortHandleSlot;
 use super::Backend;
+use crate::collection::CollectionBuilder;
 use crate::pb;
 pub(super) use crate::pb::sync::sync_service::Service as SyncService;
 use crate::pb::sync::SyncStatusResponse;
 use crate::prelude::*;
+use crate::sync::collection::conflicts::SyncConflict;
+use crate::sync::collection::conflicts::SyncConflicts;
 use crate::sync::collection::normal::ClientSyncState;
 use crate::sync::collection::normal::NormalSyncProgress;
 use crate::sync::collection::normal::SyncActionRequired;
@@ -24,6 +29,7 @@ use crate::sync::collection::progress::sync_abort;
 use crate::sync::collection::progress::FullSyncProgress;
 use crate::sync::collection::status::online_sync_status_check;
 use crate::sync::http_client::HttpSyncClient;
+use crate::sync::http_server::peer::PeerServer;
 use crate::sync::login::sync_login;
 use crate::sync::login::SyncAuth;
 
@@ -31,6 +37,14 @@ use crate::sync::login::SyncAuth;
 pub(super) struct SyncState {
     remote_sync_status: RemoteSyncStatus,
     media_sync_abort: Option<AbortHandle>,
+    peer_host: Option<PeerHost>,
+}
+
+/// A collection being hosted for peers, and how to re-open it afterwards.
+pub(super) struct PeerHost {
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<std::result::Result<(), hyper::Error>>,
+    builder: CollectionBuilder,
 }
 
 #[derive(Default, Debug)]
@@ -72,10 +86,90 @@ impl From<SyncOutput> for pb::sync::SyncCollectionResponse {
                     pb::sync::sync_collection_response::ChangesRequired::NormalSync as i32
                 }
             },
+            conflicts: Some(o.conflicts.into()),
+        }
+    }
+}
+
+impl From<SyncConflicts> for pb::sync::SyncConflicts {
+    fn from(c: SyncConflicts) -> Self {
+        use pb::sync::sync_conflicts::CardConflict;
+        use pb::sync::sync_conflicts::DeckConflict;
+        use pb::sync::sync_conflicts::NoteConflict;
+        pb::sync::SyncConflicts {
+            notes: c
+                .notes
+                .into_iter()
+                .map(|c| NoteConflict {
+                    kept: Some(c.kept.into()),
+                    discarded: Some(c.discarded.into()),
+                    kept_local: c.kept_local,
+                })
+                .collect(),
+            cards: c
+                .cards
+                .into_iter()
+                .map(|c| CardConflict {
+                    kept: Some(c.kept.into()),
+                    discarded: Some(c.discarded.into()),
+                    kept_local: c.kept_local,
+                })
+                .collect(),
+            decks: c
+                .decks
+                .into_iter()
+                .map(|c| DeckConflict {
+                    kept: Some(c.kept.into()),
+                    discarded: Some(c.discarded.into()),
+                    kept_local: c.kept_local,
+                })
+                .collect(),
         }
     }
 }
 
+impl TryFrom<pb::sync::SyncConflicts> for SyncConflicts {
+    type Error = AnkiError;
+
+    fn try_from(c: pb::sync::SyncConflicts) -> std::result::Result<Self, Self::Error> {
+        Ok(SyncConflicts {
+            notes: c
+                .notes
+                .into_iter()
+                .map(|c| {
+                    Ok(SyncConflict {
+                        kept: c.kept.or_invalid("missing note")?.into(),
+                        discarded: c.discarded.or_invalid("missing note")?.into(),
+                        kept_local: c.kept_local,
+                    })
+                })
+                .collect::<Result<_>>()?,
+            cards: c
+                .cards
+                .into_iter()
+                .map(|c| {
+                    Ok(SyncConflict {
+                        kept: c.kept.or_invalid("missing card")?.try_into()?,
+                        discarded: c.discarded.or_invalid("missing card")?.try_into()?,
+                        kept_local: c.kept_local,
+                    })
+                })
+                .collect::<Result<_>>()?,
+            decks: c
+                .decks
+                .into_iter()
+                .map(|c| {
+                    Ok(SyncConflict {
+                        kept: c.kept.or_invalid("missing deck")?.try_into()?,
+                        discarded: c.discarded.or_invalid("missing deck")?.try_into()?,
+                        kept_local: c.kept_local,
+                    })
+                })
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
 impl TryFrom<pb::sync::SyncAuth> for SyncAuth {
     type Error = AnkiError;
 
@@ -129,6 +223,14 @@ impl SyncService for Backend {
         self.sync_collection_inner(input)
     }
 
+    fn restore_sync_conflicts(
+        &self,
+        input: pb::sync::SyncConflicts,
+    ) -> Result<pb::collection::OpChangesWithCount> {
+        let conflicts = input.try_into()?;
+        self.with_col(|col| col.restore_sync_conflicts(conflicts).map(Into::into))
+    }
+
     fn full_upload(&self, input: pb::sync::SyncAuth) -> Result<pb::generic::Empty> {
         self.full_sync_inner(input, true)?;
         Ok(().into())
@@ -138,6 +240,15 @@ impl SyncService for Backend {
         self.full_sync_inner(input, false)?;
         Ok(().into())
     }
+
+    fn host_peer_sync(&self, input: pb::sync::HostPeerSyncRequest) -> Result<pb::generic::String> {
+        self.host_peer_sync_inner(input).map(Into::into)
+    }
+
+    fn stop_peer_sync(&self, _input: pb::generic::Empty) -> Result<pb::generic::Empty> {
+        self.stop_peer_sync_inner()?;
+        Ok(().into())
+    }
 }
 
 impl Backend {
@@ -372,6 +483,69 @@ impl Backend {
             Err(_) => Err(AnkiError::Interrupted),
         }
     }
+
+    pub(super) fn host_peer_sync_inner(
+        &self,
+        input: pb::sync::HostPeerSyncRequest,
+    ) -> Result<String> {
+        self.abort_media_sync_and_wait();
+        let mut col = self.col.lock().unwrap();
+        if col.is_none() {
+            return Err(AnkiError::CollectionNotOpen);
+        }
+        let listener = match PeerServer::bind(&input.address) {
+            Ok(listener) => listener,
+            Err(err) => invalid_input!("couldn't host peer sync: {err}"),
+        };
+        let col_inner = col.take().unwrap();
+        let builder = col_inner.as_builder();
+        let (stop, stopped) = oneshot::channel::<()>();
+        // the listener must be created inside the runtime
+        let runtime = self.runtime_handle();
+        let _guard = runtime.enter();
+        let hosted =
+            PeerServer::new(col_inner, input.pairing_key, input.backup_folder).and_then(|server| {
+                let shutdown = async {
+                    let _ = stopped.await;
+                };
+                match server.make_server(listener, shutdown) {
+                    Ok(hosted) => Ok(hosted),
+                    Err(err) => invalid_input!("couldn't host peer sync: {err}"),
+                }
+            });
+        let (addr, server) = match hosted {
+            Ok(hosted) => hosted,
+            Err(err) => {
+                col.replace(builder.build()?);
+                return Err(err);
+            }
+        };
+        let task = runtime.spawn(server);
+        self.state.lock().unwrap().sync.peer_host = Some(PeerHost {
+            stop,
+            task,
+            builder,
+        });
+        Ok(addr.to_string())
+    }
+
+    /// Waits for any peer that is still syncing to finish.
+    pub(super) fn stop_peer_sync_inner(&self) -> Result<()> {
+        let host = self
+            .state
+            .lock()
+            .unwrap()
+            .sync
+            .peer_host
+            .take()
+            .or_invalid("not hosting")?;
+        let _ = host.stop.send(());
+        let served = self.runtime_handle().block_on(host.task);
+        self.col.lock().unwrap().replace(host.builder.build()?);
+        served
+            .or_invalid("peer sync panicked")?
+            .or_invalid("peer sync failed")
+    }
 }
 
 impl From<Required> for SyncStatusResponse {
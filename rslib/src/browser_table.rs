@@ -12,6 +12,7 @@ use strum::IntoEnumIterator;
 use crate::card::CardQueue;
 use crate::card::CardType;
 use crate::card_rendering::prettify_av_tags;
+use crate::media::usage::note_media_bytes;
 use crate::notetype::CardTemplate;
 use crate::notetype::NotetypeKind;
 use crate::pb;
@@ -37,6 +38,7 @@ pub enum Column {
     Ease,
     #[strum(serialize = "cardLapses")]
     Lapses,
+    MediaSize,
     #[strum(serialize = "cardIvl")]
     Interval,
     #[strum(serialize = "noteCrt")]
@@ -47,6 +49,7 @@ pub enum Column {
     Question,
     #[strum(serialize = "cardReps")]
     Reps,
+    Retrievability,
     #[strum(serialize = "noteFld")]
     SortField,
     #[strum(serialize = "noteTags")]
@@ -69,6 +72,8 @@ struct RowContext {
     tr: I18n,
     timing: SchedTimingToday,
     render_context: RenderContext,
+    /// Only calculated if the media size column is shown.
+    media_bytes: Option<u64>,
 }
 
 enum RenderContext {
@@ -88,6 +93,10 @@ fn card_render_required(columns: &[Column]) -> bool {
         .any(|c| matches!(c, Column::Question | Column::Answer))
 }
 
+fn media_size_required(columns: &[Column]) -> bool {
+    columns.contains(&Column::MediaSize)
+}
+
 impl Card {
     fn is_new_type_or_queue(&self) -> bool {
         self.queue == CardQueue::New || self.ctype == CardType::New
@@ -142,11 +151,13 @@ impl Column {
             Self::Ease => tr.browsing_ease(),
             Self::Interval => tr.browsing_interval(),
             Self::Lapses => tr.scheduling_lapses(),
+            Self::MediaSize => tr.browsing_media_size(),
             Self::NoteCreation => tr.browsing_created(),
             Self::NoteMod => tr.search_note_modified(),
             Self::Notetype => tr.browsing_note(),
             Self::Question => tr.browsing_question(),
             Self::Reps => tr.scheduling_reviews(),
+            Self::Retrievability => tr.browsing_retrievability(),
             Self::SortField => tr.browsing_sort_field(),
             Self::Tags => tr.editing_tags(),
         }
@@ -160,6 +171,7 @@ impl Column {
             Self::Ease => tr.browsing_average_ease(),
             Self::Interval => tr.browsing_average_interval(),
             Self::Reps => tr.scheduling_reviews(),
+            Self::Retrievability => tr.browsing_average_retrievability(),
             _ => return self.cards_mode_label(tr),
         }
         .into()
@@ -170,9 +182,11 @@ impl Column {
             Self::Answer => tr.browsing_tooltip_answer(),
             Self::CardMod => tr.browsing_tooltip_card_modified(),
             Self::Cards => tr.browsing_tooltip_card(),
+            Self::MediaSize => tr.browsing_tooltip_media_size(),
             Self::NoteMod => tr.browsing_tooltip_note_modified(),
             Self::Notetype => tr.browsing_tooltip_notetype(),
             Self::Question => tr.browsing_tooltip_question(),
+            Self::Retrievability => tr.browsing_tooltip_retrievability(),
             _ => "".into(),
         }
         .into()
@@ -189,7 +203,7 @@ impl Column {
     pub fn default_order(self) -> pb::search::browser_columns::Sorting {
         use pb::search::browser_columns::Sorting;
         match self {
-            Column::Question | Column::Answer | Column::Custom => Sorting::None,
+            Column::Question | Column::Answer | Column::Custom | Column::MediaSize => Sorting::None,
             Column::SortField | Column::Tags | Column::Notetype | Column::Deck => {
                 Sorting::Ascending
             }
@@ -201,7 +215,8 @@ impl Column {
             | Column::Interval
             | Column::NoteCreation
             | Column::NoteMod
-            | Column::Reps => Sorting::Descending,
+            | Column::Reps
+            | Column::Retrievability => Sorting::Descending,
         }
     }
 
@@ -242,7 +257,14 @@ impl Collection {
                 .as_ref()
                 .or_invalid("Active browser columns not set.")?,
         );
-        RowContext::new(self, id, notes_mode, card_render_required(&columns))?.browser_row(&columns)
+        RowContext::new(
+            self,
+            id,
+            notes_mode,
+            card_render_required(&columns),
+            media_size_required(&columns),
+        )?
+        .browser_row(&columns)
     }
 
     fn get_note_maybe_with_fields(&self, id: NoteId, _with_fields: bool) -> Result<Note> {
@@ -310,6 +332,7 @@ impl RowContext {
         id: i64,
         notes_mode: bool,
         with_card_render: bool,
+        with_media_size: bool,
     ) -> Result<Self> {
         let cards;
         let note;
@@ -351,6 +374,7 @@ impl RowContext {
         } else {
             RenderContext::Unset
         };
+        let media_bytes = with_media_size.then(|| note_media_bytes(&note, &col.media_folder));
 
         Ok(RowContext {
             notes_mode,
@@ -362,6 +386,7 @@ impl RowContext {
             tr: col.tr.clone(),
             timing,
             render_context,
+            media_bytes,
         })
     }
 
@@ -394,7 +419,9 @@ impl RowContext {
             Column::Interval => self.interval_str(),
             Column::Lapses => self.cards.iter().map(|c| c.lapses).sum::<u32>().to_string(),
             Column::CardMod => self.card_mod_str(),
+            Column::MediaSize => self.media_size_str(),
             Column::Reps => self.cards.iter().map(|c| c.reps).sum::<u32>().to_string(),
+            Column::Retrievability => self.retrievability_str(),
             Column::Cards => self.cards_str()?,
             Column::NoteCreation => self.note_creation_str(),
             Column::SortField => self.note_field_str(),
@@ -405,6 +432,16 @@ impl RowContext {
         })
     }
 
+    /// Returns the size of the note's media in KB or MB, or the empty string
+    /// if it has none.
+    fn media_size_str(&self) -> String {
+        match self.media_bytes.unwrap_or_default() {
+            0 => "".into(),
+            bytes if bytes < 1024 * 1024 => format!("{} KB", (bytes + 1023) / 1024),
+            bytes => format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0)),
+        }
+    }
+
     fn note_creation_str(&self) -> String {
         TimestampMillis(self.note.id.into()).as_secs().date_string()
     }
@@ -482,6 +519,22 @@ impl RowContext {
         }
     }
 
+    /// Returns the average retrievability of the review cards if there are
+    /// any.
+    fn retrievability_str(&self) -> String {
+        let retrievabilities: Vec<f32> = self
+            .cards
+            .iter()
+            .filter_map(|c| c.retrievability(self.timing.days_elapsed))
+            .collect();
+        if retrievabilities.is_empty() {
+            "".into()
+        } else {
+            let average = retrievabilities.iter().sum::<f32>() / retrievabilities.len() as f32;
+            format!("{:.0}%", average * 100.0)
+        }
+    }
+
     /// Returns the average interval of the review and relearn cards if there
     /// are any.
     fn interval_str(&self) -> String {
@@ -22,6 +22,8 @@ use crate::error::Result;
 use crate::notes::NoteId;
 use crate::ops::StateChanges;
 use crate::prelude::*;
+use crate::scheduler::states::algorithm::review_card_retrievability;
+use crate::scheduler::states::MemoryState;
 use crate::timestamp::TimestampSecs;
 use crate::types::Usn;
 
@@ -61,7 +63,7 @@ pub enum CardQueue {
     UserBuried = -3,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Card {
     pub(crate) id: CardId,
     pub(crate) note_id: NoteId,
@@ -85,6 +87,8 @@ pub struct Card {
     /// JSON object or empty; exposed through the reviewer for persisting custom
     /// state
     pub(crate) custom_data: String,
+    /// Set when the card was last scheduled by the memory model.
+    pub(crate) memory_state: Option<MemoryState>,
 }
 
 impl Default for Card {
@@ -109,6 +113,7 @@ impl Default for Card {
             flags: 0,
             original_position: None,
             custom_data: String::new(),
+            memory_state: None,
         }
     }
 }
@@ -149,6 +154,19 @@ impl Card {
         (self.ease_factor as f32) / 1000.0
     }
 
+    /// The predicted chance of recalling the card on day `today`, if it is a
+    /// review card.
+    pub fn retrievability(&self, today: u32) -> Option<f32> {
+        (self.ctype == CardType::Review).then(|| {
+            review_card_retrievability(
+                self.memory_state,
+                self.interval,
+                self.original_or_current_due(),
+                today,
+            )
+        })
+    }
+
     pub fn is_intraday_learning(&self) -> bool {
         matches!(self.queue, CardQueue::Learn | CardQueue::PreviewRepeat)
     }
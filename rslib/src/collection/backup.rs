@@ -110,6 +110,15 @@ fn thin_backups<P: AsRef<Path>>(backup_folder: P, limits: BackupLimits) -> Resul
     Ok(())
 }
 
+/// Paths of the backups in the folder, newest first.
+pub(crate) fn list_backups(backup_folder: &Path) -> Result<Vec<PathBuf>> {
+    Ok(read_dir(backup_folder)?
+        .filter_map(|entry| entry.ok().and_then(Backup::from_entry))
+        .sorted_unstable_by(|a, b| b.datetime.cmp(&a.datetime))
+        .map(|backup| backup.path)
+        .collect())
+}
+
 fn datetime_from_file_name(file_name: &str) -> Option<DateTime<Local>> {
     NaiveDateTime::parse_from_str(file_name, BACKUP_FORMAT_STRING)
         .ok()
@@ -33,6 +33,8 @@ pub enum BoolKey {
     ResetCountsReviewer,
     RandomOrderReposition,
     ShiftPositionOfExistingCards,
+    /// Set while the collection syncs with peers instead of a server.
+    SyncsWithPeers,
 
     #[strum(to_string = "normalize_note_text")]
     NormalizeNoteText,
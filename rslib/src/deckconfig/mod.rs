@@ -9,17 +9,21 @@ pub use schema11::DeckConfSchema11;
 pub use schema11::NewCardOrderSchema11;
 pub use update::UpdateDeckConfigsRequest;
 
+pub use crate::pb::deckconfig::deck_config::config::EasyDate;
 pub use crate::pb::deckconfig::deck_config::config::LeechAction;
 pub use crate::pb::deckconfig::deck_config::config::NewCardGatherPriority;
 pub use crate::pb::deckconfig::deck_config::config::NewCardInsertOrder;
 pub use crate::pb::deckconfig::deck_config::config::NewCardSortOrder;
 pub use crate::pb::deckconfig::deck_config::config::ReviewCardOrder;
 pub use crate::pb::deckconfig::deck_config::config::ReviewMix;
+pub use crate::pb::deckconfig::deck_config::config::SchedulingAlgorithmKind;
 pub use crate::pb::deckconfig::deck_config::Config as DeckConfigInner;
 
 /// Old deck config and cards table store 250% as 2500.
 pub(crate) const INITIAL_EASE_FACTOR_THOUSANDS: u16 = (INITIAL_EASE_FACTOR * 1000.0) as u16;
 
+use chrono::NaiveDate;
+
 use crate::define_newtype;
 use crate::prelude::*;
 use crate::scheduler::states::review::INITIAL_EASE_FACTOR;
@@ -66,6 +70,12 @@ const DEFAULT_DECK_CONFIG_INNER: DeckConfigInner = DeckConfigInner {
     bury_new: false,
     bury_reviews: false,
     bury_interday_learning: false,
+    scheduling_algorithm: SchedulingAlgorithmKind::Sm2 as i32,
+    desired_retention: 0.9,
+    load_balancer: false,
+    easy_days_percentages: Vec::new(),
+    sibling_spacing_days: 0,
+    easy_dates: Vec::new(),
     other: Vec::new(),
 };
 
@@ -90,6 +100,26 @@ impl DeckConfig {
         self.mtime_secs = TimestampSecs::now();
         self.usn = usn;
     }
+
+    /// An easy date that can't be parsed would silently have no effect.
+    fn ensure_easy_dates_valid(&self) -> Result<()> {
+        if let Some(date) = self
+            .inner
+            .easy_dates
+            .iter()
+            .find(|date| date.naive_date().is_none())
+        {
+            invalid_input!("invalid easy date: {}", date.date);
+        }
+        Ok(())
+    }
+}
+
+impl EasyDate {
+    /// None if the date is not a valid YYYY-MM-DD date.
+    pub(crate) fn naive_date(&self) -> Option<NaiveDate> {
+        NaiveDate::parse_from_str(&self.date, "%Y-%m-%d").ok()
+    }
 }
 
 impl Collection {
@@ -116,6 +146,7 @@ impl Collection {
 
 impl Collection {
     pub(crate) fn add_or_update_deck_config(&mut self, config: &mut DeckConfig) -> Result<()> {
+        config.ensure_easy_dates_valid()?;
         let usn = Some(self.usn()?);
 
         if config.id.0 == 0 {
@@ -135,6 +166,7 @@ impl Collection {
         &mut self,
         config: &mut DeckConfig,
     ) -> Result<()> {
+        config.ensure_easy_dates_valid()?;
         let usn = Some(self.usn()?);
 
         if config.id.0 == 0 {
@@ -16,6 +16,7 @@ use serde_tuple::Serialize_tuple;
 use super::DeckConfig;
 use super::DeckConfigId;
 use super::DeckConfigInner;
+use super::EasyDate;
 use super::NewCardInsertOrder;
 use super::INITIAL_EASE_FACTOR_THOUSANDS;
 use crate::serde::default_on_invalid;
@@ -63,6 +64,18 @@ pub struct DeckConfSchema11 {
     new_gather_priority: i32,
     #[serde(default)]
     bury_interday_learning: bool,
+    #[serde(default)]
+    scheduling_algorithm: i32,
+    #[serde(default = "desired_retention_default")]
+    desired_retention: f32,
+    #[serde(default)]
+    load_balancer: bool,
+    #[serde(default)]
+    easy_days_percentages: Vec<f32>,
+    #[serde(default)]
+    sibling_spacing_days: u32,
+    #[serde(default)]
+    easy_dates: Vec<EasyDateSchema11>,
 
     #[serde(flatten)]
     other: HashMap<String, Value>,
@@ -87,6 +100,12 @@ pub struct NewConfSchema11 {
     other: HashMap<String, Value>,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct EasyDateSchema11 {
+    date: String,
+    percentage: f32,
+}
+
 #[derive(Serialize_tuple, Debug, PartialEq, Eq, Clone)]
 pub struct NewCardIntervals {
     good: u16,
@@ -140,6 +159,10 @@ impl Default for NewCardOrderSchema11 {
     }
 }
 
+fn desired_retention_default() -> f32 {
+    0.9
+}
+
 fn hard_factor_default() -> f32 {
     1.2
 }
@@ -253,6 +276,12 @@ impl Default for DeckConfSchema11 {
             new_sort_order: 0,
             new_gather_priority: 0,
             bury_interday_learning: false,
+            scheduling_algorithm: 0,
+            desired_retention: desired_retention_default(),
+            load_balancer: false,
+            easy_days_percentages: vec![],
+            sibling_spacing_days: 0,
+            easy_dates: vec![],
         }
     }
 }
@@ -321,6 +350,19 @@ impl From<DeckConfSchema11> for DeckConfig {
                 bury_new: c.new.bury,
                 bury_reviews: c.rev.bury,
                 bury_interday_learning: c.bury_interday_learning,
+                scheduling_algorithm: c.scheduling_algorithm,
+                desired_retention: c.desired_retention,
+                load_balancer: c.load_balancer,
+                easy_days_percentages: c.easy_days_percentages,
+                sibling_spacing_days: c.sibling_spacing_days,
+                easy_dates: c
+                    .easy_dates
+                    .into_iter()
+                    .map(|d| EasyDate {
+                        date: d.date,
+                        percentage: d.percentage,
+                    })
+                    .collect(),
                 other: other_bytes,
             },
         }
@@ -409,6 +451,19 @@ impl From<DeckConfig> for DeckConfSchema11 {
             new_sort_order: i.new_card_sort_order,
             new_gather_priority: i.new_card_gather_priority,
             bury_interday_learning: i.bury_interday_learning,
+            scheduling_algorithm: i.scheduling_algorithm,
+            desired_retention: i.desired_retention,
+            load_balancer: i.load_balancer,
+            easy_days_percentages: i.easy_days_percentages,
+            sibling_spacing_days: i.sibling_spacing_days,
+            easy_dates: i
+                .easy_dates
+                .into_iter()
+                .map(|d| EasyDateSchema11 {
+                    date: d.date,
+                    percentage: d.percentage,
+                })
+                .collect(),
         }
     }
 }
@@ -429,6 +484,12 @@ fn clear_other_duplicates(top_other: &mut HashMap<String, Value>) {
         "newSortOrder",
         "newGatherPriority",
         "buryInterdayLearning",
+        "schedulingAlgorithm",
+        "desiredRetention",
+        "loadBalancer",
+        "easyDaysPercentages",
+        "siblingSpacingDays",
+        "easyDates",
     ] {
         top_other.remove(*key);
     }
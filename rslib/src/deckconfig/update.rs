@@ -368,6 +368,20 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn invalid_easy_dates_are_refused() -> Result<()> {
+        let mut col = open_test_collection();
+        let mut config = col.get_deck_config(DeckConfigId(1), false)?.unwrap();
+        config.inner.easy_dates = vec![EasyDate {
+            date: "2024-02-30".into(),
+            percentage: 0.5,
+        }];
+        assert!(col.add_or_update_deck_config(&mut config).is_err());
+        config.inner.easy_dates[0].date = "2024-02-29".into();
+        col.add_or_update_deck_config(&mut config)?;
+        Ok(())
+    }
+
     #[test]
     fn should_increase_remaining_learning_steps_if_unpassed_learning_step_added() {
         let mut col = open_test_collection_with_learning_card();
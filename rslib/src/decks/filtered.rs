@@ -59,6 +59,7 @@ impl FilteredSearchOrder {
             FilteredSearchOrder::Due => tr.decks_order_due(),
             FilteredSearchOrder::ReverseAdded => tr.decks_latest_added_first(),
             FilteredSearchOrder::DuePriority => tr.decks_relative_overdueness(),
+            FilteredSearchOrder::RetrievabilityAscending => tr.decks_lowest_retrievability(),
         }
         .into()
     }
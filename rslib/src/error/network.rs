@@ -45,6 +45,8 @@ pub enum SyncErrorKind {
     DatabaseCheckRequired,
     SyncNotStarted,
     UploadTooLarge,
+    QuotaExceeded,
+    RateLimited,
     SanityCheckFailed {
         client: Option<SanityCheckCounts>,
         server: Option<SanityCheckCounts>,
@@ -129,6 +131,18 @@ fn error_for_status_code(info: String, code: StatusCode) -> AnkiError {
                 kind: SyncErrorKind::DatabaseCheckRequired,
             },
         },
+        S::INSUFFICIENT_STORAGE => AnkiError::SyncError {
+            source: SyncError {
+                info,
+                kind: SyncErrorKind::QuotaExceeded,
+            },
+        },
+        S::TOO_MANY_REQUESTS => AnkiError::SyncError {
+            source: SyncError {
+                info,
+                kind: SyncErrorKind::RateLimited,
+            },
+        },
         _ => AnkiError::NetworkError {
             source: NetworkError {
                 info,
@@ -186,6 +200,8 @@ impl SyncError {
             }
             SyncErrorKind::SyncNotStarted => "sync not started".into(),
             SyncErrorKind::UploadTooLarge => tr.sync_upload_too_large(&self.info),
+            SyncErrorKind::QuotaExceeded => tr.sync_quota_exceeded(),
+            SyncErrorKind::RateLimited => tr.sync_rate_limited(),
         }
         .into()
     }
@@ -217,6 +233,8 @@ impl From<HttpError> for AnkiError {
                         StatusCode::FORBIDDEN => SyncErrorKind::AuthFailed,
                         StatusCode::INTERNAL_SERVER_ERROR => SyncErrorKind::ServerError,
                         StatusCode::BAD_REQUEST => SyncErrorKind::DatabaseCheckRequired,
+                        StatusCode::INSUFFICIENT_STORAGE => SyncErrorKind::QuotaExceeded,
+                        StatusCode::TOO_MANY_REQUESTS => SyncErrorKind::RateLimited,
                         _ => SyncErrorKind::Other,
                     };
                     let info = format!("{:?}", err);
@@ -37,6 +37,9 @@ struct Context<'a> {
     data: ExchangeData,
     usn: Usn,
     progress: IncrementableProgress<ImportProgress>,
+    /// If true, existing notes that differ from the package are updated,
+    /// even if they were modified more recently.
+    revert_edits: bool,
 }
 
 impl Collection {
@@ -44,12 +47,32 @@ impl Collection {
         &mut self,
         path: impl AsRef<Path>,
         progress_fn: impl 'static + FnMut(ImportProgress, bool) -> bool,
+    ) -> Result<OpOutput<NoteLog>> {
+        self.import_apkg_inner(path, progress_fn, false)
+    }
+
+    /// Like [Collection::import_apkg], but any changes made to the
+    /// package's notes since they were imported are reverted.
+    pub(crate) fn import_apkg_reverting_edits(
+        &mut self,
+        path: impl AsRef<Path>,
+        progress_fn: impl 'static + FnMut(ImportProgress, bool) -> bool,
+    ) -> Result<OpOutput<NoteLog>> {
+        self.import_apkg_inner(path, progress_fn, true)
+    }
+
+    fn import_apkg_inner(
+        &mut self,
+        path: impl AsRef<Path>,
+        progress_fn: impl 'static + FnMut(ImportProgress, bool) -> bool,
+        revert_edits: bool,
     ) -> Result<OpOutput<NoteLog>> {
         let file = open_file(path)?;
         let archive = ZipArchive::new(file)?;
 
         self.transact(Op::Import, |col| {
             let mut ctx = Context::new(archive, col, progress_fn)?;
+            ctx.revert_edits = revert_edits;
             ctx.import()
         })
     }
@@ -81,6 +104,7 @@ impl<'a> Context<'a> {
             data,
             usn,
             progress,
+            revert_edits: false,
         })
     }
 
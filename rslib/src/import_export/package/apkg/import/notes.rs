@@ -28,6 +28,7 @@ struct NoteContext<'a> {
     target_ids: HashSet<NoteId>,
     media_map: &'a mut MediaUseMap,
     imports: NoteImports,
+    revert_edits: bool,
 }
 
 #[derive(Debug, Default)]
@@ -84,6 +85,7 @@ impl Context<'_> {
         media_map: &mut MediaUseMap,
     ) -> Result<NoteImports> {
         let mut ctx = NoteContext::new(self.usn, self.target_col, media_map)?;
+        ctx.revert_edits = self.revert_edits;
         ctx.import_notetypes(mem::take(&mut self.data.notetypes))?;
         ctx.import_notes(mem::take(&mut self.data.notes), &mut self.progress)?;
         Ok(ctx.imports)
@@ -108,6 +110,7 @@ impl<'n> NoteContext<'n> {
             target_ids,
             imports: NoteImports::default(),
             media_map,
+            revert_edits: false,
         })
     }
 
@@ -218,7 +221,7 @@ impl<'n> NoteContext<'n> {
     }
 
     fn maybe_update_note(&mut self, note: Note, meta: NoteMeta) -> Result<()> {
-        if meta.mtime < note.mtime {
+        if meta.mtime < note.mtime || self.revert_edits {
             if meta.notetype_id == note.notetype_id {
                 self.update_note(note, meta.id)?;
             } else {
@@ -235,6 +238,11 @@ impl<'n> NoteContext<'n> {
         note.id = target_id;
         self.munge_media(&mut note)?;
         let original = self.get_expected_note(note.id)?;
+        if self.revert_edits && note_content_matches(&original, &note) {
+            note.id = source_id;
+            self.imports.log_duplicate(note, target_id);
+            return Ok(());
+        }
         let notetype = self.get_expected_notetype(note.notetype_id)?;
         self.target_col.update_note_inner_without_cards(
             &mut note,
@@ -276,6 +284,18 @@ impl<'n> NoteContext<'n> {
     }
 }
 
+/// Tags are compared ignoring case, as the importer matches their case to
+/// existing tags.
+fn note_content_matches(existing: &Note, incoming: &Note) -> bool {
+    existing.fields() == incoming.fields()
+        && existing.tags.len() == incoming.tags.len()
+        && existing
+            .tags
+            .iter()
+            .zip(&incoming.tags)
+            .all(|(a, b)| a.to_lowercase() == b.to_lowercase())
+}
+
 impl Notetype {
     fn schema_hash(&self) -> Sha1Hash {
         let mut hasher = Sha1::new();
@@ -363,6 +383,31 @@ mod test {
         assert_note_logged!(log, duplicate, &["outdated", ""]);
     }
 
+    #[test]
+    fn should_revert_edited_note_if_reverting_edits() {
+        let mut col = open_test_collection();
+        let note = col.add_new_note("basic");
+        let mut edited = note.clone();
+        edited.fields_mut()[0] = "edited".to_string();
+        col.update_note(&mut edited).unwrap();
+
+        let import_reverting = |col: &mut Collection, note: Note| {
+            let mut media_map = MediaUseMap::default();
+            let mut ctx = NoteContext::new(Usn(1), col, &mut media_map).unwrap();
+            ctx.revert_edits = true;
+            let mut progress = IncrementableProgress::new(|_, _| true);
+            ctx.import_notes(vec![note], &mut progress).unwrap();
+            ctx.imports.log
+        };
+        let mut log = import_reverting(&mut col, note.clone());
+        assert_eq!(col.get_all_notes()[0].fields()[0], "");
+        assert_note_logged!(log, updated, &["", ""]);
+
+        // an unchanged note is left alone
+        let mut log = import_reverting(&mut col, note);
+        assert_note_logged!(log, duplicate, &["", ""]);
+    }
+
     #[test]
     fn should_update_note_if_guid_already_exists_with_different_id() {
         let mut col = open_test_collection();
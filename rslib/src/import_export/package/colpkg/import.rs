@@ -61,6 +61,22 @@ pub fn import_colpkg(
     Ok(())
 }
 
+/// Replace the collection at `target_col_path` with the one in the package,
+/// ignoring any media. The schema is marked as modified, so the next sync
+/// will be a full one.
+pub(crate) fn restore_collection_from_colpkg(
+    colpkg_path: &Path,
+    target_col_path: &Path,
+) -> Result<()> {
+    let mut tempfile = new_tempfile_in_parent_of(target_col_path)?;
+    let mut archive = ZipArchive::new(open_file(colpkg_path)?)?;
+    let meta = Meta::from_archive(&mut archive)?;
+    copy_collection(&mut archive, &mut tempfile, &meta)?;
+    check_collection_and_mod_schema(tempfile.path())?;
+    atomic_rename(tempfile, target_col_path, true)?;
+    Ok(())
+}
+
 fn check_collection_and_mod_schema(col_path: &Path) -> Result<()> {
     CollectionBuilder::new(col_path)
         .build()
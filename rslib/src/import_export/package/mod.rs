@@ -8,7 +8,9 @@ mod meta;
 
 pub(crate) use apkg::NoteMeta;
 pub(crate) use colpkg::export::export_colpkg_from_data;
+pub(crate) use colpkg::export::MediaIter;
 pub use colpkg::import::import_colpkg;
+pub(crate) use colpkg::import::restore_collection_from_colpkg;
 pub(self) use meta::Meta;
 pub(self) use meta::Version;
 
@@ -14,12 +14,15 @@ use tracing::debug;
 use crate::collection::Collection;
 use crate::error::AnkiError;
 use crate::error::DbErrorKind;
+use crate::error::OrInvalid;
+use crate::error::OrNotFound;
 use crate::error::Result;
 use crate::latex::extract_latex_expanding_clozes;
 use crate::media::files::data_for_file;
 use crate::media::files::filename_if_normalized;
 use crate::media::files::normalize_nfc_filename;
 use crate::media::files::trash_folder;
+use crate::media::repair::suggest_repairs;
 use crate::media::MediaManager;
 use crate::notes::Note;
 use crate::sync::media::MAX_INDIVIDUAL_MEDIA_FILE_SIZE;
@@ -37,6 +40,8 @@ pub struct MediaCheckOutput {
     pub oversize: Vec<String>,
     pub trash_count: u64,
     pub trash_bytes: u64,
+    /// Missing files mapped to the unused file they were probably renamed to.
+    pub repairs: HashMap<String, String>,
 }
 
 #[derive(Debug, PartialEq, Eq, Default)]
@@ -78,6 +83,7 @@ where
         let folder_check = self.check_media_folder()?;
         let referenced_files = self.check_media_references(&folder_check.renamed)?;
         let (unused, missing) = find_unused_and_missing(folder_check.files, referenced_files);
+        let repairs = suggest_repairs(&missing, &unused);
         let (trash_count, trash_bytes) = self.files_in_trash()?;
         Ok(MediaCheckOutput {
             unused,
@@ -87,6 +93,7 @@ where
             oversize: folder_check.oversize,
             trash_count,
             trash_bytes,
+            repairs,
         })
     }
 
@@ -111,6 +118,10 @@ where
             buf += &tr.media_check_renamed_count(output.renamed.len());
             buf.push('\n');
         }
+        if !output.repairs.is_empty() {
+            buf += &tr.media_check_repair_count(output.repairs.len());
+            buf.push('\n');
+        }
         if !output.oversize.is_empty() {
             buf += &tr.media_check_oversize_count(output.oversize.len());
             buf.push('\n');
@@ -167,6 +178,20 @@ where
             buf.push('\n')
         }
 
+        if !output.repairs.is_empty() {
+            let mut repairs: Vec<_> = output.repairs.iter().collect();
+            repairs.sort();
+            buf += &tr.media_check_repair_header();
+            buf.push('\n');
+            for (missing, found) in repairs {
+                buf += &without_unicode_isolation(
+                    &tr.media_check_repair_file(missing.as_str(), found.as_str()),
+                );
+                buf.push('\n');
+            }
+            buf.push('\n')
+        }
+
         if !output.unused.is_empty() {
             output.unused.sort();
             buf += &tr.media_check_unused_header();
@@ -387,6 +412,33 @@ where
     }
 }
 
+impl Collection {
+    /// Update references to renamed files in all notes, saving undo entries.
+    /// Returns the number of notes changed.
+    pub(super) fn rename_media_refs_undoable(
+        &mut self,
+        renamed: &HashMap<String, String>,
+        media_folder: &Path,
+    ) -> Result<usize> {
+        let usn = self.usn()?;
+        let mut count = 0;
+        for nid in self.search_notes_unordered("")? {
+            let original = self.storage.get_note(nid)?.or_not_found(nid)?;
+            let mut note = original.clone();
+            if fix_and_extract_media_refs(&mut note, &mut HashSet::new(), renamed, media_folder)? {
+                let nt = self
+                    .get_notetype(note.notetype_id)?
+                    .or_invalid("missing note type")?;
+                self.update_note_inner_without_cards(
+                    &mut note, &original, &nt, usn, true, false, false,
+                )?;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+}
+
 /// Returns true if note was modified.
 fn fix_and_extract_media_refs(
     note: &mut Note,
@@ -571,7 +623,8 @@ pub(crate) mod test {
                 dirs: vec!["folder".to_string()],
                 oversize: vec![],
                 trash_count: 0,
-                trash_bytes: 0
+                trash_bytes: 0,
+                repairs: Default::default(),
             }
         );
 
@@ -691,7 +744,8 @@ Unused: unused.jpg
                     dirs: vec![],
                     oversize: vec![],
                     trash_count: 0,
-                    trash_bytes: 0
+                    trash_bytes: 0,
+                    repairs: Default::default(),
                 }
             );
             assert!(fs::metadata(mgr.media_folder.join("ぱぱ.jpg")).is_ok());
@@ -708,7 +762,8 @@ Unused: unused.jpg
                     dirs: vec![],
                     oversize: vec![],
                     trash_count: 0,
-                    trash_bytes: 0
+                    trash_bytes: 0,
+                    repairs: Default::default(),
                 }
             );
             assert!(fs::metadata(mgr.media_folder.join("ぱぱ.jpg")).is_err());
@@ -0,0 +1,148 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::collections::HashMap;
+
+use crate::media::Sha1Hash;
+use crate::prelude::*;
+
+impl Collection {
+    /// Find media files with identical contents, update notes to refer to a
+    /// single copy, and move the other copies to the trash. Undoing restores
+    /// them from the trash. Returns the number of files trashed.
+    pub fn deduplicate_media(&mut self) -> Result<OpOutput<usize>> {
+        self.transact(Op::DeduplicateMedia, |col| col.deduplicate_media_inner())
+    }
+
+    fn deduplicate_media_inner(&mut self) -> Result<usize> {
+        let mgr = self.media()?;
+        let checksums = mgr.all_checksums_after_checking(|_| true)?;
+        let renamed = duplicates_to_canonical_names(checksums);
+        if renamed.is_empty() {
+            return Ok(0);
+        }
+
+        self.rename_media_refs_undoable(&renamed, &mgr.media_folder)?;
+        let mut trashed: Vec<_> = renamed.into_keys().collect();
+        trashed.sort_unstable();
+        let count = trashed.len();
+        self.trash_media_undoable(&mgr, trashed)?;
+        Ok(count)
+    }
+}
+
+/// Maps the name of each file that should be removed to the name of the copy
+/// that is kept.
+fn duplicates_to_canonical_names(checksums: HashMap<String, Sha1Hash>) -> HashMap<String, String> {
+    let mut by_checksum: HashMap<Sha1Hash, Vec<String>> = HashMap::new();
+    for (fname, sha1) in checksums {
+        by_checksum.entry(sha1).or_default().push(fname);
+    }
+    let mut renamed = HashMap::new();
+    for names in by_checksum.into_values().filter(|names| names.len() > 1) {
+        let canonical = names
+            .iter()
+            .min_by_key(|name| (!is_protected(name), name.len(), name.as_str()))
+            .unwrap()
+            .clone();
+        for name in names {
+            if name != canonical && !is_protected(&name) {
+                renamed.insert(name, canonical.clone());
+            }
+        }
+    }
+    renamed
+}
+
+/// Files used by templates, and generated LaTeX images, are not referenced by
+/// name in note fields, so they are never removed. Copies of them are replaced
+/// with them instead.
+pub(super) fn is_protected(fname: &str) -> bool {
+    fname.starts_with('_') || fname.starts_with("latex-")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::io::read_file;
+    use crate::tests::open_fs_test_collection;
+
+    #[test]
+    fn canonical_names() {
+        let checksums: HashMap<_, _> = [
+            ("a.jpg", [1; 20]),
+            ("a-0102.jpg", [1; 20]),
+            ("b.jpg", [2; 20]),
+            ("_b.jpg", [2; 20]),
+            ("_c.jpg", [3; 20]),
+            ("_d.jpg", [3; 20]),
+        ]
+        .into_iter()
+        .map(|(name, sha1)| (name.to_string(), sha1))
+        .collect();
+        let renamed = duplicates_to_canonical_names(checksums);
+        assert_eq!(
+            renamed,
+            [
+                ("a-0102.jpg".to_string(), "a.jpg".to_string()),
+                ("b.jpg".to_string(), "_b.jpg".to_string()),
+            ]
+            .into_iter()
+            .collect()
+        );
+    }
+
+    #[test]
+    fn duplicates_are_merged() -> Result<()> {
+        let (mut col, _dir) = open_fs_test_collection("dedup");
+        col.add_media(&[
+            ("a.jpg", b"same"),
+            ("a-copy.jpg", b"same"),
+            ("other.jpg", b"different"),
+        ]);
+        let note = col.add_new_note_with_fields(
+            "Basic",
+            &[r#"<img src="a-copy.jpg"><img src="other.jpg">"#, ""],
+        );
+        let mgr = col.media()?;
+
+        assert_eq!(col.deduplicate_media()?.output, 1);
+        let note = col.storage.get_note(note.id)?.unwrap();
+        assert_eq!(
+            note.fields()[0],
+            r#"<img src="a.jpg"><img src="other.jpg">"#
+        );
+        assert!(!mgr.media_folder.join("a-copy.jpg").exists());
+
+        // undoing restores both the reference and the file
+        col.undo()?;
+        let note = col.storage.get_note(note.id)?.unwrap();
+        assert_eq!(
+            note.fields()[0],
+            r#"<img src="a-copy.jpg"><img src="other.jpg">"#
+        );
+        assert!(mgr.media_folder.join("a-copy.jpg").exists());
+
+        // and redoing removes it again
+        col.redo()?;
+        assert!(!mgr.media_folder.join("a-copy.jpg").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn undo_fails_if_a_trashed_name_is_taken() -> Result<()> {
+        let (mut col, _dir) = open_fs_test_collection("dedup");
+        col.add_media(&[("a.jpg", b"same"), ("a-copy.jpg", b"same")]);
+        let note = col.add_new_note_with_fields("Basic", &[r#"<img src="a-copy.jpg">"#, ""]);
+        col.deduplicate_media()?;
+        col.add_media(&[("a-copy.jpg", b"different")]);
+
+        // restoring under another name would break the reference
+        assert!(col.undo().is_err());
+        let note = col.storage.get_note(note.id)?.unwrap();
+        assert_eq!(note.fields()[0], r#"<img src="a.jpg">"#);
+        let folder = col.media()?.media_folder;
+        assert_eq!(read_file(folder.join("a-copy.jpg"))?, b"different");
+        Ok(())
+    }
+}
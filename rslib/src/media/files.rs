@@ -202,7 +202,7 @@ pub(crate) fn add_hash_suffix_to_file_stem(fname: &str, hash: &Sha1Hash) -> Stri
 }
 
 /// If filename is longer than max_bytes, truncate it.
-fn truncate_filename(fname: &str, max_bytes: usize) -> Cow<str> {
+pub(crate) fn truncate_filename(fname: &str, max_bytes: usize) -> Cow<str> {
     if fname.len() <= max_bytes {
         return Cow::Borrowed(fname);
     }
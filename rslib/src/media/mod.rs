@@ -2,7 +2,12 @@
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
 pub mod check;
+pub mod dedup;
 pub mod files;
+pub mod optimize;
+pub mod repair;
+pub(crate) mod undo;
+pub mod usage;
 
 use std::borrow::Cow;
 use std::collections::HashMap;
@@ -0,0 +1,393 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Shrinking large images referenced by notes. Images are re-encoded with
+//! pure-Rust codecs: opaque images are converted to JPEG, and images with
+//! transparency are saved as maximally-compressed PNGs. JPEGs with an EXIF
+//! orientation are skipped, as the decoder does not apply it, and the
+//! re-encoded image would lose it.
+//!
+//! Two conversions are deliberately not done, as they would need C
+//! libraries: WebP output, as the pure-Rust WebP encoder requires a newer
+//! toolchain than ours, and WAV to Opus, as there is no pure-Rust Opus
+//! encoder. Audio files are left alone.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::CompressionType;
+use image::codecs::png::FilterType as PngFilterType;
+use image::codecs::png::PngEncoder;
+use image::imageops::FilterType;
+use image::ColorType;
+use image::DynamicImage;
+use image::ImageEncoder;
+use tracing::debug;
+use tracing::warn;
+
+use crate::io::filename_is_safe;
+use crate::media::dedup::is_protected;
+use crate::media::files::data_for_file;
+use crate::media::MediaManager;
+use crate::prelude::*;
+use crate::text::extract_media_refs;
+use crate::text::normalize_to_nfc;
+use crate::text::REMOTE_FILENAME;
+
+/// Qualities tried in turn, until the image fits in the target size.
+const JPEG_QUALITIES: [u8; 4] = [85, 75, 65, 55];
+const IMAGE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MediaOptimizeOptions {
+    /// Files no larger than this are left alone.
+    pub min_bytes: u64,
+    /// Larger images are scaled down to fit within this many pixels in each
+    /// direction.
+    pub max_dimension: u32,
+    /// The size JPEGs should be brought under. If the lowest quality does not
+    /// reach it, the smallest version is used.
+    pub target_bytes: u64,
+}
+
+impl Default for MediaOptimizeOptions {
+    fn default() -> Self {
+        Self {
+            min_bytes: 300 * 1024,
+            max_dimension: 1920,
+            target_bytes: 300 * 1024,
+        }
+    }
+}
+
+impl Collection {
+    /// Recompress oversized images referenced by the notes matching `search`,
+    /// and update every note referring to them. The originals are moved to
+    /// the trash, and restored on undo. Returns the number of files replaced.
+    pub fn optimize_media(
+        &mut self,
+        search: &str,
+        options: MediaOptimizeOptions,
+        progress: impl FnMut(usize) -> bool,
+    ) -> Result<OpOutput<usize>> {
+        self.transact(Op::OptimizeMedia, |col| {
+            col.optimize_media_inner(search, options, progress)
+        })
+    }
+
+    fn optimize_media_inner(
+        &mut self,
+        search: &str,
+        options: MediaOptimizeOptions,
+        mut progress: impl FnMut(usize) -> bool,
+    ) -> Result<usize> {
+        let mgr = self.media()?;
+        let mut optimized = vec![];
+        for (idx, fname) in self.media_referenced_by(search)?.into_iter().enumerate() {
+            if !progress(idx) {
+                return Err(AnkiError::Interrupted);
+            }
+            if is_protected(&fname) || !filename_is_safe(&fname) {
+                continue;
+            }
+            let Some(data) = data_for_file(&mgr.media_folder, &fname)? else {
+                // missing
+                continue;
+            };
+            if data.len() as u64 <= options.min_bytes {
+                continue;
+            }
+            let Some((ext, data)) = optimize_image(&fname, &data, &options) else {
+                continue;
+            };
+            let desired_name = Path::new(&fname).with_extension(ext);
+            optimized.push((fname, desired_name.to_string_lossy().into_owned(), data));
+        }
+        if optimized.is_empty() {
+            return Ok(0);
+        }
+
+        // nothing is written until every file has been processed, and added
+        // files are removed again on failure, so the media folder is left as
+        // it was if the operation does not complete
+        let mut added = vec![];
+        let res = self.replace_media(&mgr, optimized, &mut added);
+        if res.is_err() && !added.is_empty() {
+            if let Err(err) = mgr.remove_files(&added) {
+                warn!(?err, "unable to remove optimized media");
+            }
+        }
+        res
+    }
+
+    /// Adds the optimized files, points notes at them, and trashes the
+    /// originals. Files that did not exist before are recorded in `added`.
+    fn replace_media(
+        &mut self,
+        mgr: &MediaManager,
+        optimized: Vec<(String, String, Vec<u8>)>,
+        added: &mut Vec<String>,
+    ) -> Result<usize> {
+        let mut renamed = HashMap::new();
+        for (fname, desired_name, data) in optimized {
+            let (new_name, is_new) = self.add_media_undoable(mgr, &desired_name, &data)?;
+            if is_new {
+                added.push(new_name.clone());
+            }
+            debug!(from = fname.as_str(), to = new_name.as_str(), "optimized");
+            renamed.insert(fname, new_name);
+        }
+        self.rename_media_refs_undoable(&renamed, &mgr.media_folder)?;
+        let mut trashed: Vec<_> = renamed.into_keys().collect();
+        trashed.sort_unstable();
+        let count = trashed.len();
+        self.trash_media_undoable(mgr, trashed)?;
+        Ok(count)
+    }
+
+    fn media_referenced_by(&mut self, search: &str) -> Result<Vec<String>> {
+        let mut fnames = HashSet::new();
+        for nid in self.search_notes_unordered(search)? {
+            let note = self.storage.get_note(nid)?.or_not_found(nid)?;
+            for field in note.fields() {
+                for media_ref in extract_media_refs(field) {
+                    if !REMOTE_FILENAME.is_match(media_ref.fname) {
+                        fnames.insert(normalize_to_nfc(&media_ref.fname_decoded).into_owned());
+                    }
+                }
+            }
+        }
+        let mut fnames: Vec<_> = fnames.into_iter().collect();
+        fnames.sort_unstable();
+        Ok(fnames)
+    }
+}
+
+/// Returns the new extension and data, if the image could be made smaller.
+fn optimize_image(
+    fname: &str,
+    data: &[u8],
+    options: &MediaOptimizeOptions,
+) -> Option<(&'static str, Vec<u8>)> {
+    let ext = Path::new(fname).extension()?.to_str()?.to_ascii_lowercase();
+    if !IMAGE_EXTENSIONS.contains(&ext.as_str()) {
+        return None;
+    }
+    if matches!(jpeg_orientation(data), Some(orientation) if orientation != 1) {
+        debug!(fname, "skipping rotated image");
+        return None;
+    }
+    let mut img = match image::load_from_memory(data) {
+        Ok(img) => img,
+        Err(err) => {
+            debug!(fname, ?err, "unable to decode image");
+            return None;
+        }
+    };
+    if img.width() > options.max_dimension || img.height() > options.max_dimension {
+        img = img.resize(
+            options.max_dimension,
+            options.max_dimension,
+            FilterType::Lanczos3,
+        );
+    }
+    let (ext, optimized) = if is_transparent(&img) {
+        ("png", encode_png(&img)?)
+    } else {
+        ("jpg", encode_jpeg(&img, options.target_bytes)?)
+    };
+    (optimized.len() < data.len()).then_some((ext, optimized))
+}
+
+/// The EXIF orientation of a JPEG, if it has one.
+fn jpeg_orientation(data: &[u8]) -> Option<u16> {
+    if data.get(..2)? != [0xff, 0xd8] {
+        return None;
+    }
+    let mut pos = 2;
+    loop {
+        let header = data.get(pos..pos + 4)?;
+        // metadata comes before the start of scan
+        if header[0] != 0xff || header[1] == 0xda {
+            return None;
+        }
+        let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let segment = data.get(pos + 4..pos + 2 + len)?;
+        if header[1] == 0xe1 {
+            if let Some(tiff) = segment.strip_prefix(b"Exif\0\0") {
+                return tiff_orientation(tiff);
+            }
+        }
+        pos += 2 + len;
+    }
+}
+
+/// Reads the orientation tag from the first IFD of EXIF data.
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let read_u16 = |offset: usize| {
+        let bytes = [*tiff.get(offset)?, *tiff.get(offset + 1)?];
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let read_u32 = |offset: usize| {
+        let high = read_u16(offset)? as u32;
+        let low = read_u16(offset + 2)? as u32;
+        Some(if big_endian {
+            (high << 16) | low
+        } else {
+            (low << 16) | high
+        })
+    };
+    let ifd = read_u32(4)? as usize;
+    (0..read_u16(ifd)? as usize)
+        .map(|idx| ifd + 2 + idx * 12)
+        .find(|&entry| read_u16(entry) == Some(0x0112))
+        .and_then(|entry| read_u16(entry + 8))
+}
+
+/// Many PNGs have an alpha channel that is fully opaque.
+fn is_transparent(img: &DynamicImage) -> bool {
+    img.color().has_alpha() && img.to_rgba8().pixels().any(|pixel| pixel[3] < u8::MAX)
+}
+
+fn encode_png(img: &DynamicImage) -> Option<Vec<u8>> {
+    let img = img.to_rgba8();
+    let mut out = vec![];
+    PngEncoder::new_with_quality(&mut out, CompressionType::Best, PngFilterType::Adaptive)
+        .write_image(img.as_raw(), img.width(), img.height(), ColorType::Rgba8)
+        .ok()?;
+    Some(out)
+}
+
+fn encode_jpeg(img: &DynamicImage, target_bytes: u64) -> Option<Vec<u8>> {
+    let img = img.to_rgb8();
+    let mut out = vec![];
+    for quality in JPEG_QUALITIES {
+        out.clear();
+        JpegEncoder::new_with_quality(&mut out, quality)
+            .encode_image(&img)
+            .ok()?;
+        if out.len() as u64 <= target_bytes {
+            break;
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod test {
+    use image::Rgba;
+    use image::RgbaImage;
+
+    use super::*;
+    use crate::tests::open_fs_test_collection;
+
+    /// A noisy image, so it doesn't compress well as a PNG.
+    fn png_data(width: u32, height: u32, alpha: u8) -> Vec<u8> {
+        let img = RgbaImage::from_fn(width, height, |x, y| {
+            let v =
+                (x.wrapping_mul(0x9e37_79b9) ^ y.wrapping_mul(0x85eb_ca6b)).rotate_left(13) as u8;
+            Rgba([v, v.wrapping_mul(3), v.wrapping_mul(5), alpha])
+        });
+        let mut out = vec![];
+        PngEncoder::new(&mut out)
+            .write_image(img.as_raw(), width, height, ColorType::Rgba8)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn images_are_shrunk() {
+        let options = MediaOptimizeOptions {
+            min_bytes: 0,
+            max_dimension: 100,
+            target_bytes: 10 * 1024,
+        };
+        let data = png_data(400, 200, u8::MAX);
+        let (ext, out) = optimize_image("a.png", &data, &options).unwrap();
+        assert_eq!(ext, "jpg");
+        let img = image::load_from_memory(&out).unwrap();
+        assert_eq!((img.width(), img.height()), (100, 50));
+
+        // transparency is preserved
+        let data = png_data(400, 200, 128);
+        let (ext, _) = optimize_image("a.png", &data, &options).unwrap();
+        assert_eq!(ext, "png");
+
+        // other files are ignored
+        assert_eq!(optimize_image("a.mp3", &data, &options), None);
+    }
+
+    /// Adds an EXIF segment with the given orientation to a JPEG.
+    fn with_orientation(jpeg: &[u8], orientation: u16) -> Vec<u8> {
+        let mut segment = b"Exif\0\0II*\0\x08\0\0\0\x01\0\x12\x01\x03\0\x01\0\0\0".to_vec();
+        segment.extend(orientation.to_le_bytes());
+        segment.extend([0; 6]);
+        let mut out = jpeg[..2].to_vec();
+        out.extend([0xff, 0xe1]);
+        out.extend((segment.len() as u16 + 2).to_be_bytes());
+        out.extend(segment);
+        out.extend(&jpeg[2..]);
+        out
+    }
+
+    #[test]
+    fn rotated_jpegs_are_skipped() {
+        let options = MediaOptimizeOptions {
+            min_bytes: 0,
+            max_dimension: 100,
+            target_bytes: 10 * 1024,
+        };
+        let img = image::load_from_memory(&png_data(400, 200, u8::MAX)).unwrap();
+        let jpeg = encode_jpeg(&img, u64::MAX).unwrap();
+        assert_eq!(jpeg_orientation(&jpeg), None);
+        let rotated = with_orientation(&jpeg, 6);
+        assert_eq!(jpeg_orientation(&rotated), Some(6));
+        assert_eq!(optimize_image("a.jpg", &rotated, &options), None);
+        // the default orientation needs no rotating
+        assert!(optimize_image("a.jpg", &with_orientation(&jpeg, 1), &options).is_some());
+    }
+
+    #[test]
+    fn references_are_updated() -> Result<()> {
+        let (mut col, _dir) = open_fs_test_collection("optimize");
+        col.add_media(&[("big.png", &png_data(400, 200, u8::MAX))]);
+        let note = col.add_new_note_with_fields("Basic", &[r#"<img src="big.png">"#, ""]);
+        let mgr = col.media()?;
+
+        let options = MediaOptimizeOptions {
+            min_bytes: 0,
+            max_dimension: 100,
+            ..Default::default()
+        };
+        assert_eq!(col.optimize_media("", options, |_| true)?.output, 1);
+        let note = col.storage.get_note(note.id)?.unwrap();
+        assert_eq!(note.fields()[0], r#"<img src="big.jpg">"#);
+        assert!(!mgr.media_folder.join("big.png").exists());
+
+        col.undo()?;
+        let note = col.storage.get_note(note.id)?.unwrap();
+        assert_eq!(note.fields()[0], r#"<img src="big.png">"#);
+        assert!(mgr.media_folder.join("big.png").exists());
+        assert!(!mgr.media_folder.join("big.jpg").exists());
+
+        // nothing is written if the operation is interrupted
+        col.add_media(&[("big2.png", &png_data(400, 200, u8::MAX))]);
+        col.add_new_note_with_fields("Basic", &[r#"<img src="big2.png">"#, ""]);
+        let err = col.optimize_media("", options, |idx| idx < 1).unwrap_err();
+        assert_eq!(err, AnkiError::Interrupted);
+        assert!(!mgr.media_folder.join("big.jpg").exists());
+        assert!(!mgr.media_folder.join("big2.jpg").exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Matching missing files to unused ones that are probably what was intended.
+//! A file's name may have been changed when it was added or synced, by having
+//! a hash appended to make it unique, being truncated, or being normalized,
+//! and on case-insensitive filesystems a reference may differ only in case.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::media::files::add_hash_suffix_to_file_stem;
+use crate::media::files::truncate_filename;
+use crate::prelude::*;
+use crate::sync::media::MAX_MEDIA_FILENAME_LENGTH;
+
+lazy_static! {
+    /// The stem and extension of a name produced by
+    /// add_hash_suffix_to_file_stem().
+    static ref HASH_SUFFIX: Regex = Regex::new(r"^(.*)-[0-9a-f]{40}\.([^.]*)$").unwrap();
+}
+
+impl Collection {
+    /// Update notes referring to each missing file to refer to its suggested
+    /// replacement instead. Returns the number of notes changed.
+    pub fn repair_media_references(
+        &mut self,
+        repairs: &HashMap<String, String>,
+    ) -> Result<OpOutput<usize>> {
+        let media_folder = self.media_folder.clone();
+        self.transact(Op::RepairMediaReferences, |col| {
+            col.rename_media_refs_undoable(repairs, &media_folder)
+        })
+    }
+}
+
+/// Maps each missing file that can be matched to a single unused file to
+/// that file.
+pub(super) fn suggest_repairs(missing: &[String], unused: &[String]) -> HashMap<String, String> {
+    let mut candidates: HashMap<String, Vec<&str>> = HashMap::new();
+    for fname in unused {
+        let mut keys = vec![match_key(fname)];
+        if let Some(caps) = HASH_SUFFIX.captures(fname) {
+            keys.push(match_key(&format!("{}.{}", &caps[1], &caps[2])));
+        }
+        for key in keys {
+            candidates.entry(key).or_default().push(fname);
+        }
+    }
+
+    let mut repairs = HashMap::new();
+    for fname in missing {
+        let mut keys = vec![
+            match_key(fname),
+            match_key(&truncate_filename(fname, MAX_MEDIA_FILENAME_LENGTH)),
+        ];
+        // the hash itself can't be recovered, so the stem as truncated by
+        // add_hash_suffix_to_file_stem() is matched instead
+        if let Some(caps) = HASH_SUFFIX.captures(&add_hash_suffix_to_file_stem(fname, &[0; 20])) {
+            keys.push(match_key(&format!("{}.{}", &caps[1], &caps[2])));
+        }
+        let matches: HashSet<&str> = keys
+            .iter()
+            .filter_map(|key| candidates.get(key))
+            .flatten()
+            .copied()
+            .collect();
+        if matches.len() == 1 {
+            repairs.insert(fname.clone(), matches.into_iter().next().unwrap().into());
+        }
+    }
+    repairs
+}
+
+/// Names that differ only in case or Unicode normalization have the same
+/// key.
+fn match_key(fname: &str) -> String {
+    fname.nfkc().collect::<String>().to_lowercase()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::media::check::MediaChecker;
+    use crate::tests::open_fs_test_collection;
+
+    fn repairs(missing: &[&str], unused: &[&str]) -> Vec<(String, String)> {
+        let to_vec = |names: &[&str]| names.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let mut repairs: Vec<_> = suggest_repairs(&to_vec(missing), &to_vec(unused))
+            .into_iter()
+            .collect();
+        repairs.sort();
+        repairs
+    }
+
+    #[test]
+    fn matching() {
+        // case and normalization
+        assert_eq!(
+            repairs(&["Photo.JPG", "ｆｕｌｌ.jpg"], &["photo.jpg", "full.jpg"]),
+            [
+                ("Photo.JPG".to_string(), "photo.jpg".to_string()),
+                ("ｆｕｌｌ.jpg".into(), "full.jpg".into())
+            ]
+        );
+        // hash suffix
+        let hashed = add_hash_suffix_to_file_stem("a.mp3", &[1; 20]);
+        assert_eq!(
+            repairs(&["a.mp3"], &[&hashed]),
+            [("a.mp3".to_string(), hashed.clone())]
+        );
+        // truncation, including of long names with a hash suffix
+        let long = format!("{}.png", "x".repeat(200));
+        let truncated = truncate_filename(&long, MAX_MEDIA_FILENAME_LENGTH).into_owned();
+        assert_eq!(
+            repairs(&[&long], &[&truncated]),
+            [(long.clone(), truncated)]
+        );
+        let hashed = add_hash_suffix_to_file_stem(&long, &[2; 20]);
+        assert_eq!(repairs(&[&long], &[&hashed]), [(long, hashed)]);
+        // ambiguous and unrelated names are left alone
+        assert!(repairs(&["b.jpg"], &["B.jpg", "b.JPG"]).is_empty());
+        assert!(repairs(&["c.jpg"], &["d.jpg"]).is_empty());
+    }
+
+    #[test]
+    fn references_are_repaired() -> Result<()> {
+        let (mut col, _dir) = open_fs_test_collection("repair");
+        let hashed = add_hash_suffix_to_file_stem("photo.jpg", &[1; 20]);
+        col.add_media(&[(&hashed, b"data")]);
+        let note = col.add_new_note_with_fields("Basic", &[r#"<img src="photo.jpg">"#, ""]);
+        let mgr = col.media()?;
+
+        let output = MediaChecker::new(&mut col, &mgr, |_| true).check()?;
+        assert_eq!(
+            output.repairs,
+            [("photo.jpg".to_string(), hashed.clone())]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(col.repair_media_references(&output.repairs)?.output, 1);
+        let note = col.storage.get_note(note.id)?.unwrap();
+        assert_eq!(note.fields()[0], format!(r#"<img src="{hashed}">"#));
+
+        col.undo()?;
+        let note = col.storage.get_note(note.id)?.unwrap();
+        assert_eq!(note.fields()[0], r#"<img src="photo.jpg">"#);
+        Ok(())
+    }
+}
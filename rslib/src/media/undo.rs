@@ -0,0 +1,89 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use tracing::debug;
+
+use crate::io::read_file;
+use crate::io::remove_file;
+use crate::media::files::sha1_of_file;
+use crate::media::files::trash_folder;
+use crate::media::MediaManager;
+use crate::prelude::*;
+
+/// Media files live outside the collection, so undoing a removal relies on
+/// the files still being in the trash.
+#[derive(Debug)]
+pub(crate) enum UndoableMediaChange {
+    Trashed(Vec<String>),
+    Added(Vec<String>),
+}
+
+impl Collection {
+    pub(crate) fn undo_media_change(&mut self, change: UndoableMediaChange) -> Result<()> {
+        let mgr = self.media()?;
+        match change {
+            UndoableMediaChange::Trashed(fnames) => self.restore_media_undoable(&mgr, fnames),
+            UndoableMediaChange::Added(fnames) => self.trash_media_undoable(&mgr, fnames),
+        }
+    }
+
+    /// Moves the files to the trash, saving an undo entry.
+    pub(crate) fn trash_media_undoable(
+        &mut self,
+        mgr: &MediaManager,
+        fnames: Vec<String>,
+    ) -> Result<()> {
+        mgr.remove_files(&fnames)?;
+        self.save_undo(UndoableMediaChange::Trashed(fnames));
+        Ok(())
+    }
+
+    /// Moves the files back from the trash, saving an undo entry. Files no
+    /// longer in the trash are skipped. Notes refer to the files by name, so
+    /// if a different file has since been given one of the names, nothing
+    /// is restored, and the undo fails.
+    fn restore_media_undoable(&mut self, mgr: &MediaManager, fnames: Vec<String>) -> Result<()> {
+        let trash = trash_folder(&mgr.media_folder)?;
+        let mut trashed = vec![];
+        for fname in fnames {
+            let path = trash.join(&fname);
+            if !path.exists() {
+                debug!(fname, "media missing from trash");
+                continue;
+            }
+            let existing = mgr.media_folder.join(&fname);
+            if existing.exists() && sha1_of_file(&existing)? != sha1_of_file(&path)? {
+                invalid_input!("{fname} has been replaced by a different file");
+            }
+            trashed.push((fname, path));
+        }
+        let mut restored = vec![];
+        for (fname, path) in trashed {
+            let data = read_file(&path)?;
+            let added = mgr.add_file(&fname, &data)?;
+            remove_file(&path)?;
+            restored.push(added.into_owned());
+        }
+        self.save_undo(UndoableMediaChange::Added(restored));
+        Ok(())
+    }
+
+    /// Adds a file to the media folder, saving an undo entry. Returns the
+    /// name it was given, which will differ from the desired one if a
+    /// different file already has that name, and whether the file is new.
+    pub(crate) fn add_media_undoable(
+        &mut self,
+        mgr: &MediaManager,
+        desired_name: &str,
+        data: &[u8],
+    ) -> Result<(String, bool)> {
+        let existed = mgr.media_folder.join(desired_name).exists();
+        let fname = mgr.add_file(desired_name, data)?.into_owned();
+        // an identical file was already present, and must be kept on undo
+        let is_new = !(existed && fname == desired_name);
+        if is_new {
+            self.save_undo(UndoableMediaChange::Added(vec![fname.clone()]));
+        }
+        Ok((fname, is_new))
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Which notes and decks the space in the media folder is used by.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::prelude::*;
+use crate::text::extract_media_refs;
+use crate::text::normalize_to_nfc;
+use crate::text::REMOTE_FILENAME;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaReference {
+    pub note_id: NoteId,
+    /// The name of the field the reference is in.
+    pub field: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaFileUsage {
+    pub fname: String,
+    /// None if the file is referenced, but missing from the media folder.
+    pub size: Option<u64>,
+    pub references: Vec<MediaReference>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MediaUsage {
+    /// Every file in the media folder, and every missing file that is
+    /// referenced, sorted by name.
+    pub files: Vec<MediaFileUsage>,
+    /// The total size of the files each note refers to. A file is only
+    /// counted once per note, however often it is referenced.
+    pub note_bytes: HashMap<NoteId, u64>,
+    /// The total size of the files referred to by notes with cards in each
+    /// deck, counting each file once per deck. Cards in filtered decks count
+    /// towards their home deck.
+    pub deck_bytes: HashMap<DeckId, u64>,
+}
+
+impl Collection {
+    /// Build a report of the space used by each media file, note and deck.
+    /// Notes without any media are not included.
+    pub fn media_usage(&mut self, mut progress: impl FnMut(usize) -> bool) -> Result<MediaUsage> {
+        let mut files = files_in_media_folder(&self.media_folder)?;
+        let notetypes = self.get_all_notetypes()?;
+        let mut note_files: HashMap<NoteId, HashSet<String>> = HashMap::new();
+        for (idx, nid) in self.search_notes_unordered("")?.into_iter().enumerate() {
+            if idx % 10 == 0 && !progress(idx) {
+                return Err(AnkiError::Interrupted);
+            }
+            let note = self.storage.get_note(nid)?.or_not_found(nid)?;
+            let nt = notetypes
+                .get(&note.notetype_id)
+                .or_not_found(note.notetype_id)?;
+            for (field, text) in nt.fields.iter().zip(note.fields()) {
+                for media_ref in extract_media_refs(text) {
+                    if REMOTE_FILENAME.is_match(media_ref.fname) {
+                        continue;
+                    }
+                    let fname = normalize_to_nfc(&media_ref.fname_decoded).into_owned();
+                    files
+                        .entry(fname.clone())
+                        .or_insert_with(|| MediaFileUsage {
+                            fname: fname.clone(),
+                            size: None,
+                            references: vec![],
+                        })
+                        .references
+                        .push(MediaReference {
+                            note_id: nid,
+                            field: field.name.clone(),
+                        });
+                    note_files.entry(nid).or_default().insert(fname);
+                }
+            }
+        }
+
+        let size_of = |fnames: &HashSet<String>| -> u64 {
+            fnames
+                .iter()
+                .filter_map(|fname| files.get(fname).and_then(|file| file.size))
+                .sum()
+        };
+        let mut deck_files: HashMap<DeckId, HashSet<String>> = HashMap::new();
+        for (nid, did) in self.storage.all_note_home_decks()? {
+            if let Some(fnames) = note_files.get(&nid) {
+                deck_files
+                    .entry(did)
+                    .or_default()
+                    .extend(fnames.iter().cloned());
+            }
+        }
+
+        Ok(MediaUsage {
+            note_bytes: note_files
+                .iter()
+                .map(|(&nid, fnames)| (nid, size_of(fnames)))
+                .collect(),
+            deck_bytes: deck_files
+                .iter()
+                .map(|(&did, fnames)| (did, size_of(fnames)))
+                .collect(),
+            files: files.into_values().collect(),
+        })
+    }
+}
+
+fn files_in_media_folder(media_folder: &Path) -> Result<BTreeMap<String, MediaFileUsage>> {
+    let mut files = BTreeMap::new();
+    for dentry in media_folder.read_dir()? {
+        let dentry = dentry?;
+        let Some(fname) = dentry.file_name().to_str().map(ToString::to_string) else {
+            continue;
+        };
+        let metadata = dentry.metadata()?;
+        if metadata.is_dir() {
+            continue;
+        }
+        files.insert(
+            fname.clone(),
+            MediaFileUsage {
+                fname,
+                size: Some(metadata.len()),
+                references: vec![],
+            },
+        );
+    }
+    Ok(files)
+}
+
+/// The total size of the distinct files a note refers to. Missing files are
+/// ignored.
+pub(crate) fn note_media_bytes(note: &Note, media_folder: &Path) -> u64 {
+    note.fields()
+        .iter()
+        .flat_map(|field| extract_media_refs(field))
+        .filter(|media_ref| !REMOTE_FILENAME.is_match(media_ref.fname))
+        .map(|media_ref| normalize_to_nfc(&media_ref.fname_decoded).into_owned())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .filter_map(|fname| fs::metadata(media_folder.join(fname)).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tests::open_fs_test_collection;
+
+    #[test]
+    fn usage_is_reported() -> Result<()> {
+        let (mut col, _dir) = open_fs_test_collection("usage");
+        col.add_media(&[
+            ("a.jpg", b"1234"),
+            ("b.mp3", b"12345678"),
+            ("unused.jpg", b"12"),
+        ]);
+        let note1 = col.add_new_note_with_fields(
+            "Basic",
+            &[
+                r#"<img src="a.jpg"><img src="a.jpg">"#,
+                "[sound:b.mp3]<img src=missing.png>",
+            ],
+        );
+        let deck = col.get_or_create_normal_deck("other")?;
+        let mut note2 = col.new_note("Basic");
+        note2.set_field(0, r#"<img src="a.jpg">"#)?;
+        col.add_note(&mut note2, deck.id)?;
+
+        let usage = col.media_usage(|_| true)?;
+        let summary: Vec<_> = usage
+            .files
+            .iter()
+            .map(|file| (file.fname.as_str(), file.size, file.references.len()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("a.jpg", Some(4), 3),
+                ("b.mp3", Some(8), 1),
+                ("missing.png", None, 1),
+                ("unused.jpg", Some(2), 0),
+            ]
+        );
+        assert_eq!(
+            usage.files[1].references,
+            [MediaReference {
+                note_id: note1.id,
+                field: "Back".into()
+            }]
+        );
+        assert_eq!(usage.note_bytes[&note1.id], 12);
+        assert_eq!(usage.note_bytes[&note2.id], 4);
+        assert_eq!(usage.deck_bytes[&DeckId(1)], 12);
+        assert_eq!(usage.deck_bytes[&deck.id], 4);
+
+        let note = col.storage.get_note(note1.id)?.unwrap();
+        assert_eq!(note_media_bytes(&note, &col.media()?.media_folder), 12);
+        Ok(())
+    }
+}
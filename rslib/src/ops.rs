@@ -15,9 +15,11 @@ pub enum Op {
     ChangeNotetype,
     ClearUnusedTags,
     CreateCustomStudy,
+    DeduplicateMedia,
     EmptyFilteredDeck,
     FindAndReplace,
     Import,
+    OptimizeMedia,
     RebuildFilteredDeck,
     RemoveDeck,
     RemoveNote,
@@ -27,11 +29,15 @@ pub enum Op {
     ReparentDeck,
     RenameTag,
     ReparentTag,
+    RepairMediaReferences,
+    RescheduleByRetention,
+    RestoreSyncConflicts,
     ScheduleAsNew,
     SetCardDeck,
     SetDueDate,
     SetFlag,
     SortCards,
+    SpreadOverdueReviews,
     Suspend,
     UnburyUnsuspend,
     UpdateCard,
@@ -56,12 +62,18 @@ impl Op {
             Op::AnswerCard => tr.actions_answer_card(),
             Op::Bury => tr.studying_bury(),
             Op::CreateCustomStudy => tr.actions_custom_study(),
+            Op::DeduplicateMedia => tr.actions_deduplicate_media(),
             Op::Import => tr.actions_import(),
+            Op::OptimizeMedia => tr.actions_optimize_media(),
             Op::RemoveDeck => tr.decks_delete_deck(),
             Op::RemoveNote => tr.studying_delete_note(),
             Op::RenameDeck => tr.actions_rename_deck(),
+            Op::RepairMediaReferences => tr.actions_repair_media_references(),
             Op::ScheduleAsNew => tr.actions_forget_card(),
             Op::SetDueDate => tr.actions_set_due_date(),
+            Op::RescheduleByRetention => tr.actions_reschedule_by_retention(),
+            Op::RestoreSyncConflicts => tr.actions_restore_sync_conflicts(),
+            Op::SpreadOverdueReviews => tr.actions_spread_overdue_reviews(),
             Op::Suspend => tr.studying_suspend(),
             Op::UnburyUnsuspend => tr.actions_unbury_unsuspend(),
             Op::UpdateCard => tr.actions_update_card(),
@@ -48,7 +48,9 @@ pub struct RevlogEntry {
     #[serde(rename = "lastIvl", deserialize_with = "deserialize_int_from_number")]
     pub last_interval: i32,
     /// Card's ease after answering, stored as 10x the %, eg 2500 represents
-    /// 250%.
+    /// 250%. 0 if the memory model scheduled the card, which has no ease. The
+    /// algorithm is recorded in the revlog_algorithms table, which is synced
+    /// alongside the entries; see `SqliteStorage::revlog_algorithm`.
     #[serde(rename = "factor", deserialize_with = "deserialize_int_from_number")]
     pub ease_factor: u32,
     /// Amount of milliseconds taken to answer the card.
@@ -69,6 +71,9 @@ pub enum RevlogReviewKind {
     /// rescheduling disabled.
     Filtered = 3,
     Manual = 4,
+    /// The due date was recomputed from the card's memory state, eg by
+    /// rescheduling cards to a target retention.
+    Rescheduled = 5,
 }
 
 impl Default for RevlogReviewKind {
@@ -94,6 +99,25 @@ impl Collection {
         card: &Card,
         original: &Card,
         usn: Usn,
+    ) -> Result<()> {
+        self.log_scheduling_change(card, original, usn, RevlogReviewKind::Manual)
+    }
+
+    pub(crate) fn log_rescheduled_review(
+        &mut self,
+        card: &Card,
+        original: &Card,
+        usn: Usn,
+    ) -> Result<()> {
+        self.log_scheduling_change(card, original, usn, RevlogReviewKind::Rescheduled)
+    }
+
+    fn log_scheduling_change(
+        &mut self,
+        card: &Card,
+        original: &Card,
+        usn: Usn,
+        review_kind: RevlogReviewKind,
     ) -> Result<()> {
         let entry = RevlogEntry {
             id: RevlogId::new(),
@@ -104,7 +128,7 @@ impl Collection {
             last_interval: i32::try_from(original.interval).unwrap_or(i32::MAX),
             ease_factor: u32::from(card.ease_factor),
             taken_millis: 0,
-            review_kind: RevlogReviewKind::Manual,
+            review_kind,
         };
         self.add_revlog_entry_undoable(entry)?;
         Ok(())
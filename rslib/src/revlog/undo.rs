@@ -3,11 +3,14 @@
 
 use super::RevlogEntry;
 use crate::prelude::*;
+use crate::scheduler::states::SchedulingAlgorithmKind;
 
 #[derive(Debug)]
 pub(crate) enum UndoableRevlogChange {
     Added(Box<RevlogEntry>),
     Removed(Box<RevlogEntry>),
+    AlgorithmSet(RevlogId, SchedulingAlgorithmKind),
+    AlgorithmRemoved(RevlogId, SchedulingAlgorithmKind),
 }
 
 impl Collection {
@@ -23,9 +26,33 @@ impl Collection {
                 self.save_undo(UndoableRevlogChange::Added(revlog));
                 Ok(())
             }
+            UndoableRevlogChange::AlgorithmSet(id, algorithm) => {
+                self.storage.remove_revlog_algorithm(id)?;
+                self.save_undo(UndoableRevlogChange::AlgorithmRemoved(id, algorithm));
+                Ok(())
+            }
+            UndoableRevlogChange::AlgorithmRemoved(id, algorithm) => {
+                self.storage.set_revlog_algorithm(id, algorithm)?;
+                self.save_undo(UndoableRevlogChange::AlgorithmSet(id, algorithm));
+                Ok(())
+            }
         }
     }
 
+    /// Record the algorithm that produced the entry's interval. Nothing is
+    /// stored for SM-2, which is assumed when no algorithm is recorded.
+    pub(crate) fn set_revlog_algorithm_undoable(
+        &mut self,
+        id: RevlogId,
+        algorithm: SchedulingAlgorithmKind,
+    ) -> Result<()> {
+        if algorithm != SchedulingAlgorithmKind::Sm2 {
+            self.storage.set_revlog_algorithm(id, algorithm)?;
+            self.save_undo(UndoableRevlogChange::AlgorithmSet(id, algorithm));
+        }
+        Ok(())
+    }
+
     /// Add the provided revlog entry, modifying the ID if it is not unique.
     pub(crate) fn add_revlog_entry_undoable(&mut self, mut entry: RevlogEntry) -> Result<RevlogId> {
         entry.id = self.storage.add_revlog_entry(&entry, true)?.unwrap();
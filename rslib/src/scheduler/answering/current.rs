@@ -82,6 +82,7 @@ impl CardStateUpdater {
                 ease_factor,
                 lapses,
                 leeched: false,
+                memory_state: self.card.memory_state,
             }
             .into(),
             CardType::Relearn => RelearnState {
@@ -95,6 +96,7 @@ impl CardStateUpdater {
                     ease_factor,
                     lapses,
                     leeched: false,
+                    memory_state: self.card.memory_state,
                 },
             }
             .into(),
@@ -25,7 +25,7 @@ impl CardStateUpdater {
         self.card.due = next.position as i32;
         self.card.original_position = None;
 
-        RevlogEntryPartial::new(current, next.into(), 0.0, self.secs_until_rollover())
+        RevlogEntryPartial::new(current, next.into(), 0, self.secs_until_rollover())
     }
 
     pub(super) fn apply_learning_state(
@@ -53,7 +53,7 @@ impl CardStateUpdater {
             }
         }
 
-        RevlogEntryPartial::new(current, next.into(), 0.0, self.secs_until_rollover())
+        RevlogEntryPartial::new(current, next.into(), 0, self.secs_until_rollover())
     }
 
     /// Adds secs + fuzz to current time
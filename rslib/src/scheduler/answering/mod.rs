@@ -8,14 +8,18 @@ mod relearning;
 mod review;
 mod revlog;
 
+use chrono::NaiveDate;
 use rand::prelude::*;
 use rand::rngs::StdRng;
 use revlog::RevlogEntryPartial;
 
+use super::states::fuzz::EasyDays;
+use super::states::fuzz::LoadBalancer;
 use super::states::steps::LearningSteps;
 use super::states::CardState;
 use super::states::FilteredState;
 use super::states::NormalState;
+use super::states::SchedulingAlgorithmKind;
 use super::states::SchedulingStates;
 use super::states::StateContext;
 use super::timespan::answer_button_time_collapsible;
@@ -60,6 +64,9 @@ struct CardStateUpdater {
     timing: SchedTimingToday,
     now: TimestampSecs,
     fuzz_seed: Option<u64>,
+    load_balancer: Option<LoadBalancer>,
+    /// Only set if the config has easy days.
+    today: Option<NaiveDate>,
 }
 
 impl CardStateUpdater {
@@ -69,6 +76,15 @@ impl CardStateUpdater {
     pub(crate) fn state_context(&self) -> StateContext<'_> {
         StateContext {
             fuzz_factor: get_fuzz_factor(self.fuzz_seed),
+            load_balancer: self.load_balancer.as_ref(),
+            easy_days: self.today.and_then(|today| {
+                EasyDays::new(
+                    &self.config.inner.easy_days_percentages,
+                    &self.config.inner.easy_dates,
+                    today,
+                )
+            }),
+            algorithm: self.config.inner.scheduling_algorithm().algorithm(),
             steps: self.learn_steps(),
             graduating_interval_good: self.config.inner.graduating_interval_good,
             graduating_interval_easy: self.config.inner.graduating_interval_easy,
@@ -81,6 +97,7 @@ impl CardStateUpdater {
             relearn_steps: self.relearn_steps(),
             lapse_multiplier: self.config.inner.lapse_multiplier,
             minimum_lapse_interval: self.config.inner.minimum_lapse_interval,
+            desired_retention: self.config.inner.desired_retention,
             in_filtered_deck: self.deck.is_filtered(),
             preview_step: if let DeckKind::Filtered(deck) = &self.deck.kind {
                 deck.preview_delay
@@ -189,10 +206,23 @@ impl Collection {
     /// Return the next states that will be applied for each answer button.
     pub fn get_scheduling_states(&mut self, cid: CardId) -> Result<SchedulingStates> {
         let card = self.storage.get_card(cid)?.or_not_found(cid)?;
-        let ctx = self.card_state_updater(card)?;
+        let mut ctx = self.card_state_updater(card)?;
         let current = ctx.current_card_state();
-        let state_ctx = ctx.state_context();
-        Ok(current.next_states(&state_ctx))
+        let mut load_balancer = ctx.config.inner.load_balancer.then(LoadBalancer::default);
+        loop {
+            ctx.load_balancer = load_balancer;
+            let states = current.next_states(&ctx.state_context());
+            load_balancer = ctx.load_balancer.take();
+            // the days the fuzz ranges fall on depend on the states, so they
+            // are calculated again once the due counts of those days are known
+            let Some(days) = load_balancer.as_ref().and_then(LoadBalancer::days_to_load) else {
+                return Ok(states);
+            };
+            let due_counts =
+                self.storage
+                    .review_due_counts(ctx.timing.days_elapsed, cid, days.clone())?;
+            load_balancer = Some(LoadBalancer::new(due_counts, days));
+        }
     }
 
     /// Describe the next intervals, to display on the answer buttons.
@@ -266,12 +296,20 @@ impl Collection {
         );
 
         let revlog_partial = updater.apply_study_state(current_state, answer.new_state)?;
-        self.add_partial_revlog(revlog_partial, usn, answer)?;
+        self.add_partial_revlog(
+            revlog_partial,
+            usn,
+            answer,
+            updater.config.inner.scheduling_algorithm(),
+        )?;
 
         self.update_deck_stats_from_answer(usn, answer, &updater, original.queue)?;
         self.maybe_bury_siblings(&original, &updater.config)?;
         let timing = updater.timing;
+        let sibling_spacing = updater.config.inner.sibling_spacing_days;
         let mut card = updater.into_card();
+        let spaced_queued_sibling =
+            self.maybe_space_siblings(&card, sibling_spacing, timing.days_elapsed, usn)?;
         if let Some(data) = answer.custom_data.take() {
             card.custom_data = data;
             card.validate_custom_data()?;
@@ -281,9 +319,55 @@ impl Collection {
             self.add_leech_tag(card.note_id)?;
         }
 
+        if spaced_queued_sibling {
+            // the queues hold a sibling that is no longer due
+            self.clear_study_queues();
+        }
         self.update_queues_after_answering_card(&card, timing)
     }
 
+    /// Postpone review siblings due within `spacing` days of today or of the
+    /// answered card's new due date, so that they are not seen shortly before
+    /// or after it. As with setting a due date, their intervals are kept, and
+    /// the move is logged. Returns true if a sibling that was due today or
+    /// earlier was moved.
+    fn maybe_space_siblings(
+        &mut self,
+        card: &Card,
+        spacing: u32,
+        today: u32,
+        usn: Usn,
+    ) -> Result<bool> {
+        if spacing == 0 {
+            return Ok(false);
+        }
+        let (spacing, today) = (spacing as i32, today as i32);
+        let answered_due = match card.queue {
+            CardQueue::Review | CardQueue::DayLearn => card.original_or_current_due(),
+            _ => today,
+        };
+        let mut moved_due_sibling = false;
+        for original in self.storage.all_cards_of_note(card.note_id)? {
+            if original.id == card.id
+                || original.queue != CardQueue::Review
+                || original.original_deck_id.0 != 0
+            {
+                continue;
+            }
+            let due = spaced_sibling_due(original.due, answered_due, today, spacing);
+            if due == original.due {
+                continue;
+            }
+            let mut sibling = original.clone();
+            sibling.due = due;
+            moved_due_sibling |= original.due <= today;
+            self.log_manually_scheduled_review(&sibling, &original, usn)?;
+            self.update_card_inner(&mut sibling, original, usn)?;
+        }
+
+        Ok(moved_due_sibling)
+    }
+
     fn maybe_bury_siblings(&mut self, card: &Card, config: &DeckConfig) -> Result<()> {
         if config.inner.bury_new || config.inner.bury_reviews {
             self.bury_siblings(
@@ -303,6 +387,7 @@ impl Collection {
         partial: RevlogEntryPartial,
         usn: Usn,
         answer: &CardAnswer,
+        algorithm: SchedulingAlgorithmKind,
     ) -> Result<()> {
         let revlog = partial.into_revlog_entry(
             usn,
@@ -311,8 +396,8 @@ impl Collection {
             answer.answered_at,
             answer.milliseconds_taken,
         );
-        self.add_revlog_entry_undoable(revlog)?;
-        Ok(())
+        let id = self.add_revlog_entry_undoable(revlog)?;
+        self.set_revlog_algorithm_undoable(id, algorithm)
     }
 
     fn update_deck_stats_from_answer(
@@ -348,8 +433,18 @@ impl Collection {
             .get_deck(card.deck_id)?
             .or_not_found(card.deck_id)?;
         let config = self.home_deck_config(deck.config_id(), card.original_deck_id)?;
+        let today = if config.inner.easy_days_percentages.is_empty()
+            && config.inner.easy_dates.is_empty()
+        {
+            None
+        } else {
+            Some(timing.local_date()?)
+        };
         Ok(CardStateUpdater {
             fuzz_seed: get_fuzz_seed(&card),
+            // only needed when calculating states; see get_scheduling_states()
+            load_balancer: None,
+            today,
             card,
             deck,
             config,
@@ -432,6 +527,18 @@ pub mod test_helpers {
     }
 }
 
+/// The day a review sibling due on `due` should be moved to, so that it is at
+/// least `spacing` days after today and `spacing` days away from the answered
+/// card's due date. Siblings are only ever postponed.
+fn spaced_sibling_due(due: i32, answered_due: i32, today: i32, spacing: i32) -> i32 {
+    let due = due.max(today + spacing);
+    if (due - answered_due).abs() < spacing {
+        answered_due + spacing
+    } else {
+        due
+    }
+}
+
 /// Return a consistent seed for a given card at a given number of reps.
 /// If in test environment, disable fuzzing.
 fn get_fuzz_seed(card: &Card) -> Option<u64> {
@@ -454,6 +561,8 @@ mod test {
     use crate::card::CardType;
     use crate::collection::open_test_collection;
     use crate::deckconfig::ReviewMix;
+    use crate::deckconfig::SchedulingAlgorithmKind;
+    use crate::revlog::RevlogReviewKind;
     use crate::search::SortMode;
 
     fn current_state(col: &mut Collection, card_id: CardId) -> CardState {
@@ -578,6 +687,112 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn memory_model_recorded_in_revlog() -> Result<()> {
+        let mut col = open_test_collection();
+        let mut conf = col.get_deck_config(DeckConfigId(1), false)?.unwrap();
+        conf.inner
+            .set_scheduling_algorithm(SchedulingAlgorithmKind::MemoryModel);
+        col.storage.update_deck_conf(&conf)?;
+        let nt = col.get_notetype_by_name("Basic")?.unwrap();
+        let mut note = nt.new_note();
+        col.add_note(&mut note, DeckId(1))?;
+
+        // graduating with easy sets up the memory state
+        let post_answer = col.answer_easy();
+        let card = col.storage.get_card(post_answer.card_id)?.unwrap();
+        let memory = card.memory_state.unwrap();
+        assert_eq!(memory.stability, 4.0);
+        assert_eq!(card.interval, 4);
+        // ease is retained in case the preset is switched back
+        assert_eq!(card.ease_factor, 2500);
+
+        // the revlog records the algorithm, and no ease
+        let revlog = col.storage.get_revlog_entries_for_card(card.id)?;
+        assert_eq!(
+            col.storage.revlog_algorithm(revlog[0].id)?,
+            SchedulingAlgorithmKind::MemoryModel
+        );
+        assert_eq!(revlog[0].ease_factor, 0);
+
+        // switching back to SM-2 drops the memory state on the next answer
+        conf.inner
+            .set_scheduling_algorithm(SchedulingAlgorithmKind::Sm2);
+        col.storage.update_deck_conf(&conf)?;
+        col.storage.db.execute_batch("update cards set due=0")?;
+        col.clear_study_queues();
+        col.answer_good();
+        let card = col.storage.get_card(post_answer.card_id)?.unwrap();
+        assert_eq!(card.memory_state, None);
+        let revlog = col.storage.get_revlog_entries_for_card(card.id)?;
+        assert_eq!(
+            col.storage.revlog_algorithm(revlog[1].id)?,
+            SchedulingAlgorithmKind::Sm2
+        );
+        assert_eq!(revlog[1].ease_factor, 2500);
+
+        // undoing the first answer removes the recorded algorithm
+        col.undo()?;
+        col.undo()?;
+        assert_eq!(
+            col.storage.revlog_algorithm(revlog[0].id)?,
+            SchedulingAlgorithmKind::Sm2
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn sibling_spacing() -> Result<()> {
+        let mut col = open_test_collection();
+        col.set_config_bool(BoolKey::Sched2021, true, false)?;
+        let mut conf = col.get_deck_config(DeckConfigId(1), false)?.unwrap();
+        conf.inner.bury_reviews = false;
+        conf.inner.sibling_spacing_days = 3;
+        col.storage.update_deck_conf(&conf)?;
+        let nt = col
+            .get_notetype_by_name("Basic (and reversed card)")?
+            .unwrap();
+        let mut note = nt.new_note();
+        col.add_note(&mut note, DeckId(1))?;
+        let cids = col.search_cards("", SortMode::NoOrder)?;
+        col.set_due_date(&cids, "0", None)?;
+        let today = col.timing_today()?.days_elapsed as i32;
+        assert_eq!(col.get_queued_cards(2, false)?.review_count, 2);
+
+        // answering one card postpones its sibling until 3 days after the
+        // answered card's new due date, which leaves the queue
+        let answered = col.answer_good().card_id;
+        let answered_due = col.storage.get_card(answered)?.unwrap().due;
+        assert!(answered_due > today);
+        let sibling = cids.iter().find(|&&cid| cid != answered).unwrap();
+        let card = col.storage.get_card(*sibling)?.unwrap();
+        assert_eq!((card.due, card.interval), (answered_due + 3, 1));
+        assert_eq!(col.get_queued_cards(2, false)?.review_count, 0);
+        // the move is logged after the one from setting the due date
+        let revlog = col.storage.get_revlog_entries_for_card(*sibling)?;
+        assert_eq!(revlog.len(), 2);
+        assert_eq!(revlog[1].review_kind, RevlogReviewKind::Manual);
+
+        col.undo()?;
+        let card = col.storage.get_card(*sibling)?.unwrap();
+        assert_eq!((card.due, card.interval), (today, 1));
+        assert_eq!(col.get_queued_cards(2, false)?.review_count, 2);
+        assert_eq!(col.storage.get_revlog_entries_for_card(*sibling)?.len(), 1);
+
+        // a sibling due just after the answered card's new due date is also
+        // postponed
+        let days = (answered_due + 1 - today).to_string();
+        col.set_due_date(&[*sibling], &days, None)?;
+        let interval = col.storage.get_card(*sibling)?.unwrap().interval;
+        assert_eq!(col.answer_good().card_id, answered);
+        assert_eq!(col.storage.get_card(answered)?.unwrap().due, answered_due);
+        let card = col.storage.get_card(*sibling)?.unwrap();
+        assert_eq!((card.due, card.interval), (answered_due + 3, interval));
+
+        Ok(())
+    }
+
     fn v3_test_collection(cards: usize) -> Result<(Collection, Vec<CardId>)> {
         let mut col = open_test_collection();
         let nt = col.get_notetype_by_name("Basic")?.unwrap();
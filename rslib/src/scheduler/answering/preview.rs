@@ -15,7 +15,7 @@ impl CardStateUpdater {
         current: CardState,
         next: PreviewState,
     ) -> RevlogEntryPartial {
-        let revlog = RevlogEntryPartial::new(current, next.into(), 0.0, self.secs_until_rollover());
+        let revlog = RevlogEntryPartial::new(current, next.into(), 0, self.secs_until_rollover());
         if next.finished {
             self.card
                 .remove_from_filtered_deck_restoring_queue(SchedulerVersion::V2);
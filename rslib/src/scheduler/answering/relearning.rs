@@ -20,6 +20,7 @@ impl CardStateUpdater {
         self.card.ctype = CardType::Relearn;
         self.card.lapses = next.review.lapses;
         self.card.ease_factor = (next.review.ease_factor * 1000.0).round() as u16;
+        self.card.memory_state = next.review.memory_state;
         if let Some(position) = current.new_position() {
             self.card.original_position = Some(position)
         }
@@ -41,7 +42,7 @@ impl CardStateUpdater {
         RevlogEntryPartial::new(
             current,
             next.into(),
-            next.review.ease_factor,
+            next.review.revlog_factor(),
             self.secs_until_rollover(),
         )
     }
@@ -20,6 +20,7 @@ impl CardStateUpdater {
         self.card.due = (self.timing.days_elapsed + next.scheduled_days) as i32;
         self.card.ease_factor = (next.ease_factor * 1000.0).round() as u16;
         self.card.lapses = next.lapses;
+        self.card.memory_state = next.memory_state;
         self.card.remaining_steps = 0;
         if let Some(position) = current.new_position() {
             self.card.original_position = Some(position)
@@ -28,7 +29,7 @@ impl CardStateUpdater {
         RevlogEntryPartial::new(
             current,
             next.into(),
-            next.ease_factor,
+            next.revlog_factor(),
             self.secs_until_rollover(),
         )
     }
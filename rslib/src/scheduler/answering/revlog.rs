@@ -10,7 +10,8 @@ use crate::scheduler::states::IntervalKind;
 pub struct RevlogEntryPartial {
     interval: IntervalKind,
     last_interval: IntervalKind,
-    ease_factor: f32,
+    /// See [RevlogEntry::ease_factor].
+    ease_factor: u32,
     review_kind: RevlogReviewKind,
 }
 
@@ -18,7 +19,7 @@ impl RevlogEntryPartial {
     pub(super) fn new(
         current: CardState,
         next: CardState,
-        ease_factor: f32,
+        ease_factor: u32,
         secs_until_rollover: u32,
     ) -> Self {
         let next_interval = next.interval_kind().maybe_as_days(secs_until_rollover);
@@ -47,7 +48,7 @@ impl RevlogEntryPartial {
             button_chosen,
             interval: self.interval.as_revlog_interval(),
             last_interval: self.last_interval.as_revlog_interval(),
-            ease_factor: (self.ease_factor * 1000.0).round() as u32,
+            ease_factor: self.ease_factor,
             taken_millis,
             review_kind: self.review_kind,
         }
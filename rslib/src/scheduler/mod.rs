@@ -12,8 +12,10 @@ pub(crate) mod congrats;
 pub(crate) mod filtered;
 mod learning;
 pub mod new;
+pub mod optimizer;
 pub(crate) mod queue;
 mod reviews;
+pub mod simulator;
 pub mod states;
 pub mod timespan;
 pub mod timing;
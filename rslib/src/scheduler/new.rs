@@ -52,6 +52,7 @@ impl Card {
         self.queue = CardQueue::New;
         self.interval = 0;
         self.ease_factor = 0;
+        self.memory_state = None;
         self.original_position = None;
         if reset_counts {
             self.reps = 0;
@@ -0,0 +1,508 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Fits a preset's SM-2 parameters to the review history of its cards.
+//!
+//! Each card's review log is replayed with candidate parameters. Intervals
+//! aim for roughly 90% recall, so the replayed interval is treated as the
+//! card's stability, and the forgetting curve then gives a predicted chance
+//! of recall for the actual delay before each review. Parameters are chosen
+//! to minimise the log loss of those predictions.
+
+use std::collections::HashMap;
+
+use crate::card::CardQueue;
+use crate::deckconfig::DeckConfig;
+use crate::deckconfig::DeckConfigInner;
+use crate::prelude::*;
+use crate::revlog::RevlogEntry;
+use crate::revlog::RevlogReviewKind;
+use crate::scheduler::states::algorithm::retrievability;
+use crate::scheduler::states::algorithm::Sm2;
+use crate::scheduler::states::ReviewState;
+use crate::scheduler::states::StateContext;
+use crate::search::SortMode;
+
+/// The bounds of each parameter match the deck options screen.
+const INITIAL_EASE_RANGE: (f32, f32) = (1.31, 5.0);
+const INTERVAL_MULTIPLIER_RANGE: (f32, f32) = (0.5, 2.0);
+const EASY_MULTIPLIER_RANGE: (f32, f32) = (1.0, 5.0);
+const HARD_MULTIPLIER_RANGE: (f32, f32) = (0.5, 1.3);
+/// Each pass searches every parameter once while holding the others fixed.
+const PASSES: usize = 3;
+const GOLDEN_SECTION_ITERATIONS: usize = 16;
+/// Keeps the loss finite when a review contradicts a confident prediction.
+const MIN_PREDICTION: f32 = 0.001;
+const MILLIS_PER_DAY: f32 = 86_400_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sm2Parameters {
+    pub initial_ease: f32,
+    pub interval_multiplier: f32,
+    pub easy_multiplier: f32,
+    pub hard_multiplier: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresetOptimization {
+    pub config_id: DeckConfigId,
+    pub current: Sm2Parameters,
+    pub suggested: Sm2Parameters,
+    pub current_log_loss: f32,
+    pub suggested_log_loss: f32,
+    /// The number of reviews predictions were made for.
+    pub review_count: u32,
+}
+
+impl Collection {
+    /// Suggest parameters for each preset used by the cards matching
+    /// `search`, based on their review history. Cards in filtered decks are
+    /// attributed to the preset of their home deck. Nothing is modified.
+    pub fn optimize_deck_config(&mut self, search: &str) -> Result<Vec<PresetOptimization>> {
+        let guard = self.search_cards_into_table(search, SortMode::NoOrder)?;
+        let cards = guard.col.storage.all_searched_cards()?;
+        let mut revlog = guard.col.storage.get_revlog_entries_for_searched_cards()?;
+        drop(guard);
+
+        revlog.sort_unstable_by_key(|entry| (entry.cid, entry.id));
+        let mut histories: HashMap<CardId, Vec<RevlogEntry>> = HashMap::new();
+        for entry in revlog {
+            histories.entry(entry.cid).or_default().push(entry);
+        }
+
+        let mut configs: HashMap<DeckId, DeckConfig> = HashMap::new();
+        let mut presets: HashMap<DeckConfigId, (DeckConfig, Vec<Vec<RevlogEntry>>)> =
+            HashMap::new();
+        for card in cards {
+            if card.queue == CardQueue::New {
+                continue;
+            }
+            let Some(history) = histories.remove(&card.id) else {
+                continue;
+            };
+            let deck_id = card.original_or_current_deck_id();
+            if !configs.contains_key(&deck_id) {
+                configs.insert(deck_id, self.deck_config_for_card(&card)?);
+            }
+            let config = &configs[&deck_id];
+            presets
+                .entry(config.id)
+                .or_insert_with(|| (config.clone(), vec![]))
+                .1
+                .push(history);
+        }
+
+        let mut results: Vec<_> = presets
+            .into_values()
+            .map(|(config, histories)| optimize_preset(&config, &histories))
+            .collect();
+        results.sort_unstable_by_key(|result| result.config_id);
+        Ok(results)
+    }
+}
+
+fn optimize_preset(config: &DeckConfig, histories: &[Vec<RevlogEntry>]) -> PresetOptimization {
+    let current = Sm2Parameters::from(&config.inner);
+    let (current_log_loss, review_count) = log_loss(&config.inner, current, histories);
+    let suggested = if review_count > 0 {
+        fit_parameters(&config.inner, current, histories)
+    } else {
+        current
+    };
+    let (suggested_log_loss, _) = log_loss(&config.inner, suggested, histories);
+    PresetOptimization {
+        config_id: config.id,
+        current,
+        suggested,
+        current_log_loss,
+        suggested_log_loss,
+        review_count,
+    }
+}
+
+/// Coordinate descent from `start`, searching each parameter in turn over
+/// its allowed range. Only changes that reduce the loss are kept, so the
+/// result is never worse than `start`.
+fn fit_parameters(
+    config: &DeckConfigInner,
+    start: Sm2Parameters,
+    histories: &[Vec<RevlogEntry>],
+) -> Sm2Parameters {
+    let loss = |params| log_loss(config, params, histories).0;
+    let mut best = start;
+    let mut best_loss = loss(best);
+
+    for _ in 0..PASSES {
+        for index in 0..Sm2Parameters::COUNT {
+            let range = Sm2Parameters::range(index);
+            let value = golden_section_search(range, |value| loss(best.with(index, value)));
+            let candidate = best.with(index, value).rounded();
+            let candidate_loss = loss(candidate);
+            if candidate_loss < best_loss {
+                best = candidate;
+                best_loss = candidate_loss;
+            }
+        }
+    }
+
+    best
+}
+
+/// The value in `range` minimising `func`, assuming it has a single minimum
+/// there.
+fn golden_section_search(range: (f32, f32), func: impl Fn(f32) -> f32) -> f32 {
+    let ratio = (5f32.sqrt() - 1.0) / 2.0;
+    let (mut low, mut high) = range;
+    let mut left = high - ratio * (high - low);
+    let mut right = low + ratio * (high - low);
+    let mut left_value = func(left);
+    let mut right_value = func(right);
+    for _ in 0..GOLDEN_SECTION_ITERATIONS {
+        if left_value <= right_value {
+            high = right;
+            right = left;
+            right_value = left_value;
+            left = high - ratio * (high - low);
+            left_value = func(left);
+        } else {
+            low = left;
+            left = right;
+            left_value = right_value;
+            right = low + ratio * (high - low);
+            right_value = func(right);
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// Mean log loss of the recall predictions made by replaying `histories` with
+/// `params`, and the number of predictions.
+fn log_loss(
+    config: &DeckConfigInner,
+    params: Sm2Parameters,
+    histories: &[Vec<RevlogEntry>],
+) -> (f32, u32) {
+    let ctx = params.state_context(config);
+    let mut total = 0.0;
+    let mut count = 0;
+    for history in histories {
+        replay_history(&ctx, history, |predicted, recalled| {
+            let predicted = predicted.clamp(MIN_PREDICTION, 1.0 - MIN_PREDICTION);
+            total -= if recalled {
+                predicted.ln()
+            } else {
+                (1.0 - predicted).ln()
+            };
+            count += 1;
+        });
+    }
+    if count == 0 {
+        (0.0, 0)
+    } else {
+        (total / count as f32, count)
+    }
+}
+
+/// Walk through a card's log entries in order, keeping track of the interval
+/// the card would have had with `ctx`, and call `on_review` with the predicted
+/// chance of recall and the actual outcome of each review.
+fn replay_history(
+    ctx: &StateContext,
+    history: &[RevlogEntry],
+    mut on_review: impl FnMut(f32, bool),
+) {
+    let mut state: Option<ReviewState> = None;
+    let mut last_review = None;
+    for entry in history {
+        match entry.review_kind {
+            RevlogReviewKind::Learning | RevlogReviewKind::Relearning => {
+                if entry.interval > 0 {
+                    // graduated, or lapsed card returning to review
+                    state.get_or_insert(ReviewState {
+                        scheduled_days: entry.interval as u32,
+                        ease_factor: ctx.initial_ease_factor,
+                        ..Default::default()
+                    });
+                    last_review = Some(entry.id);
+                }
+            }
+            RevlogReviewKind::Review => {
+                if !(1..=4).contains(&entry.button_chosen) {
+                    continue;
+                }
+                let current = match (state, last_review) {
+                    (Some(state), Some(last_review)) => Some((state, last_review)),
+                    // history starting mid-review, such as after an import
+                    _ if entry.last_interval > 0 => Some((
+                        ReviewState {
+                            scheduled_days: entry.last_interval as u32,
+                            ease_factor: ctx.initial_ease_factor,
+                            ..Default::default()
+                        },
+                        RevlogId(entry.id.0 - entry.last_interval as i64 * 86_400_000),
+                    )),
+                    _ => None,
+                };
+                let Some((current, last_review_id)) = current else {
+                    continue;
+                };
+                let elapsed_days = ((entry.id.0 - last_review_id.0) as f32 / MILLIS_PER_DAY)
+                    .round()
+                    .max(0.0);
+                let recalled = entry.button_chosen > 1;
+                on_review(
+                    retrievability(elapsed_days, current.scheduled_days as f32),
+                    recalled,
+                );
+
+                let current = ReviewState {
+                    elapsed_days: elapsed_days as u32,
+                    ..current
+                };
+                state = Some(if recalled {
+                    let (hard, good, easy) = ctx.algorithm.passing_review_states(current, ctx);
+                    match entry.button_chosen {
+                        2 => hard,
+                        3 => good,
+                        _ => easy,
+                    }
+                } else {
+                    ctx.algorithm.failing_review_state(current, ctx)
+                });
+                last_review = Some(entry.id);
+            }
+            RevlogReviewKind::Manual | RevlogReviewKind::Rescheduled => {
+                if entry.interval > 0 {
+                    let ease_factor = match entry.ease_factor {
+                        0 => state.map_or(ctx.initial_ease_factor, |state| state.ease_factor),
+                        ease => ease as f32 / 1000.0,
+                    };
+                    state = Some(ReviewState {
+                        scheduled_days: entry.interval as u32,
+                        ease_factor,
+                        ..Default::default()
+                    });
+                    last_review = Some(entry.id);
+                } else {
+                    // reset to new
+                    state = None;
+                    last_review = None;
+                }
+            }
+            // reviews ahead in filtered decks without rescheduling have no
+            // effect on the card's schedule
+            RevlogReviewKind::Filtered => (),
+        }
+    }
+}
+
+impl Sm2Parameters {
+    const COUNT: usize = 4;
+
+    fn range(index: usize) -> (f32, f32) {
+        match index {
+            0 => INITIAL_EASE_RANGE,
+            1 => INTERVAL_MULTIPLIER_RANGE,
+            2 => EASY_MULTIPLIER_RANGE,
+            _ => HARD_MULTIPLIER_RANGE,
+        }
+    }
+
+    fn with(mut self, index: usize, value: f32) -> Self {
+        match index {
+            0 => self.initial_ease = value,
+            1 => self.interval_multiplier = value,
+            2 => self.easy_multiplier = value,
+            _ => self.hard_multiplier = value,
+        }
+        self
+    }
+
+    /// Round to the precision shown on the deck options screen.
+    fn rounded(self) -> Self {
+        let round = |value: f32| (value * 100.0).round() / 100.0;
+        Sm2Parameters {
+            initial_ease: round(self.initial_ease),
+            interval_multiplier: round(self.interval_multiplier),
+            easy_multiplier: round(self.easy_multiplier),
+            hard_multiplier: round(self.hard_multiplier),
+        }
+    }
+
+    /// Replays always use SM-2, as that is what these parameters control.
+    fn state_context(self, config: &DeckConfigInner) -> StateContext {
+        StateContext {
+            algorithm: &Sm2,
+            initial_ease_factor: self.initial_ease,
+            interval_multiplier: self.interval_multiplier,
+            easy_multiplier: self.easy_multiplier,
+            hard_multiplier: self.hard_multiplier,
+            ..StateContext::for_config(config)
+        }
+    }
+
+    pub fn apply_to(self, config: &mut DeckConfigInner) {
+        config.initial_ease = self.initial_ease;
+        config.interval_multiplier = self.interval_multiplier;
+        config.easy_multiplier = self.easy_multiplier;
+        config.hard_multiplier = self.hard_multiplier;
+    }
+}
+
+impl From<&DeckConfigInner> for Sm2Parameters {
+    fn from(config: &DeckConfigInner) -> Self {
+        Sm2Parameters {
+            initial_ease: config.initial_ease,
+            interval_multiplier: config.interval_multiplier,
+            easy_multiplier: config.easy_multiplier,
+            hard_multiplier: config.hard_multiplier,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::prelude::*;
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::scheduler::states::SchedulingAlgorithm;
+
+    const TRUTH: Sm2Parameters = Sm2Parameters {
+        initial_ease: 2.5,
+        interval_multiplier: 1.4,
+        easy_multiplier: 1.3,
+        hard_multiplier: 1.2,
+    };
+
+    /// Review histories of cards whose recall really follows the intervals
+    /// `truth` produces, reviewed somewhat early or late at random.
+    fn synthetic_histories(truth: Sm2Parameters, cards: i64) -> Vec<Vec<RevlogEntry>> {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut config = DeckConfig::default().inner;
+        truth.apply_to(&mut config);
+        let ctx = truth.state_context(&config);
+        (0..cards)
+            .map(|cid| {
+                let mut millis = 0;
+                let entry = |millis, button, interval, last_interval, review_kind| RevlogEntry {
+                    id: RevlogId(millis),
+                    cid: CardId(cid),
+                    button_chosen: button,
+                    interval,
+                    last_interval,
+                    review_kind,
+                    ..Default::default()
+                };
+                let mut history = vec![entry(millis, 3, 1, -600, RevlogReviewKind::Learning)];
+                let mut state = ReviewState {
+                    scheduled_days: 1,
+                    ease_factor: truth.initial_ease,
+                    ..Default::default()
+                };
+                for _ in 0..8 {
+                    let scheduled = state.scheduled_days as f32;
+                    let elapsed = (scheduled * rng.gen_range(0.5..2.0)).round().max(1.0);
+                    millis += (elapsed * MILLIS_PER_DAY) as i64;
+                    let recalled = rng.gen::<f32>() < retrievability(elapsed, scheduled);
+                    let current = ReviewState {
+                        elapsed_days: elapsed as u32,
+                        ..state
+                    };
+                    let (button, next) = if recalled {
+                        let (hard, good, easy) = Sm2.passing_review_states(current, &ctx);
+                        match rng.gen_range(0..10) {
+                            0 => (2, hard),
+                            1 => (4, easy),
+                            _ => (3, good),
+                        }
+                    } else {
+                        (1, Sm2.failing_review_state(current, &ctx))
+                    };
+                    history.push(entry(
+                        millis,
+                        button,
+                        next.scheduled_days as i32,
+                        state.scheduled_days as i32,
+                        RevlogReviewKind::Review,
+                    ));
+                    state = next;
+                }
+                history
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replay_predicts_from_replayed_interval() {
+        let mut config = DeckConfig::default().inner;
+        TRUTH.apply_to(&mut config);
+        let ctx = TRUTH.state_context(&config);
+        let history = [
+            RevlogEntry {
+                id: RevlogId(0),
+                interval: 10,
+                review_kind: RevlogReviewKind::Learning,
+                ..Default::default()
+            },
+            RevlogEntry {
+                id: RevlogId(10 * 86_400_000),
+                button_chosen: 3,
+                review_kind: RevlogReviewKind::Review,
+                ..Default::default()
+            },
+            RevlogEntry {
+                id: RevlogId(20 * 86_400_000),
+                button_chosen: 1,
+                review_kind: RevlogReviewKind::Review,
+                ..Default::default()
+            },
+        ];
+        let mut predictions = vec![];
+        replay_history(&ctx, &history, |predicted, recalled| {
+            predictions.push((predicted, recalled))
+        });
+        assert_eq!(predictions.len(), 2);
+        assert!((predictions[0].0 - 0.9).abs() < 0.001);
+        assert!(predictions[0].1);
+        // the second interval was 10 * 2.5 * 1.4 = 35 days, but only 10 passed
+        assert!((predictions[1].0 - retrievability(10.0, 35.0)).abs() < 0.001);
+        assert!(!predictions[1].1);
+    }
+
+    #[test]
+    fn fit_recovers_interval_multiplier() {
+        let histories = synthetic_histories(TRUTH, 500);
+        let config = DeckConfig::default().inner;
+        let current = Sm2Parameters::from(&config);
+        assert_eq!(current.interval_multiplier, 1.0);
+
+        let suggested = fit_parameters(&config, current, &histories);
+        let (current_loss, count) = log_loss(&config, current, &histories);
+        let (suggested_loss, _) = log_loss(&config, suggested, &histories);
+        assert_eq!(count, 500 * 8);
+        assert!(suggested_loss < current_loss);
+        // ease and interval multiplier trade off against each other, so only
+        // their product is well determined
+        let growth = suggested.initial_ease * suggested.interval_multiplier;
+        assert!(
+            (growth - TRUTH.initial_ease * TRUTH.interval_multiplier).abs() < 0.5,
+            "{suggested:?}"
+        );
+
+        // fitting is deterministic
+        assert_eq!(fit_parameters(&config, current, &histories), suggested);
+    }
+
+    #[test]
+    fn fit_never_worsens_loss() {
+        let config = DeckConfig::default().inner;
+        let current = Sm2Parameters::from(&config);
+        assert_eq!(fit_parameters(&config, current, &[]), current);
+        let histories = synthetic_histories(Sm2Parameters::from(&config), 50);
+        let suggested = fit_parameters(&config, current, &histories);
+        assert!(
+            log_loss(&config, suggested, &histories).0 <= log_loss(&config, current, &histories).0
+        );
+    }
+}
@@ -459,4 +459,37 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn retrievability_review_order() -> Result<()> {
+        let mut col = open_test_collection();
+        col.set_config_bool(BoolKey::Sched2021, true, false)?;
+        let mut deck = col.get_or_create_normal_deck("Default").unwrap();
+        let nt = col.get_notetype_by_name("Basic")?.unwrap();
+        let today = col.timing_today()?.days_elapsed as i32;
+
+        // (due, ivl), with retrievabilities of 45%, 72%, 89% and 90%
+        let expected_queue = vec![
+            (today - 10, 1),
+            (today - 50, 20),
+            (today - 10, 100),
+            (today, 1),
+        ];
+        let mut cards = vec![];
+        for (due, interval) in expected_queue.iter().rev() {
+            let mut note = nt.new_note();
+            col.add_note(&mut note, deck.id)?;
+            let mut card = col.storage.get_card_by_ordinal(note.id, 0)?.unwrap();
+            card.interval = *interval;
+            card.due = *due;
+            card.ctype = CardType::Review;
+            card.queue = CardQueue::Review;
+            cards.push(card);
+        }
+        col.update_cards_maybe_undoable(cards, false)?;
+        col.set_deck_review_order(&mut deck, ReviewCardOrder::RetrievabilityAscending);
+        assert_eq!(col.queue_as_due_and_ivl(deck.id), expected_queue);
+
+        Ok(())
+    }
 }
@@ -6,6 +6,7 @@ use std::collections::HashMap;
 use lazy_static::lazy_static;
 use rand::distributions::Distribution;
 use rand::distributions::Uniform;
+use rand::Rng;
 use regex::Regex;
 
 use crate::card::Card;
@@ -16,6 +17,11 @@ use crate::collection::Collection;
 use crate::config::StringKey;
 use crate::error::Result;
 use crate::prelude::*;
+use crate::revlog::RevlogReviewKind;
+use crate::scheduler::states::fuzz::EasyDays;
+use crate::scheduler::states::MemoryState;
+use crate::scheduler::states::SchedulingAlgorithmKind;
+use crate::scheduler::states::StateContext;
 
 impl Card {
     /// Make card due in `days_from_today`.
@@ -42,6 +48,48 @@ impl Card {
         self.schedule_as_review(new_interval, new_due, ease_factor);
     }
 
+    /// Move a review card's due date to the day its predicted chance of recall
+    /// drops to `retention`, adjusting its interval to match. Cards already
+    /// past that point become due today. Returns false if nothing changed.
+    /// With SM-2, or without a memory state, one is estimated from
+    /// `sm2_interval`, the interval the card was given when it was last
+    /// reviewed.
+    fn reschedule_for_retention(
+        &mut self,
+        today: u32,
+        retention: f32,
+        maximum_interval: u32,
+        algorithm: SchedulingAlgorithmKind,
+        sm2_interval: u32,
+    ) -> bool {
+        let original_due = self.original_or_current_due();
+        let last_review = original_due - self.interval as i32;
+        let memory = match self.memory_state {
+            Some(memory) if algorithm == SchedulingAlgorithmKind::MemoryModel => memory,
+            // a memory state left over from using the memory model is stale
+            _ => MemoryState::from_sm2(sm2_interval, self.ease_factor()),
+        };
+        let interval = (memory.interval_for_retention(retention).round() as u32)
+            .clamp(1, maximum_interval.max(1));
+        let due = (last_review + interval as i32).max(today as i32);
+        let interval = (due - last_review).max(1) as u32;
+        if due == original_due && interval == self.interval {
+            return false;
+        }
+        self.interval = interval;
+        if algorithm == SchedulingAlgorithmKind::MemoryModel {
+            // keep an approximated memory state, so that the stability does not
+            // grow with the new interval if the card is rescheduled again
+            self.memory_state = Some(memory);
+        }
+        if self.original_deck_id.0 == 0 {
+            self.due = due;
+        } else {
+            self.original_due = due;
+        }
+        true
+    }
+
     fn schedule_as_review(&mut self, interval: u32, due: i32, ease_factor: u16) {
         self.original_position = self.last_position();
         self.remove_from_filtered_deck_before_reschedule();
@@ -101,6 +149,8 @@ impl Collection {
     /// `days` should be in a format parseable by `parse_due_date_str`.
     /// If `context` is provided, provided key will be updated with the new
     /// value of `days`.
+    /// When a range is given, days are picked according to the easy days of
+    /// each card's preset.
     pub fn set_due_date(
         &mut self,
         cids: &[CardId],
@@ -109,31 +159,43 @@ impl Collection {
     ) -> Result<OpOutput<()>> {
         let spec = parse_due_date_str(days)?;
         let usn = self.usn()?;
-        let today = self.timing_today()?.days_elapsed;
+        let timing = self.timing_today()?;
+        let today = timing.days_elapsed;
+        let local_date = timing.local_date()?;
         let mut rng = rand::thread_rng();
         let distribution = Uniform::from(spec.min..=spec.max);
-        let mut decks_initial_ease: HashMap<DeckId, f32> = HashMap::new();
+        let mut decks_config: HashMap<DeckId, DeckConfig> = HashMap::new();
         self.transact(Op::SetDueDate, |col| {
             for mut card in col.all_cards_for_ids(cids, false)? {
                 let deck_id = card.original_deck_id.or(card.deck_id);
-                let ease_factor = match decks_initial_ease.get(&deck_id) {
-                    Some(ease) => *ease,
-                    None => {
-                        let deck = col.get_deck(deck_id)?.or_not_found(deck_id)?;
-                        let config_id = deck.config_id().or_invalid("home deck is filtered")?;
-                        let ease = col
-                            .get_deck_config(config_id, true)?
-                            // just for compiler; get_deck_config() is guaranteed to return a value
-                            .unwrap_or_default()
-                            .inner
-                            .initial_ease;
-                        decks_initial_ease.insert(deck_id, ease);
-                        ease
+                if !decks_config.contains_key(&deck_id) {
+                    let deck = col.get_deck(deck_id)?.or_not_found(deck_id)?;
+                    let config_id = deck.config_id().or_invalid("home deck is filtered")?;
+                    let config = col
+                        .get_deck_config(config_id, true)?
+                        // just for compiler; get_deck_config() is guaranteed to return a value
+                        .unwrap_or_default();
+                    decks_config.insert(deck_id, config);
+                }
+                let config = &decks_config[&deck_id].inner;
+                let original = card.clone();
+                let easy_days = EasyDays::new(
+                    &config.easy_days_percentages,
+                    &config.easy_dates,
+                    local_date,
+                );
+                let days_from_today = match easy_days {
+                    Some(easy_days) => {
+                        easy_days.pick_interval(spec.min, spec.max, rng.gen_range(0.0..1.0))
                     }
+                    None => distribution.sample(&mut rng),
                 };
-                let original = card.clone();
-                let days_from_today = distribution.sample(&mut rng);
-                card.set_due_date(today, days_from_today, ease_factor, spec.force_reset);
+                card.set_due_date(
+                    today,
+                    days_from_today,
+                    config.initial_ease,
+                    spec.force_reset,
+                );
                 col.log_manually_scheduled_review(&card, &original, usn)?;
                 col.update_card_inner(&mut card, original, usn)?;
             }
@@ -143,12 +205,169 @@ impl Collection {
             Ok(())
         })
     }
+
+    /// Move the due dates of review cards matching `search` to the day their
+    /// predicted chance of recall drops to `target_retention`, or to their
+    /// preset's desired retention if not provided. Returns the number of
+    /// cards that were moved.
+    pub fn reschedule_cards_by_retention(
+        &mut self,
+        search: &str,
+        target_retention: Option<f32>,
+    ) -> Result<OpOutput<usize>> {
+        if let Some(retention) = target_retention {
+            require!(
+                retention > 0.0 && retention < 1.0,
+                "target retention must be between 0 and 1"
+            );
+        }
+        let usn = self.usn()?;
+        let today = self.timing_today()?.days_elapsed;
+        let mut decks_config: HashMap<DeckId, DeckConfig> = HashMap::new();
+        self.transact(Op::RescheduleByRetention, |col| {
+            let mut count = 0;
+            for mut card in col.all_cards_for_search(search)? {
+                if card.ctype != CardType::Review {
+                    continue;
+                }
+                let deck_id = card.original_or_current_deck_id();
+                if !decks_config.contains_key(&deck_id) {
+                    let config = col.deck_config_for_card(&card)?;
+                    decks_config.insert(deck_id, config);
+                }
+                let config = &decks_config[&deck_id].inner;
+                let retention = target_retention
+                    .unwrap_or_else(|| StateContext::for_config(config).desired_retention());
+                let original = card.clone();
+                let sm2_interval = col.interval_before_rescheduling(&card)?;
+                if card.reschedule_for_retention(
+                    today,
+                    retention,
+                    config.maximum_review_interval,
+                    config.scheduling_algorithm(),
+                    sm2_interval,
+                ) {
+                    col.log_rescheduled_review(&card, &original, usn)?;
+                    col.update_card_inner(&mut card, original, usn)?;
+                    count += 1;
+                }
+            }
+            Ok(count)
+        })
+    }
+
+    /// The card's interval before it was last rescheduled by retention, so
+    /// that rescheduling it again does not compound.
+    fn interval_before_rescheduling(&self, card: &Card) -> Result<u32> {
+        let mut interval = card.interval;
+        for entry in self
+            .storage
+            .get_revlog_entries_for_card(card.id)?
+            .iter()
+            .rev()
+        {
+            if entry.review_kind != RevlogReviewKind::Rescheduled {
+                break;
+            }
+            interval = entry.last_interval.max(1) as u32;
+        }
+        Ok(interval)
+    }
+
+    /// Spread the review cards matching `search` that are due today or
+    /// overdue across the next `days` days, starting today. Cards that are
+    /// most overdue relative to their interval come first. Each day gets at
+    /// most `max_per_day` reviews including those already due then, unless
+    /// the cards would not fit, in which case the limit is raised evenly. A
+    /// limit of 0 spreads the cards as evenly as possible. Returns the number
+    /// of cards that were moved.
+    pub fn spread_overdue_reviews(
+        &mut self,
+        search: &str,
+        days: u32,
+        max_per_day: u32,
+    ) -> Result<OpOutput<usize>> {
+        require!(days > 0, "days must be at least 1");
+        let usn = self.usn()?;
+        let today = self.timing_today()?.days_elapsed;
+        let due_counts = self
+            .storage
+            .review_due_counts(today, CardId(0), 0..=days - 1)?;
+        let mut day_counts: Vec<u32> = (0..days)
+            .map(|day| due_counts.get(&day).copied().unwrap_or_default())
+            .collect();
+        self.transact(Op::SpreadOverdueReviews, |col| {
+            let mut cards: Vec<_> = col
+                .all_cards_for_search(search)?
+                .into_iter()
+                .filter(|card| {
+                    card.queue == CardQueue::Review
+                        && card.original_or_current_due() <= today as i32
+                })
+                .collect();
+            // the cards being moved are counted as due today
+            day_counts[0] = day_counts[0].saturating_sub(cards.len() as u32);
+            cards.sort_unstable_by(|a, b| {
+                relative_overdueness(b, today)
+                    .total_cmp(&relative_overdueness(a, today))
+                    .then_with(|| a.id.cmp(&b.id))
+            });
+            let limit = daily_limit(&day_counts, cards.len(), max_per_day);
+            let mut day = 0;
+            let count = cards.len();
+            for mut card in cards {
+                while day_counts[day] >= limit {
+                    day += 1;
+                }
+                day_counts[day] += 1;
+                let original = card.clone();
+                let ease_factor = card.ease_factor();
+                card.set_due_date(today, day as u32, ease_factor, false);
+                col.log_manually_scheduled_review(&card, &original, usn)?;
+                col.update_card_inner(&mut card, original, usn)?;
+            }
+            Ok(count)
+        })
+    }
+}
+
+/// How far past its due date a review card is, as a share of its interval.
+fn relative_overdueness(card: &Card, today: u32) -> f32 {
+    (today as i32 - card.original_or_current_due()) as f32 / card.interval.max(1) as f32
+}
+
+/// The smallest per-day limit of at least `requested` that leaves room for
+/// `backlog` cards on days that already have `existing` reviews due.
+fn daily_limit(existing: &[u32], backlog: usize, requested: u32) -> u32 {
+    let capacity = |limit: u32| -> usize {
+        existing
+            .iter()
+            .map(|&due| limit.saturating_sub(due) as usize)
+            .sum()
+    };
+    let mut low = requested.max(1);
+    let mut high = existing.iter().copied().max().unwrap_or_default() + backlog as u32;
+    if capacity(low) >= backlog {
+        return low;
+    }
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if capacity(middle) >= backlog {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+    low
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::collection::open_test_collection;
     use crate::prelude::*;
+    use crate::revlog::RevlogReviewKind;
+    use crate::search::SortMode;
 
     #[test]
     fn parse() -> Result<()> {
@@ -244,4 +463,165 @@ mod test {
         assert_eq!(c.interval, 2);
         assert_eq!(c.ease_factor, 2200); // interval doesn't change
     }
+
+    #[test]
+    fn reschedule_for_retention() {
+        use SchedulingAlgorithmKind::*;
+        // last reviewed 5 days ago, with a 10 day interval
+        let mut c = Card::new(NoteId(0), 0, DeckId(0), 0);
+        c.ctype = CardType::Review;
+        c.interval = 10;
+        c.due = 105;
+
+        // a lower target moves the card further out
+        assert!(c.reschedule_for_retention(100, 0.75, 36500, Sm2, c.interval));
+        assert_eq!((c.due, c.interval), (125, 30));
+        // but not beyond the maximum interval
+        c.interval = 10;
+        c.due = 105;
+        assert!(c.reschedule_for_retention(100, 0.75, 20, Sm2, c.interval));
+        assert_eq!((c.due, c.interval), (115, 20));
+
+        // a card that is already past the target becomes due today
+        c.interval = 10;
+        c.due = 105;
+        assert!(c.reschedule_for_retention(100, 0.99, 36500, Sm2, c.interval));
+        assert_eq!((c.due, c.interval), (100, 5));
+        // SM-2 cards are not given a memory state
+        assert_eq!(c.memory_state, None);
+
+        // the memory state is used when available
+        c.interval = 10;
+        c.due = 105;
+        c.memory_state = Some(MemoryState {
+            stability: 20.0,
+            difficulty: 5.0,
+        });
+        assert!(c.reschedule_for_retention(100, 0.9, 36500, MemoryModel, c.interval));
+        assert_eq!((c.due, c.interval), (115, 20));
+        assert!(!c.reschedule_for_retention(100, 0.9, 36500, MemoryModel, c.interval));
+        // but not with SM-2
+        c.interval = 10;
+        c.due = 105;
+        assert!(c.reschedule_for_retention(100, 0.75, 36500, Sm2, c.interval));
+        assert_eq!((c.due, c.interval), (125, 30));
+
+        // cards in filtered decks have their home due date changed
+        c.original_deck_id = DeckId(1);
+        c.original_due = c.due;
+        c.due = -100_000;
+        assert!(c.reschedule_for_retention(100, 0.75, 36500, MemoryModel, c.interval));
+        assert_eq!((c.due, c.original_due, c.interval), (-100_000, 155, 60));
+    }
+
+    #[test]
+    fn reschedule_cards_by_retention() -> Result<()> {
+        let mut col = open_test_collection();
+        let nt = col.get_notetype_by_name("Basic")?.unwrap();
+        let mut note = nt.new_note();
+        col.add_note(&mut note, DeckId(1))?;
+        let mut note = nt.new_note();
+        col.add_note(&mut note, DeckId(1))?;
+        let cids = col.search_cards("", SortMode::NoOrder)?;
+        let today = col.timing_today()?.days_elapsed as i32;
+
+        // only review cards are rescheduled
+        let mut card = col.storage.get_card(cids[0])?.unwrap();
+        card.ctype = CardType::Review;
+        card.queue = CardQueue::Review;
+        card.interval = 10;
+        card.due = today + 5;
+        col.storage.update_card(&card)?;
+        let changes = col.reschedule_cards_by_retention("", Some(0.75))?;
+        assert_eq!(changes.output, 1);
+        let card = col.storage.get_card(cids[0])?.unwrap();
+        assert_eq!((card.due, card.interval), (today + 25, 30));
+        let revlog = col.storage.get_revlog_entries_for_card(cids[0])?;
+        assert_eq!(revlog.len(), 1);
+        assert_eq!(revlog[0].review_kind, RevlogReviewKind::Rescheduled);
+        assert_eq!((revlog[0].last_interval, revlog[0].interval), (10, 30));
+
+        // cards that would not move are left alone
+        assert_eq!(col.reschedule_cards_by_retention("", Some(0.75))?.output, 0);
+        // without a target, the preset's desired retention is used
+        assert_eq!(col.reschedule_cards_by_retention("", None)?.output, 1);
+        let card = col.storage.get_card(cids[0])?.unwrap();
+        assert_eq!((card.due, card.interval), (today + 5, 10));
+
+        // the change can be undone
+        col.undo()?;
+        let card = col.storage.get_card(cids[0])?.unwrap();
+        assert_eq!((card.due, card.interval), (today + 25, 30));
+        assert_eq!(col.storage.get_revlog_entries_for_card(cids[0])?.len(), 1);
+
+        assert!(col.reschedule_cards_by_retention("", Some(1.0)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn daily_limit() {
+        // the requested limit is kept if the cards fit
+        assert_eq!(super::daily_limit(&[0, 0, 0], 30, 10), 10);
+        assert_eq!(super::daily_limit(&[5, 0, 0], 25, 10), 10);
+        // and raised if they don't
+        assert_eq!(super::daily_limit(&[0, 0, 0], 31, 10), 11);
+        assert_eq!(super::daily_limit(&[10, 0, 0], 30, 10), 14);
+        // no limit spreads evenly
+        assert_eq!(super::daily_limit(&[0, 0, 0, 0], 10, 0), 3);
+        assert_eq!(super::daily_limit(&[0, 0], 0, 0), 1);
+    }
+
+    #[test]
+    fn spread_overdue_reviews() -> Result<()> {
+        let mut col = open_test_collection();
+        let nt = col.get_notetype_by_name("Basic")?.unwrap();
+        for _ in 0..6 {
+            let mut note = nt.new_note();
+            col.add_note(&mut note, DeckId(1))?;
+        }
+        let cids = col.search_cards("", SortMode::NoOrder)?;
+        let today = col.timing_today()?.days_elapsed as i32;
+        // (interval, days overdue); the last card is not overdue, and is
+        // already due tomorrow
+        let schedule = [(10, 5), (100, 5), (1, 2), (4, 0), (20, 40), (5, -1)];
+        for (cid, (interval, overdue)) in cids.iter().zip(schedule) {
+            let mut card = col.storage.get_card(*cid)?.unwrap();
+            card.ctype = CardType::Review;
+            card.queue = CardQueue::Review;
+            card.interval = interval;
+            card.due = today - overdue;
+            card.ease_factor = 2500;
+            col.storage.update_card(&card)?;
+        }
+
+        let changes = col.spread_overdue_reviews("", 3, 2)?;
+        assert_eq!(changes.output, 5);
+        let due_days: Vec<_> = cids
+            .iter()
+            .map(|cid| col.storage.get_card(*cid).unwrap().unwrap().due - today)
+            .collect();
+        // relative overdueness is 0.5, 0.05, 2, 0 and 2; tomorrow already
+        // has one review, so only one more fits
+        assert_eq!(due_days, [1, 2, 0, 2, 0, 1]);
+        let card = col.storage.get_card(cids[0])?.unwrap();
+        assert_eq!(card.interval, 10);
+        let revlog = col.storage.get_revlog_entries_for_card(cids[0])?;
+        assert_eq!(revlog[0].review_kind, RevlogReviewKind::Manual);
+
+        col.undo()?;
+        let card = col.storage.get_card(cids[0])?.unwrap();
+        assert_eq!(card.due, today - 5);
+        assert!(col.storage.get_revlog_entries_for_card(cids[0])?.is_empty());
+
+        // overdue cards that are not being moved count towards today's limit
+        let search = format!("cid:{},{},{},{}", cids[0], cids[1], cids[2], cids[3]);
+        let changes = col.spread_overdue_reviews(&search, 3, 2)?;
+        assert_eq!(changes.output, 4);
+        let due_days: Vec<_> = cids
+            .iter()
+            .map(|cid| col.storage.get_card(*cid).unwrap().unwrap().due - today)
+            .collect();
+        assert_eq!(due_days, [1, 2, 0, 2, -40, 1]);
+        Ok(())
+    }
 }
@@ -0,0 +1,484 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Projects the workload a preset will produce, by answering cards day by
+//! day with the same state transitions used when studying.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::VecDeque;
+
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+use crate::card::CardQueue;
+use crate::card::CardType;
+use crate::deckconfig::DeckConfigInner;
+use crate::prelude::*;
+use crate::revlog::RevlogReviewKind;
+use crate::scheduler::states::steps::LearningSteps;
+use crate::scheduler::states::CardState;
+use crate::scheduler::states::IntervalKind;
+use crate::scheduler::states::LearnState;
+use crate::scheduler::states::NewState;
+use crate::scheduler::states::NormalState;
+use crate::scheduler::states::RelearnState;
+use crate::scheduler::states::ReviewState;
+use crate::scheduler::states::StateContext;
+
+pub(crate) const DEFAULT_SECONDS_PER_REVIEW: f32 = 10.0;
+/// Chance of passing a learning step when using [RecallModel::ForgettingCurve].
+const LEARNING_STEP_RECALL: f32 = 0.9;
+const SECS_PER_DAY: u32 = 86_400;
+const MAX_SIMULATED_DAYS: u32 = 3650;
+const MAX_NEW_CARDS_PER_DAY: u32 = 1000;
+/// Each simulated day scans every card, and every day adds new cards, so the
+/// run time grows with the number of cards times the number of days. This
+/// bounds that product, counting every card that will have been introduced by
+/// the last day.
+const MAX_SIMULATED_CARD_DAYS: u64 = 50_000_000;
+
+/// Decides whether a simulated review is answered correctly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecallModel {
+    /// Review cards are recalled with the chance predicted by their memory
+    /// state (or their interval, for cards scheduled by SM-2), and learning
+    /// steps are usually passed.
+    ForgettingCurve,
+    /// Every answer is correct with the given probability.
+    Fixed(f32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatorConfig {
+    pub deck_config: DeckConfigInner,
+    pub days: u32,
+    pub new_cards_per_day: u32,
+    pub recall_model: RecallModel,
+    pub seconds_per_review: f32,
+    pub seed: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SimulatedDay {
+    /// Answers given to review cards, counting each card at most once.
+    pub review_count: u32,
+    /// New cards introduced.
+    pub new_count: u32,
+    /// Answers given to learning and relearning cards.
+    pub learning_count: u32,
+    pub seconds: f32,
+    /// Share of review cards that were recalled.
+    pub retention: f32,
+    /// Mean chance of recalling each card in the review state at the end of
+    /// the day, per the forgetting curve.
+    pub average_retrievability: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SimulatedCard {
+    state: NormalState,
+    /// Day the card next needs answering, relative to the start of the
+    /// simulation.
+    due_day: i32,
+    /// Day the card last entered the review state.
+    last_review_day: i32,
+}
+
+struct Simulator<'a> {
+    config: &'a SimulatorConfig,
+    rng: StdRng,
+    cards: Vec<SimulatedCard>,
+}
+
+impl Collection {
+    /// Simulate studying the cards matching `search` (which may be empty)
+    /// with the given options, starting today.
+    pub fn simulate_reviews(
+        &mut self,
+        config: &SimulatorConfig,
+        search: &str,
+    ) -> Result<Vec<SimulatedDay>> {
+        require!(
+            config.days <= MAX_SIMULATED_DAYS,
+            "days must be at most {MAX_SIMULATED_DAYS}"
+        );
+        require!(
+            config.new_cards_per_day <= MAX_NEW_CARDS_PER_DAY,
+            "new cards per day must be at most {MAX_NEW_CARDS_PER_DAY}"
+        );
+        require!(
+            config.seconds_per_review >= 1.0,
+            "seconds per review must be at least 1"
+        );
+        if let RecallModel::Fixed(probability) = config.recall_model {
+            require!(
+                (0.0..=1.0).contains(&probability),
+                "recall probability must be between 0 and 1"
+            );
+        }
+        let cards = if search.trim().is_empty() {
+            vec![]
+        } else {
+            self.all_cards_for_search(search)?
+        };
+        let today = self.timing_today()?.days_elapsed;
+        let steps = LearningSteps::new(&config.deck_config.learn_steps);
+        let relearn_steps = LearningSteps::new(&config.deck_config.relearn_steps);
+        let existing: Vec<_> = cards
+            .iter()
+            .filter_map(|card| SimulatedCard::from_card(card, today, steps, relearn_steps))
+            .collect();
+        let total_cards =
+            existing.len() as u64 + config.days as u64 * config.new_cards_per_day as u64;
+        require!(
+            total_cards * config.days as u64 <= MAX_SIMULATED_CARD_DAYS,
+            "too many cards to simulate over {} days; reduce the days or new cards per day",
+            config.days
+        );
+        Ok(simulate_reviews(config, existing))
+    }
+}
+
+/// Simulate `config.days` days, starting from `cards` that already have
+/// been studied. Cards in the learning states should be due on day 0.
+fn simulate_reviews(config: &SimulatorConfig, cards: Vec<SimulatedCard>) -> Vec<SimulatedDay> {
+    let mut simulator = Simulator {
+        config,
+        rng: StdRng::seed_from_u64(config.seed),
+        cards,
+    };
+    (0..config.days as i32)
+        .map(|day| simulator.simulate_day(day))
+        .collect()
+}
+
+impl SimulatedCard {
+    /// The state of a card in the collection, with its due date relative to
+    /// `today`. New and suspended cards are skipped, as new cards are
+    /// introduced at the configured rate instead.
+    fn from_card(
+        card: &Card,
+        today: u32,
+        steps: LearningSteps,
+        relearn_steps: LearningSteps,
+    ) -> Option<Self> {
+        if card.queue == CardQueue::Suspended {
+            return None;
+        }
+        let due = if card.original_due != 0 {
+            card.original_due
+        } else {
+            card.due
+        };
+        let review = ReviewState {
+            scheduled_days: card.interval,
+            elapsed_days: card.interval,
+            ease_factor: card.ease_factor(),
+            lapses: card.lapses,
+            leeched: false,
+            memory_state: card.memory_state,
+        };
+        let remaining_steps = card.remaining_steps();
+        let (state, due_day) = match card.ctype {
+            CardType::New => return None,
+            CardType::Learn => (
+                LearnState {
+                    scheduled_secs: steps.current_delay_secs(remaining_steps),
+                    remaining_steps,
+                }
+                .into(),
+                0,
+            ),
+            CardType::Review => (review.into(), (due - today as i32).max(0)),
+            CardType::Relearn => (
+                RelearnState {
+                    learning: LearnState {
+                        scheduled_secs: relearn_steps.current_delay_secs(remaining_steps),
+                        remaining_steps,
+                    },
+                    review,
+                }
+                .into(),
+                0,
+            ),
+        };
+        Some(SimulatedCard {
+            state,
+            due_day,
+            last_review_day: due - today as i32 - card.interval as i32,
+        })
+    }
+
+    fn new() -> Self {
+        SimulatedCard {
+            state: NewState::default().into(),
+            due_day: 0,
+            last_review_day: 0,
+        }
+    }
+
+    /// The card's state with the days since its last review filled in.
+    fn state_on(&self, day: i32) -> NormalState {
+        match self.state {
+            NormalState::Review(review) => ReviewState {
+                elapsed_days: (day - self.last_review_day).max(0) as u32,
+                ..review
+            }
+            .into(),
+            state => state,
+        }
+    }
+}
+
+impl Simulator<'_> {
+    fn simulate_day(&mut self, day: i32) -> SimulatedDay {
+        let mut stats = SimulatedDay::default();
+        let mut recalled_reviews = 0;
+
+        // cards in learning are answered as they become due; the rest are
+        // answered in the order they became due, then new cards
+        let mut learning: BinaryHeap<Reverse<(u32, usize)>> = BinaryHeap::new();
+        let mut reviews = vec![];
+        for (index, card) in self.cards.iter().enumerate() {
+            if card.due_day > day {
+                continue;
+            }
+            match card.state {
+                NormalState::Review(_) => reviews.push((card.due_day, index)),
+                _ => learning.push(Reverse((0, index))),
+            }
+        }
+        reviews.sort_unstable();
+        let review_limit = self.config.deck_config.reviews_per_day as usize;
+        let mut queue: VecDeque<_> = reviews
+            .into_iter()
+            .take(review_limit)
+            .map(|(_, index)| index)
+            .collect();
+        for _ in 0..self.config.new_cards_per_day {
+            queue.push_back(self.cards.len());
+            self.cards.push(SimulatedCard::new());
+        }
+
+        let mut clock: u32 = 0;
+        loop {
+            let index = match learning.peek() {
+                Some(Reverse((due, index))) if *due <= clock => {
+                    let index = *index;
+                    learning.pop();
+                    index
+                }
+                _ => {
+                    if let Some(index) = queue.pop_front() {
+                        index
+                    } else if let Some(Reverse((due, index))) = learning.pop() {
+                        clock = clock.max(due);
+                        index
+                    } else {
+                        break;
+                    }
+                }
+            };
+
+            let state = self.cards[index].state_on(day);
+            let recalled = self.rng.gen::<f32>() < self.recall_probability(state);
+            match state {
+                NormalState::New(_) => stats.new_count += 1,
+                _ if state.revlog_kind() == RevlogReviewKind::Review => {
+                    stats.review_count += 1;
+                    if recalled {
+                        recalled_reviews += 1;
+                    }
+                }
+                _ => stats.learning_count += 1,
+            }
+            stats.seconds += self.config.seconds_per_review;
+            clock += self.config.seconds_per_review as u32;
+
+            let next = self.answer(state, recalled);
+            let card = &mut self.cards[index];
+            card.state = next;
+            match next
+                .interval_kind()
+                .maybe_as_days(SECS_PER_DAY.saturating_sub(clock))
+            {
+                // a step of 0 would requeue the card at the current clock
+                // value, so it is shown again after at least a second
+                IntervalKind::InSecs(secs) => learning.push(Reverse((clock + secs.max(1), index))),
+                IntervalKind::InDays(days) => {
+                    card.due_day = day + days as i32;
+                    if let NormalState::Review(_) = next {
+                        card.last_review_day = day;
+                    }
+                }
+            }
+        }
+
+        if stats.review_count > 0 {
+            stats.retention = recalled_reviews as f32 / stats.review_count as f32;
+        }
+        stats.average_retrievability = self.average_retrievability(day + 1);
+        stats
+    }
+
+    fn answer(&mut self, state: NormalState, recalled: bool) -> NormalState {
+        let ctx = StateContext {
+            fuzz_factor: Some(self.rng.gen_range(0.0..1.0)),
+            ..StateContext::for_config(&self.config.deck_config)
+        };
+        let states = state.next_states(&ctx);
+        let next = if recalled { states.good } else { states.again };
+        match next {
+            CardState::Normal(state) => state,
+            CardState::Filtered(_) => unreachable!(),
+        }
+    }
+
+    fn recall_probability(&self, state: NormalState) -> f32 {
+        match (self.config.recall_model, state) {
+            (RecallModel::Fixed(probability), _) => probability,
+            (RecallModel::ForgettingCurve, NormalState::Review(review)) => review
+                .memory_state_or_sm2()
+                .retrievability(review.elapsed_days as f32),
+            (RecallModel::ForgettingCurve, _) => LEARNING_STEP_RECALL,
+        }
+    }
+
+    fn average_retrievability(&self, day: i32) -> f32 {
+        let (total, count) = self
+            .cards
+            .iter()
+            .filter_map(|card| match card.state_on(day) {
+                NormalState::Review(review) => Some(
+                    review
+                        .memory_state_or_sm2()
+                        .retrievability(review.elapsed_days as f32),
+                ),
+                _ => None,
+            })
+            .fold((0.0, 0), |(total, count), r| (total + r, count + 1));
+        if count == 0 {
+            0.0
+        } else {
+            total / count as f32
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::collection::open_test_collection;
+    use crate::deckconfig::DeckConfig;
+
+    fn simulator_config(days: u32, recall_model: RecallModel) -> SimulatorConfig {
+        SimulatorConfig {
+            deck_config: DeckConfig::default().inner,
+            days,
+            new_cards_per_day: 10,
+            recall_model,
+            seconds_per_review: DEFAULT_SECONDS_PER_REVIEW,
+            seed: 1,
+        }
+    }
+
+    #[test]
+    fn perfect_recall() {
+        let days = simulate_reviews(&simulator_config(30, RecallModel::Fixed(1.0)), vec![]);
+        assert_eq!(days.len(), 30);
+        // new cards pass both learning steps and graduate with a 1 day interval
+        assert_eq!(days[0].new_count, 10);
+        assert_eq!(days[0].learning_count, 10);
+        assert_eq!(days[0].review_count, 0);
+        assert_eq!(days[0].seconds, 200.0);
+        assert_eq!(days[1].review_count, 10);
+        assert!(days[1..].iter().all(|day| day.retention == 1.0));
+        assert!(days.iter().all(|day| day.new_count == 10));
+    }
+
+    #[test]
+    fn more_new_cards_means_more_reviews() {
+        let mut config = simulator_config(60, RecallModel::ForgettingCurve);
+        config.deck_config.reviews_per_day = 10_000;
+        let total_reviews = |config: &SimulatorConfig| -> u32 {
+            simulate_reviews(config, vec![])
+                .iter()
+                .map(|day| day.review_count)
+                .sum()
+        };
+        let baseline = total_reviews(&config);
+        let more = total_reviews(&SimulatorConfig {
+            new_cards_per_day: 40,
+            ..config.clone()
+        });
+        assert!(more > baseline * 3);
+
+        // the forgetting curve keeps retention near the default 90% target
+        let days = simulate_reviews(&config, vec![]);
+        let retention = days[30..].iter().map(|day| day.retention).sum::<f32>() / 30.0;
+        assert!((0.8..0.97).contains(&retention), "{retention}");
+
+        // the same seed produces the same results
+        assert_eq!(simulate_reviews(&config, vec![]), days);
+    }
+
+    #[test]
+    fn zero_learning_steps_and_no_recall_finish() {
+        let mut config = simulator_config(2, RecallModel::Fixed(0.0));
+        config.new_cards_per_day = 1;
+        config.deck_config.learn_steps = vec![0.0];
+        config.seconds_per_review = 1.0;
+        let days = simulate_reviews(&config, vec![]);
+        // the card is failed once a second until the day ends
+        assert_eq!(days[0].new_count, 1);
+        assert!(days[0].learning_count > 0);
+    }
+
+    #[test]
+    fn invalid_config_is_rejected() {
+        let mut col = open_test_collection();
+        let mut config = simulator_config(30, RecallModel::ForgettingCurve);
+        assert!(col.simulate_reviews(&config, "").is_ok());
+        config.seconds_per_review = 0.5;
+        assert!(col.simulate_reviews(&config, "").is_err());
+        config.seconds_per_review = DEFAULT_SECONDS_PER_REVIEW;
+        config.days = MAX_SIMULATED_DAYS + 1;
+        assert!(col.simulate_reviews(&config, "").is_err());
+        config.days = 30;
+        config.new_cards_per_day = MAX_NEW_CARDS_PER_DAY + 1;
+        assert!(col.simulate_reviews(&config, "").is_err());
+        // the most new cards per day are fine for a short run, but not a long one
+        config.days = 1;
+        config.new_cards_per_day = MAX_NEW_CARDS_PER_DAY;
+        assert!(col.simulate_reviews(&config, "").is_ok());
+        config.days = MAX_SIMULATED_DAYS;
+        assert!(col.simulate_reviews(&config, "").is_err());
+    }
+
+    #[test]
+    fn review_limit_postpones_cards() {
+        let mut config = simulator_config(5, RecallModel::Fixed(1.0));
+        config.new_cards_per_day = 0;
+        config.deck_config.reviews_per_day = 3;
+        let cards = vec![
+            SimulatedCard {
+                state: ReviewState {
+                    scheduled_days: 10,
+                    elapsed_days: 10,
+                    ease_factor: 2.5,
+                    ..Default::default()
+                }
+                .into(),
+                due_day: 0,
+                last_review_day: -10,
+            };
+            5
+        ];
+        let days = simulate_reviews(&config, cards);
+        assert_eq!(days[0].review_count, 3);
+        assert_eq!(days[1].review_count, 2);
+        assert_eq!(days[2].review_count, 0);
+        assert!(days[2].average_retrievability > 0.9);
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use super::SchedulingAlgorithm;
+use crate::scheduler::states::review::INITIAL_EASE_FACTOR;
+use crate::scheduler::states::ReviewState;
+use crate::scheduler::states::StateContext;
+
+pub(crate) const DEFAULT_DESIRED_RETENTION: f32 = 0.9;
+pub(crate) const MINIMUM_DIFFICULTY: f32 = 1.0;
+pub(crate) const MAXIMUM_DIFFICULTY: f32 = 10.0;
+const INITIAL_DIFFICULTY_GOOD: f32 = 5.0;
+const INITIAL_DIFFICULTY_EASY: f32 = 4.0;
+/// How much a rating moves difficulty away from good.
+const DIFFICULTY_DELTA: f32 = 0.9;
+/// Share of the initial difficulty mixed back in after each review, so that
+/// difficulty can't drift to either extreme and stay there.
+const DIFFICULTY_MEAN_REVERSION: f32 = 0.05;
+/// Weights for the growth of stability after a successful review.
+const RECALL_STABILITY_FACTOR: f32 = 1.5;
+const RECALL_STABILITY_DECAY: f32 = 0.15;
+const RECALL_RETRIEVABILITY_FACTOR: f32 = 1.0;
+const RECALL_HARD_PENALTY: f32 = 0.3;
+const RECALL_EASY_BONUS: f32 = 2.6;
+/// Weights for the stability after a lapse.
+const LAPSE_STABILITY_FACTOR: f32 = 1.9;
+const LAPSE_DIFFICULTY_DECAY: f32 = 0.11;
+const LAPSE_STABILITY_POWER: f32 = 0.29;
+const LAPSE_RETRIEVABILITY_FACTOR: f32 = 2.27;
+
+/// A card's position on the forgetting curve, as tracked by [MemoryModel].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryState {
+    /// Days until the probability of recall drops to 90%.
+    pub stability: f32,
+    /// In range `1.0..=10.0`; higher values make stability grow more slowly.
+    pub difficulty: f32,
+}
+
+/// Schedules cards using a stability/difficulty memory model, so that each
+/// review lands where the predicted chance of recall has dropped to the
+/// preset's desired retention.
+pub(crate) struct MemoryModel;
+
+#[derive(Debug, Clone, Copy)]
+enum Rating {
+    Again = 1,
+    Hard,
+    Good,
+    Easy,
+}
+
+impl MemoryState {
+    /// Approximate the memory state of a card scheduled with SM-2. Its
+    /// intervals aim for roughly 90% recall, so the interval stands in for
+    /// stability, and a low ease maps to a high difficulty.
+    pub(crate) fn from_sm2(scheduled_days: u32, ease_factor: f32) -> Self {
+        MemoryState {
+            stability: (scheduled_days as f32).max(0.5),
+            difficulty: (INITIAL_DIFFICULTY_GOOD - (ease_factor - INITIAL_EASE_FACTOR) / 0.24)
+                .clamp(MINIMUM_DIFFICULTY, MAXIMUM_DIFFICULTY),
+        }
+    }
+
+    /// The probability of recalling the card after `elapsed_days`.
+    pub(crate) fn retrievability(self, elapsed_days: f32) -> f32 {
+        retrievability(elapsed_days, self.stability)
+    }
+
+    /// The (unconstrained) number of days until retrievability has dropped to
+    /// `retention`.
+    pub(crate) fn interval_for_retention(self, retention: f32) -> f32 {
+        9.0 * self.stability * (1.0 / retention - 1.0)
+    }
+
+    fn after_review(self, rating: Rating, elapsed_days: u32) -> Self {
+        let retrievability = self.retrievability(elapsed_days as f32);
+        let stability = match rating {
+            Rating::Again => self.stability_after_lapse(retrievability),
+            Rating::Hard => self.stability_after_recall(retrievability, RECALL_HARD_PENALTY),
+            Rating::Good => self.stability_after_recall(retrievability, 1.0),
+            Rating::Easy => self.stability_after_recall(retrievability, RECALL_EASY_BONUS),
+        };
+        MemoryState {
+            stability,
+            difficulty: self.next_difficulty(rating),
+        }
+    }
+
+    fn stability_after_recall(self, retrievability: f32, modifier: f32) -> f32 {
+        self.stability
+            * (1.0
+                + RECALL_STABILITY_FACTOR.exp()
+                    * (11.0 - self.difficulty)
+                    * self.stability.powf(-RECALL_STABILITY_DECAY)
+                    * (((1.0 - retrievability) * RECALL_RETRIEVABILITY_FACTOR).exp() - 1.0)
+                    * modifier)
+    }
+
+    fn stability_after_lapse(self, retrievability: f32) -> f32 {
+        (LAPSE_STABILITY_FACTOR
+            * self.difficulty.powf(-LAPSE_DIFFICULTY_DECAY)
+            * ((self.stability + 1.0).powf(LAPSE_STABILITY_POWER) - 1.0)
+            * ((1.0 - retrievability) * LAPSE_RETRIEVABILITY_FACTOR).exp())
+        .min(self.stability)
+    }
+
+    fn next_difficulty(self, rating: Rating) -> f32 {
+        let difficulty = self.difficulty - DIFFICULTY_DELTA * (rating as u8 as f32 - 3.0);
+        (DIFFICULTY_MEAN_REVERSION * INITIAL_DIFFICULTY_GOOD
+            + (1.0 - DIFFICULTY_MEAN_REVERSION) * difficulty)
+            .clamp(MINIMUM_DIFFICULTY, MAXIMUM_DIFFICULTY)
+    }
+}
+
+/// The probability of recalling a card with the given stability after
+/// `elapsed_days`. Stability is defined as the point where this is 90%.
+pub(crate) fn retrievability(elapsed_days: f32, stability: f32) -> f32 {
+    (1.0 + elapsed_days.max(0.0) / (9.0 * stability.max(0.1))).recip()
+}
+
+/// The chance of recalling a review card with `interval` that is due on day
+/// `due`, on day `today`. Cards without a tracked memory state use their
+/// interval as stability, as SM-2 intervals aim for about 90% recall.
+pub(crate) fn review_card_retrievability(
+    memory_state: Option<MemoryState>,
+    interval: u32,
+    due: i32,
+    today: u32,
+) -> f32 {
+    let last_review = due - interval as i32;
+    let elapsed_days = (today as i32 - last_review).max(0) as f32;
+    let stability = memory_state.map_or(interval.max(1) as f32, |memory| memory.stability);
+    retrievability(elapsed_days, stability)
+}
+
+impl ReviewState {
+    /// The tracked memory state, or an approximation if the card was last
+    /// scheduled by SM-2.
+    pub(crate) fn memory_state_or_sm2(self) -> MemoryState {
+        self.memory_state
+            .unwrap_or_else(|| MemoryState::from_sm2(self.scheduled_days, self.ease_factor))
+    }
+}
+
+impl<'a> StateContext<'a> {
+    /// Desired retention, falling back on the default for presets that
+    /// predate the option.
+    pub(crate) fn desired_retention(&self) -> f32 {
+        if self.desired_retention > 0.0 {
+            self.desired_retention.clamp(0.7, 0.99)
+        } else {
+            DEFAULT_DESIRED_RETENTION
+        }
+    }
+
+    fn memory_model_interval(&self, memory: MemoryState, minimum: u32) -> u32 {
+        let (minimum, maximum) = self.min_and_max_review_intervals(minimum);
+        self.with_review_fuzz(
+            memory.interval_for_retention(self.desired_retention()),
+            minimum,
+            maximum,
+        )
+    }
+}
+
+impl SchedulingAlgorithm for MemoryModel {
+    fn graduating_state(&self, ctx: &StateContext, easy: bool) -> ReviewState {
+        let memory = if easy {
+            MemoryState {
+                stability: ctx.graduating_interval_easy.max(1) as f32,
+                difficulty: INITIAL_DIFFICULTY_EASY,
+            }
+        } else {
+            MemoryState {
+                stability: ctx.graduating_interval_good.max(1) as f32,
+                difficulty: INITIAL_DIFFICULTY_GOOD,
+            }
+        };
+        ReviewState {
+            scheduled_days: ctx.memory_model_interval(memory, 1),
+            ease_factor: ctx.initial_ease_factor,
+            memory_state: Some(memory),
+            ..Default::default()
+        }
+    }
+
+    fn passing_review_states(
+        &self,
+        state: ReviewState,
+        ctx: &StateContext,
+    ) -> (ReviewState, ReviewState, ReviewState) {
+        let memory = state.memory_state_or_sm2();
+        let hard_memory = memory.after_review(Rating::Hard, state.elapsed_days);
+        let good_memory = memory.after_review(Rating::Good, state.elapsed_days);
+        let easy_memory = memory.after_review(Rating::Easy, state.elapsed_days);
+
+        let hard_interval = ctx.memory_model_interval(hard_memory, 1);
+        let good_interval = ctx.memory_model_interval(good_memory, hard_interval + 1);
+        let easy_interval = ctx.memory_model_interval(easy_memory, good_interval + 1);
+
+        let with = |scheduled_days, memory| ReviewState {
+            scheduled_days,
+            elapsed_days: 0,
+            memory_state: Some(memory),
+            ..state
+        };
+        (
+            with(hard_interval, hard_memory),
+            with(good_interval, good_memory),
+            with(easy_interval, easy_memory),
+        )
+    }
+
+    fn failing_review_state(&self, state: ReviewState, ctx: &StateContext) -> ReviewState {
+        let memory = state
+            .memory_state_or_sm2()
+            .after_review(Rating::Again, state.elapsed_days);
+        let state = ReviewState {
+            elapsed_days: 0,
+            memory_state: Some(memory),
+            ..state
+        };
+        ReviewState {
+            scheduled_days: self.failing_review_interval(state, ctx),
+            ..state
+        }
+    }
+
+    fn failing_review_interval(&self, state: ReviewState, ctx: &StateContext) -> u32 {
+        let (minimum, maximum) =
+            ctx.min_and_max_review_intervals(ctx.minimum_lapse_interval.max(1));
+        (state
+            .memory_state_or_sm2()
+            .interval_for_retention(ctx.desired_retention())
+            .round() as u32)
+            .clamp(minimum, maximum)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn memory_model_ctx() -> StateContext<'static> {
+        let mut ctx = StateContext::defaults_for_testing();
+        ctx.algorithm = &MemoryModel;
+        ctx
+    }
+
+    #[test]
+    fn retrievability_at_stability_is_ninety_percent() {
+        let memory = MemoryState {
+            stability: 10.0,
+            difficulty: 5.0,
+        };
+        assert!((memory.retrievability(10.0) - 0.9).abs() < 0.0001);
+        assert_eq!(memory.retrievability(0.0), 1.0);
+        assert!((memory.interval_for_retention(0.9) - 10.0).abs() < 0.0001);
+        assert!(memory.interval_for_retention(0.8) > 20.0);
+    }
+
+    #[test]
+    fn passing_reviews_grow_stability() {
+        let ctx = memory_model_ctx();
+        let state = ReviewState {
+            scheduled_days: 10,
+            elapsed_days: 10,
+            memory_state: Some(MemoryState {
+                stability: 10.0,
+                difficulty: 5.0,
+            }),
+            ..Default::default()
+        };
+        let (hard, good, easy) = MemoryModel.passing_review_states(state, &ctx);
+        assert!(hard.scheduled_days > 10);
+        assert!(good.scheduled_days > hard.scheduled_days);
+        assert!(easy.scheduled_days > good.scheduled_days);
+        assert!(hard.memory_state.unwrap().difficulty > 5.0);
+        assert!(easy.memory_state.unwrap().difficulty < 5.0);
+        // ease is left alone, so switching back to SM-2 is lossless
+        assert_eq!(good.ease_factor, state.ease_factor);
+    }
+
+    #[test]
+    fn lapse_reduces_stability() {
+        let ctx = memory_model_ctx();
+        let state = ReviewState {
+            scheduled_days: 30,
+            elapsed_days: 30,
+            ..Default::default()
+        };
+        let again = MemoryModel.failing_review_state(state, &ctx);
+        let memory = again.memory_state.unwrap();
+        assert!(memory.stability < 30.0);
+        assert!(again.scheduled_days < 30);
+        assert_eq!(
+            again.scheduled_days,
+            MemoryModel.failing_review_interval(again, &ctx)
+        );
+    }
+
+    #[test]
+    fn lower_retention_means_longer_intervals() {
+        let mut ctx = memory_model_ctx();
+        let graduated = MemoryModel.graduating_state(&ctx, false);
+        assert_eq!(graduated.scheduled_days, 1);
+        ctx.graduating_interval_good = 10;
+        assert_eq!(MemoryModel.graduating_state(&ctx, false).scheduled_days, 10);
+        ctx.desired_retention = 0.8;
+        assert_eq!(MemoryModel.graduating_state(&ctx, false).scheduled_days, 23);
+    }
+}
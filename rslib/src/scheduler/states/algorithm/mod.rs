@@ -0,0 +1,48 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+mod memory_model;
+mod sm2;
+
+pub(crate) use memory_model::retrievability;
+pub(crate) use memory_model::review_card_retrievability;
+pub(crate) use memory_model::MemoryModel;
+pub use memory_model::MemoryState;
+pub(crate) use sm2::Sm2;
+
+use super::ReviewState;
+use super::StateContext;
+pub use crate::pb::deckconfig::deck_config::config::SchedulingAlgorithmKind;
+
+/// Decides how a card's intervals grow and shrink once it has graduated.
+/// Learning steps, relearning steps and filtered decks behave the same
+/// regardless of the algorithm in use; only the places where a [ReviewState]
+/// is created or updated are dispatched through here.
+pub(crate) trait SchedulingAlgorithm: Sync {
+    /// The state a learning card enters when it graduates with good (or hard
+    /// when the steps have been changed), or with easy.
+    fn graduating_state(&self, ctx: &StateContext, easy: bool) -> ReviewState;
+
+    /// The states for answering hard, good and easy on a review card.
+    fn passing_review_states(
+        &self,
+        state: ReviewState,
+        ctx: &StateContext,
+    ) -> (ReviewState, ReviewState, ReviewState);
+
+    /// The state after a review card lapses, before relearning steps are
+    /// applied. Lapses and leech status are handled by the caller.
+    fn failing_review_state(&self, state: ReviewState, ctx: &StateContext) -> ReviewState;
+
+    /// The interval a lapsed card will get when it leaves relearning.
+    fn failing_review_interval(&self, state: ReviewState, ctx: &StateContext) -> u32;
+}
+
+impl SchedulingAlgorithmKind {
+    pub(crate) fn algorithm(self) -> &'static dyn SchedulingAlgorithm {
+        match self {
+            SchedulingAlgorithmKind::Sm2 => &Sm2,
+            SchedulingAlgorithmKind::MemoryModel => &MemoryModel,
+        }
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use super::SchedulingAlgorithm;
+use crate::scheduler::states::review::EASE_FACTOR_AGAIN_DELTA;
+use crate::scheduler::states::review::EASE_FACTOR_EASY_DELTA;
+use crate::scheduler::states::review::EASE_FACTOR_HARD_DELTA;
+use crate::scheduler::states::review::MINIMUM_EASE_FACTOR;
+use crate::scheduler::states::ReviewState;
+use crate::scheduler::states::StateContext;
+
+/// The ease factor based algorithm Anki has always used.
+pub(crate) struct Sm2;
+
+impl SchedulingAlgorithm for Sm2 {
+    fn graduating_state(&self, ctx: &StateContext, easy: bool) -> ReviewState {
+        ReviewState {
+            scheduled_days: if easy {
+                ctx.fuzzed_graduating_interval_easy()
+            } else {
+                ctx.fuzzed_graduating_interval_good()
+            },
+            ease_factor: ctx.initial_ease_factor,
+            ..Default::default()
+        }
+    }
+
+    fn passing_review_states(
+        &self,
+        state: ReviewState,
+        ctx: &StateContext,
+    ) -> (ReviewState, ReviewState, ReviewState) {
+        let (hard_interval, good_interval, easy_interval) = passing_review_intervals(state, ctx);
+        let state = ReviewState {
+            elapsed_days: 0,
+            memory_state: None,
+            ..state
+        };
+
+        (
+            ReviewState {
+                scheduled_days: hard_interval,
+                ease_factor: (state.ease_factor + EASE_FACTOR_HARD_DELTA).max(MINIMUM_EASE_FACTOR),
+                ..state
+            },
+            ReviewState {
+                scheduled_days: good_interval,
+                ..state
+            },
+            ReviewState {
+                scheduled_days: easy_interval,
+                ease_factor: state.ease_factor + EASE_FACTOR_EASY_DELTA,
+                ..state
+            },
+        )
+    }
+
+    fn failing_review_state(&self, state: ReviewState, ctx: &StateContext) -> ReviewState {
+        ReviewState {
+            scheduled_days: self.failing_review_interval(state, ctx),
+            elapsed_days: 0,
+            ease_factor: (state.ease_factor + EASE_FACTOR_AGAIN_DELTA).max(MINIMUM_EASE_FACTOR),
+            memory_state: None,
+            ..state
+        }
+    }
+
+    fn failing_review_interval(&self, state: ReviewState, ctx: &StateContext) -> u32 {
+        (((state.scheduled_days as f32) * ctx.lapse_multiplier) as u32)
+            .max(ctx.minimum_lapse_interval)
+            .max(1)
+    }
+}
+
+/// Return the intervals for hard, good and easy, each of which depends on
+/// the previous.
+fn passing_review_intervals(state: ReviewState, ctx: &StateContext) -> (u32, u32, u32) {
+    if state.days_late() < 0 {
+        passing_early_review_intervals(state, ctx)
+    } else {
+        passing_nonearly_review_intervals(state, ctx)
+    }
+}
+
+fn passing_nonearly_review_intervals(state: ReviewState, ctx: &StateContext) -> (u32, u32, u32) {
+    let current_interval = state.scheduled_days as f32;
+    let days_late = state.days_late().max(0) as f32;
+    let hard_factor = ctx.hard_multiplier;
+    let hard_minimum = if hard_factor <= 1.0 {
+        0
+    } else {
+        state.scheduled_days + 1
+    };
+
+    let hard_interval =
+        constrain_passing_interval(ctx, current_interval * hard_factor, hard_minimum, true);
+    let good_interval = constrain_passing_interval(
+        ctx,
+        (current_interval + days_late / 2.0) * state.ease_factor,
+        hard_interval + 1,
+        true,
+    );
+    let easy_interval = constrain_passing_interval(
+        ctx,
+        (current_interval + days_late) * state.ease_factor * ctx.easy_multiplier,
+        good_interval + 1,
+        true,
+    );
+
+    (hard_interval, good_interval, easy_interval)
+}
+
+/// Mostly direct port from the Python version for now, so we can confirm
+/// implementation is correct.
+/// FIXME: this needs reworking in the future; it overly penalizes reviews
+/// done shortly before the due date.
+fn passing_early_review_intervals(state: ReviewState, ctx: &StateContext) -> (u32, u32, u32) {
+    let scheduled = state.scheduled_days as f32;
+    let elapsed = (state.scheduled_days as f32) + (state.days_late() as f32);
+
+    let hard_interval = {
+        let factor = ctx.hard_multiplier;
+        let half_usual = factor / 2.0;
+        constrain_passing_interval(
+            ctx,
+            (elapsed * factor).max(scheduled * half_usual),
+            0,
+            false,
+        )
+    };
+
+    let good_interval =
+        constrain_passing_interval(ctx, (elapsed * state.ease_factor).max(scheduled), 0, false);
+
+    let easy_interval = {
+        let reduced_bonus = ctx.easy_multiplier - (ctx.easy_multiplier - 1.0) / 2.0;
+        constrain_passing_interval(
+            ctx,
+            (elapsed * state.ease_factor).max(scheduled) * reduced_bonus,
+            0,
+            false,
+        )
+    };
+
+    (hard_interval, good_interval, easy_interval)
+}
+
+/// Transform the provided hard/good/easy interval.
+/// - Apply configured interval multiplier.
+/// - Apply fuzz.
+/// - Ensure it is at least `minimum`, and at least 1.
+/// - Ensure it is at or below the configured maximum interval.
+fn constrain_passing_interval(ctx: &StateContext, interval: f32, minimum: u32, fuzz: bool) -> u32 {
+    let interval = interval * ctx.interval_multiplier;
+    let (minimum, maximum) = ctx.min_and_max_review_intervals(minimum);
+    if fuzz {
+        ctx.with_review_fuzz(interval, minimum, maximum)
+    } else {
+        (interval.round() as u32).clamp(minimum, maximum)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn extreme_multiplier_fuzz() {
+        let mut ctx = StateContext::defaults_for_testing();
+        // our calculations should work correctly with a low ease or non-default
+        // multiplier
+        let state = ReviewState {
+            scheduled_days: 1,
+            elapsed_days: 1,
+            ease_factor: 1.3,
+            lapses: 0,
+            leeched: false,
+            memory_state: None,
+        };
+        ctx.fuzz_factor = Some(0.0);
+        assert_eq!(passing_review_intervals(state, &ctx), (2, 3, 4));
+
+        // this is a silly multiplier, but it shouldn't underflow
+        ctx.interval_multiplier = 0.1;
+        assert_eq!(passing_review_intervals(state, &ctx), (2, 3, 4));
+        ctx.fuzz_factor = Some(0.99);
+        assert_eq!(passing_review_intervals(state, &ctx), (2, 4, 6));
+
+        // maximum must be respected no matter what
+        ctx.interval_multiplier = 10.0;
+        ctx.maximum_review_interval = 5;
+        assert_eq!(passing_review_intervals(state, &ctx), (5, 5, 5));
+    }
+}
@@ -1,7 +1,16 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use chrono::Datelike;
+use chrono::Days;
+use chrono::NaiveDate;
+
 use super::StateContext;
+use crate::deckconfig::EasyDate;
 
 /// Describes a range of days for which a certain amount of fuzz is applied to
 /// the new interval.
@@ -29,13 +38,174 @@ static FUZZ_RANGES: [FuzzRange; 3] = [
     },
 ];
 
+/// The number of review cards due on each upcoming day, used to place new
+/// due dates on the least busy day of their fuzz range.
+///
+/// Only the days that fuzz ranges can fall on are loaded. As those depend on
+/// the states being calculated, the balancer records the days it was asked
+/// about, so the caller can load any it is missing and calculate again.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct LoadBalancer {
+    /// Keyed by days from today.
+    due_counts: HashMap<u32, u32>,
+    /// The days `due_counts` covers, if any.
+    loaded: Option<(u32, u32)>,
+    /// The days fuzz ranges have been taken from.
+    needed: Cell<Option<(u32, u32)>>,
+}
+
+impl LoadBalancer {
+    pub(crate) fn new(due_counts: HashMap<u32, u32>, days: RangeInclusive<u32>) -> Self {
+        Self {
+            due_counts,
+            loaded: Some((*days.start(), *days.end())),
+            needed: Cell::new(None),
+        }
+    }
+
+    /// If fuzz ranges have fallen outside the loaded days, the days that
+    /// should be loaded instead, which include the ones already loaded.
+    pub(crate) fn days_to_load(&self) -> Option<RangeInclusive<u32>> {
+        let (lower, upper) = self.needed.get()?;
+        match self.loaded {
+            Some((start, end)) if start <= lower && upper <= end => None,
+            Some((start, end)) => Some(start.min(lower)..=end.max(upper)),
+            None => Some(lower..=upper),
+        }
+    }
+
+    /// Return the interval in `lower..=upper` with the fewest cards due
+    /// relative to the day's share of a normal workload, as given by
+    /// `easy_days`. Ties are broken with `fuzz_factor`, so the result is
+    /// stable for a given card and set of due counts.
+    fn least_busy_interval(
+        &self,
+        lower: u32,
+        upper: u32,
+        fuzz_factor: f32,
+        easy_days: Option<&EasyDays>,
+    ) -> u32 {
+        self.needed.set(Some(match self.needed.get() {
+            Some((start, end)) => (start.min(lower), end.max(upper)),
+            None => (lower, upper),
+        }));
+        let load = |interval| {
+            let due_count = self.due_counts.get(&interval).copied().unwrap_or_default();
+            let share = easy_days.map_or(1.0, |days| days.share(interval));
+            (due_count + 1) as f32 / share
+        };
+        let lowest = (lower..=upper).map(load).fold(f32::INFINITY, f32::min);
+        // if every day is to be avoided, all of them are candidates
+        let candidates: Vec<u32> = (lower..=upper)
+            .filter(|&interval| load(interval) == lowest)
+            .collect();
+        let index = (fuzz_factor * candidates.len() as f32) as usize;
+        candidates[index.min(candidates.len() - 1)]
+    }
+}
+
+/// Days of the week, or specific dates, on which fewer reviews should fall.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct EasyDays<'a> {
+    /// The share of a normal day's reviews for each weekday, Monday first.
+    /// Empty if every weekday is a normal day.
+    percentages: &'a [f32],
+    /// Overrides the weekday share on particular dates.
+    dates: Vec<(NaiveDate, f32)>,
+    /// The local date of the current scheduler day.
+    today: NaiveDate,
+}
+
+impl<'a> EasyDays<'a> {
+    /// None if neither a weekday nor a date has a reduced share. Invalid
+    /// dates are ignored; they are refused when a config is saved.
+    pub(crate) fn new(
+        percentages: &'a [f32],
+        dates: &[EasyDate],
+        today: NaiveDate,
+    ) -> Option<Self> {
+        let percentages = if percentages.len() == 7 {
+            percentages
+        } else {
+            &[]
+        };
+        let dates: Vec<_> = dates
+            .iter()
+            .filter_map(|d| Some((d.naive_date()?, d.percentage)))
+            .collect();
+        let reduced = percentages.iter().any(|&p| p < 1.0) || dates.iter().any(|&(_, p)| p < 1.0);
+        reduced.then_some(Self {
+            percentages,
+            dates,
+            today,
+        })
+    }
+
+    /// The share of a normal day's reviews that should fall on the day
+    /// `days_from_today` days after today.
+    fn share(&self, days_from_today: u32) -> f32 {
+        let Some(date) = self
+            .today
+            .checked_add_days(Days::new(days_from_today as u64))
+        else {
+            return 1.0;
+        };
+        let percentage = self
+            .dates
+            .iter()
+            .find(|&&(d, _)| d == date)
+            .map(|&(_, percentage)| percentage)
+            .or_else(|| {
+                self.percentages
+                    .get(date.weekday().num_days_from_monday() as usize)
+                    .copied()
+            })
+            .unwrap_or(1.0);
+        percentage.clamp(0.0, 1.0)
+    }
+
+    /// Pick a day in `lower..=upper`, where `fuzz_factor` is in `0.0..1.0`.
+    /// Each day's chance of being picked is proportional to its share.
+    pub(crate) fn pick_interval(&self, lower: u32, upper: u32, fuzz_factor: f32) -> u32 {
+        let total: f32 = (lower..=upper).map(|interval| self.share(interval)).sum();
+        if total == 0.0 {
+            return (lower as f32 + fuzz_factor * ((1 + upper - lower) as f32)).floor() as u32;
+        }
+        let mut remaining = fuzz_factor * total;
+        let mut last_allowed = upper;
+        for interval in lower..=upper {
+            let share = self.share(interval);
+            if share > 0.0 {
+                last_allowed = interval;
+            }
+            remaining -= share;
+            if remaining < 0.0 {
+                return interval;
+            }
+        }
+        // only reachable through rounding errors
+        last_allowed
+    }
+}
+
 impl<'a> StateContext<'a> {
     /// Apply fuzz, respecting the passed bounds.
     /// Caller must ensure reasonable bounds.
     pub(crate) fn with_review_fuzz(&self, interval: f32, minimum: u32, maximum: u32) -> u32 {
         if let Some(fuzz_factor) = self.fuzz_factor {
             let (lower, upper) = constrained_fuzz_bounds(interval, minimum, maximum);
-            (lower as f32 + fuzz_factor * ((1 + upper - lower) as f32)).floor() as u32
+            if let Some(load_balancer) = self.load_balancer {
+                load_balancer.least_busy_interval(
+                    lower,
+                    upper,
+                    fuzz_factor,
+                    self.easy_days.as_ref(),
+                )
+            } else if let Some(easy_days) = &self.easy_days {
+                easy_days.pick_interval(lower, upper, fuzz_factor)
+            } else {
+                (lower as f32 + fuzz_factor * ((1 + upper - lower) as f32)).floor() as u32
+            }
         } else {
             (interval.round() as u32).clamp(minimum, maximum)
         }
@@ -147,4 +317,115 @@ mod test {
         assert_lower_middle_upper!(100.0, 1, 99, 93, 96, 99);
         assert_lower_middle_upper!(100.0, 97, 103, 97, 100, 103);
     }
+
+    #[test]
+    fn load_balancer() {
+        let load_balancer = LoadBalancer::new(
+            [
+                (33, 5),
+                (34, 1),
+                (35, 7),
+                (36, 1),
+                (37, 9),
+                (38, 4),
+                (39, 8),
+                (40, 3),
+                (41, 6),
+            ]
+            .into_iter()
+            .collect(),
+            30..=60,
+        );
+        let mut ctx = StateContext::defaults_for_testing();
+        ctx.load_balancer = Some(&load_balancer);
+
+        // the emptiest days of 33..=41 are 34 and 36, and the fuzz factor
+        // decides between them
+        for (fuzz_factor, interval) in [(0.0, 34), (0.49, 34), (0.5, 36), (0.99, 36)] {
+            ctx.fuzz_factor = Some(fuzz_factor);
+            assert_eq!(ctx.with_review_fuzz(37.0, 1, 1000), interval);
+        }
+        // days without reviews are preferred, and bounds are respected
+        ctx.fuzz_factor = Some(0.0);
+        assert_eq!(ctx.with_review_fuzz(50.0, 1, 1000), 46);
+        assert_eq!(ctx.with_review_fuzz(37.0, 1, 33), 33);
+        // without fuzz, the load is not considered
+        ctx.fuzz_factor = None;
+        assert_eq!(ctx.with_review_fuzz(37.0, 1, 1000), 37);
+
+        // all of those ranges were within the loaded days
+        assert_eq!(load_balancer.days_to_load(), None);
+        ctx.fuzz_factor = Some(0.0);
+        ctx.with_review_fuzz(100.0, 1, 1000);
+        assert_eq!(load_balancer.days_to_load(), Some(30..=107));
+        // before anything is loaded, only the days asked about are needed
+        let load_balancer = LoadBalancer::default();
+        assert_eq!(load_balancer.days_to_load(), None);
+        ctx.load_balancer = Some(&load_balancer);
+        ctx.with_review_fuzz(37.0, 1, 1000);
+        assert_eq!(load_balancer.days_to_load(), Some(33..=41));
+    }
+
+    #[test]
+    fn easy_days() {
+        // Saturday counts half, and Sunday is to be avoided
+        let percentages = [1.0, 1.0, 1.0, 1.0, 1.0, 0.5, 0.0];
+        let monday = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        assert_eq!(EasyDays::new(&[1.0; 7], &[], monday), None);
+        assert_eq!(EasyDays::new(&percentages[..6], &[], monday), None);
+        let easy_days = EasyDays::new(&percentages, &[], monday).unwrap();
+        let mut ctx = StateContext::defaults_for_testing();
+        ctx.easy_days = Some(easy_days);
+
+        // 33..=41 days from a Monday runs from a Saturday to a Sunday; the
+        // Sundays (34 and 41) are never picked, and the Saturdays (33 and 40)
+        // take up half as much of the range as the weekdays
+        let picked: Vec<_> = (0..8)
+            .map(|step| {
+                ctx.fuzz_factor = Some(step as f32 / 8.0);
+                ctx.with_review_fuzz(37.0, 1, 1000)
+            })
+            .collect();
+        assert_eq!(picked, [33, 35, 36, 36, 37, 38, 39, 39]);
+
+        // with the load balancer, an empty Sunday loses to a busy weekday, and
+        // reviews on a Saturday count double
+        let load_balancer = LoadBalancer::new([(39, 3), (40, 1)].into_iter().collect(), 39..=41);
+        ctx.load_balancer = Some(&load_balancer);
+        ctx.fuzz_factor = Some(0.0);
+        assert_eq!(ctx.with_review_fuzz(40.0, 39, 41), 39);
+        ctx.fuzz_factor = Some(0.99);
+        assert_eq!(ctx.with_review_fuzz(40.0, 39, 41), 40);
+        // if only easy days are available, they are used anyway
+        assert_eq!(ctx.with_review_fuzz(41.0, 41, 41), 41);
+    }
+
+    #[test]
+    fn easy_dates() {
+        let date = |date: &str, percentage| EasyDate {
+            date: date.into(),
+            percentage,
+        };
+        let monday = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        assert_eq!(EasyDays::new(&[], &[date("2024-02-07", 1.0)], monday), None);
+
+        // a date on its own is enough; 2024-02-07 is 37 days away
+        let dates = [date("2024-02-07", 0.0)];
+        let mut ctx = StateContext::defaults_for_testing();
+        ctx.easy_days = EasyDays::new(&[], &dates, monday);
+        let picked: Vec<_> = (0..8)
+            .map(|step| {
+                ctx.fuzz_factor = Some(step as f32 / 8.0);
+                ctx.with_review_fuzz(37.0, 1, 1000)
+            })
+            .collect();
+        assert_eq!(picked, [33, 34, 35, 36, 38, 39, 40, 41]);
+
+        // a date overrides its weekday, so a Sunday (2024-02-04) can be used
+        let percentages = [1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0];
+        let dates = [date("2024-02-04", 1.0)];
+        ctx.easy_days = EasyDays::new(&percentages, &dates, monday);
+        ctx.fuzz_factor = Some(0.0);
+        assert_eq!(ctx.with_review_fuzz(34.0, 34, 35), 34);
+    }
 }
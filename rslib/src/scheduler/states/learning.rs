@@ -49,12 +49,7 @@ impl LearnState {
             .into()
         } else {
             // steps modified while card in learning
-            ReviewState {
-                scheduled_days: ctx.fuzzed_graduating_interval_good(),
-                ease_factor: ctx.initial_ease_factor,
-                ..Default::default()
-            }
-            .into()
+            ctx.algorithm.graduating_state(ctx, false).into()
         }
     }
 
@@ -66,20 +61,11 @@ impl LearnState {
             }
             .into()
         } else {
-            ReviewState {
-                scheduled_days: ctx.fuzzed_graduating_interval_good(),
-                ease_factor: ctx.initial_ease_factor,
-                ..Default::default()
-            }
-            .into()
+            ctx.algorithm.graduating_state(ctx, false).into()
         }
     }
 
     fn answer_easy(self, ctx: &StateContext) -> ReviewState {
-        ReviewState {
-            scheduled_days: ctx.fuzzed_graduating_interval_easy(),
-            ease_factor: ctx.initial_ease_factor,
-            ..Default::default()
-        }
+        ctx.algorithm.graduating_state(ctx, true)
     }
 }
@@ -1,6 +1,7 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+pub(crate) mod algorithm;
 pub(crate) mod filtered;
 pub(crate) mod fuzz;
 pub(crate) mod interval_kind;
@@ -13,6 +14,9 @@ pub(crate) mod rescheduling_filter;
 pub(crate) mod review;
 pub(crate) mod steps;
 
+pub use algorithm::MemoryState;
+pub(crate) use algorithm::SchedulingAlgorithm;
+pub use algorithm::SchedulingAlgorithmKind;
 pub use filtered::FilteredState;
 pub(crate) use interval_kind::IntervalKind;
 pub use learning::LearnState;
@@ -23,7 +27,10 @@ pub use relearning::RelearnState;
 pub use rescheduling_filter::ReschedulingFilterState;
 pub use review::ReviewState;
 
+use self::fuzz::EasyDays;
+use self::fuzz::LoadBalancer;
 use self::steps::LearningSteps;
+use crate::deckconfig::DeckConfigInner;
 use crate::revlog::RevlogReviewKind;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -83,6 +90,13 @@ pub(crate) struct StateContext<'a> {
     /// In range `0.0..1.0`. Used to pick the final interval from the fuzz
     /// range.
     pub fuzz_factor: Option<f32>,
+    /// If set, fuzzed review intervals favour the least busy day in their
+    /// fuzz range.
+    pub load_balancer: Option<&'a LoadBalancer>,
+    /// If set, fuzzed review intervals avoid days with a reduced workload.
+    pub easy_days: Option<EasyDays<'a>>,
+    /// Determines how review intervals are calculated.
+    pub algorithm: &'a dyn SchedulingAlgorithm,
 
     // learning
     pub steps: LearningSteps<'a>,
@@ -102,6 +116,9 @@ pub(crate) struct StateContext<'a> {
     pub lapse_multiplier: f32,
     pub minimum_lapse_interval: u32,
 
+    // memory model
+    pub desired_retention: f32,
+
     // filtered
     pub in_filtered_deck: bool,
     pub preview_step: u32,
@@ -117,10 +134,39 @@ impl<'a> StateContext<'a> {
         (minimum, maximum)
     }
 
+    /// Context for a card in a normal deck using `config`, without fuzz. Used
+    /// when replaying or projecting reviews instead of answering a card.
+    pub(crate) fn for_config(config: &'a DeckConfigInner) -> Self {
+        Self {
+            fuzz_factor: None,
+            load_balancer: None,
+            easy_days: None,
+            algorithm: config.scheduling_algorithm().algorithm(),
+            steps: LearningSteps::new(&config.learn_steps),
+            graduating_interval_good: config.graduating_interval_good,
+            graduating_interval_easy: config.graduating_interval_easy,
+            initial_ease_factor: config.initial_ease,
+            hard_multiplier: config.hard_multiplier,
+            easy_multiplier: config.easy_multiplier,
+            interval_multiplier: config.interval_multiplier,
+            maximum_review_interval: config.maximum_review_interval,
+            leech_threshold: config.leech_threshold,
+            relearn_steps: LearningSteps::new(&config.relearn_steps),
+            lapse_multiplier: config.lapse_multiplier,
+            minimum_lapse_interval: config.minimum_lapse_interval,
+            desired_retention: config.desired_retention,
+            in_filtered_deck: false,
+            preview_step: 0,
+        }
+    }
+
     #[cfg(test)]
     pub(crate) fn defaults_for_testing() -> Self {
         Self {
             fuzz_factor: None,
+            load_balancer: None,
+            easy_days: None,
+            algorithm: &algorithm::Sm2,
             steps: LearningSteps::new(&[1.0, 10.0]),
             graduating_interval_good: 1,
             graduating_interval_easy: 4,
@@ -133,6 +179,7 @@ impl<'a> StateContext<'a> {
             relearn_steps: LearningSteps::new(&[10.0]),
             lapse_multiplier: 0.0,
             minimum_lapse_interval: 1,
+            desired_retention: 0.9,
             in_filtered_deck: false,
             preview_step: 10,
         }
@@ -4,6 +4,7 @@
 use super::interval_kind::IntervalKind;
 use super::CardState;
 use super::LearnState;
+use super::MemoryState;
 use super::RelearnState;
 use super::SchedulingStates;
 use super::StateContext;
@@ -22,6 +23,8 @@ pub struct ReviewState {
     pub ease_factor: f32,
     pub lapses: u32,
     pub leeched: bool,
+    /// Only set when the card was last scheduled by the memory model.
+    pub memory_state: Option<MemoryState>,
 }
 
 impl Default for ReviewState {
@@ -32,6 +35,7 @@ impl Default for ReviewState {
             ease_factor: INITIAL_EASE_FACTOR,
             lapses: 0,
             leeched: false,
+            memory_state: None,
         }
     }
 }
@@ -56,32 +60,28 @@ impl ReviewState {
     }
 
     pub(crate) fn next_states(self, ctx: &StateContext) -> SchedulingStates {
-        let (hard_interval, good_interval, easy_interval) = self.passing_review_intervals(ctx);
+        let (hard, good, easy) = ctx.algorithm.passing_review_states(self, ctx);
 
         SchedulingStates {
             current: self.into(),
             again: self.answer_again(ctx),
-            hard: self.answer_hard(hard_interval).into(),
-            good: self.answer_good(good_interval).into(),
-            easy: self.answer_easy(easy_interval).into(),
+            hard: hard.into(),
+            good: good.into(),
+            easy: easy.into(),
         }
     }
 
     pub(crate) fn failing_review_interval(self, ctx: &StateContext) -> u32 {
-        (((self.scheduled_days as f32) * ctx.lapse_multiplier) as u32)
-            .max(ctx.minimum_lapse_interval)
-            .max(1)
+        ctx.algorithm.failing_review_interval(self, ctx)
     }
 
     fn answer_again(self, ctx: &StateContext) -> CardState {
         let lapses = self.lapses + 1;
         let leeched = leech_threshold_met(lapses, ctx.leech_threshold);
         let again_review = ReviewState {
-            scheduled_days: self.failing_review_interval(ctx),
-            elapsed_days: 0,
-            ease_factor: (self.ease_factor + EASE_FACTOR_AGAIN_DELTA).max(MINIMUM_EASE_FACTOR),
             lapses,
             leeched,
+            ..ctx.algorithm.failing_review_state(self, ctx)
         };
 
         if let Some(again_delay) = ctx.relearn_steps.again_delay_secs_relearn() {
@@ -98,103 +98,14 @@ impl ReviewState {
         }
     }
 
-    fn answer_hard(self, scheduled_days: u32) -> ReviewState {
-        ReviewState {
-            scheduled_days,
-            elapsed_days: 0,
-            ease_factor: (self.ease_factor + EASE_FACTOR_HARD_DELTA).max(MINIMUM_EASE_FACTOR),
-            ..self
-        }
-    }
-
-    fn answer_good(self, scheduled_days: u32) -> ReviewState {
-        ReviewState {
-            scheduled_days,
-            elapsed_days: 0,
-            ..self
-        }
-    }
-
-    fn answer_easy(self, scheduled_days: u32) -> ReviewState {
-        ReviewState {
-            scheduled_days,
-            elapsed_days: 0,
-            ease_factor: self.ease_factor + EASE_FACTOR_EASY_DELTA,
-            ..self
-        }
-    }
-
-    /// Return the intervals for hard, good and easy, each of which depends on
-    /// the previous.
-    fn passing_review_intervals(self, ctx: &StateContext) -> (u32, u32, u32) {
-        if self.days_late() < 0 {
-            self.passing_early_review_intervals(ctx)
-        } else {
-            self.passing_nonearly_review_intervals(ctx)
-        }
-    }
-
-    fn passing_nonearly_review_intervals(self, ctx: &StateContext) -> (u32, u32, u32) {
-        let current_interval = self.scheduled_days as f32;
-        let days_late = self.days_late().max(0) as f32;
-        let hard_factor = ctx.hard_multiplier;
-        let hard_minimum = if hard_factor <= 1.0 {
+    /// The value stored in the revlog's factor column: the ease factor for
+    /// SM-2, and 0 for the memory model, which has no ease.
+    pub(crate) fn revlog_factor(self) -> u32 {
+        if self.memory_state.is_some() {
             0
         } else {
-            self.scheduled_days + 1
-        };
-
-        let hard_interval =
-            constrain_passing_interval(ctx, current_interval * hard_factor, hard_minimum, true);
-        let good_interval = constrain_passing_interval(
-            ctx,
-            (current_interval + days_late / 2.0) * self.ease_factor,
-            hard_interval + 1,
-            true,
-        );
-        let easy_interval = constrain_passing_interval(
-            ctx,
-            (current_interval + days_late) * self.ease_factor * ctx.easy_multiplier,
-            good_interval + 1,
-            true,
-        );
-
-        (hard_interval, good_interval, easy_interval)
-    }
-
-    /// Mostly direct port from the Python version for now, so we can confirm
-    /// implementation is correct.
-    /// FIXME: this needs reworking in the future; it overly penalizes reviews
-    /// done shortly before the due date.
-    fn passing_early_review_intervals(self, ctx: &StateContext) -> (u32, u32, u32) {
-        let scheduled = self.scheduled_days as f32;
-        let elapsed = (self.scheduled_days as f32) + (self.days_late() as f32);
-
-        let hard_interval = {
-            let factor = ctx.hard_multiplier;
-            let half_usual = factor / 2.0;
-            constrain_passing_interval(
-                ctx,
-                (elapsed * factor).max(scheduled * half_usual),
-                0,
-                false,
-            )
-        };
-
-        let good_interval =
-            constrain_passing_interval(ctx, (elapsed * self.ease_factor).max(scheduled), 0, false);
-
-        let easy_interval = {
-            let reduced_bonus = ctx.easy_multiplier - (ctx.easy_multiplier - 1.0) / 2.0;
-            constrain_passing_interval(
-                ctx,
-                (elapsed * self.ease_factor).max(scheduled) * reduced_bonus,
-                0,
-                false,
-            )
-        };
-
-        (hard_interval, good_interval, easy_interval)
+            (self.ease_factor * 1000.0).round() as u32
+        }
     }
 }
 
@@ -210,21 +121,6 @@ fn leech_threshold_met(lapses: u32, threshold: u32) -> bool {
     }
 }
 
-/// Transform the provided hard/good/easy interval.
-/// - Apply configured interval multiplier.
-/// - Apply fuzz.
-/// - Ensure it is at least `minimum`, and at least 1.
-/// - Ensure it is at or below the configured maximum interval.
-fn constrain_passing_interval(ctx: &StateContext, interval: f32, minimum: u32, fuzz: bool) -> u32 {
-    let interval = interval * ctx.interval_multiplier;
-    let (minimum, maximum) = ctx.min_and_max_review_intervals(minimum);
-    if fuzz {
-        ctx.with_review_fuzz(interval, minimum, maximum)
-    } else {
-        (interval.round() as u32).clamp(minimum, maximum)
-    }
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
@@ -257,31 +153,4 @@ mod test {
         assert!(leech_threshold_met(2, 1));
         assert!(leech_threshold_met(3, 1));
     }
-
-    #[test]
-    fn extreme_multiplier_fuzz() {
-        let mut ctx = StateContext::defaults_for_testing();
-        // our calculations should work correctly with a low ease or non-default
-        // multiplier
-        let state = ReviewState {
-            scheduled_days: 1,
-            elapsed_days: 1,
-            ease_factor: 1.3,
-            lapses: 0,
-            leeched: false,
-        };
-        ctx.fuzz_factor = Some(0.0);
-        assert_eq!(state.passing_review_intervals(&ctx), (2, 3, 4));
-
-        // this is a silly multiplier, but it shouldn't underflow
-        ctx.interval_multiplier = 0.1;
-        assert_eq!(state.passing_review_intervals(&ctx), (2, 3, 4));
-        ctx.fuzz_factor = Some(0.99);
-        assert_eq!(state.passing_review_intervals(&ctx), (2, 4, 6));
-
-        // maximum must be respected no matter what
-        ctx.interval_multiplier = 10.0;
-        ctx.maximum_review_interval = 5;
-        assert_eq!(state.passing_review_intervals(&ctx), (5, 5, 5));
-    }
 }
@@ -5,6 +5,7 @@ use chrono::DateTime;
 use chrono::Datelike;
 use chrono::Duration;
 use chrono::FixedOffset;
+use chrono::NaiveDate;
 use chrono::Timelike;
 
 use crate::prelude::*;
@@ -18,6 +19,17 @@ pub struct SchedTimingToday {
     pub next_day_at: TimestampSecs,
 }
 
+impl SchedTimingToday {
+    /// The local date of the current scheduler day, which is the day on
+    /// which it started.
+    pub(crate) fn local_date(&self) -> Result<NaiveDate> {
+        // an hour past the last rollover, so a daylight savings change cannot
+        // move us into the previous day
+        let day_start = self.next_day_at.adding_secs(-86_400 + 3_600);
+        Ok(day_start.local_datetime()?.date_naive())
+    }
+}
+
 /// Timing information for the current day.
 /// - creation_secs is a UNIX timestamp of the collection creation time
 /// - creation_utc_offset is the UTC offset at collection creation time
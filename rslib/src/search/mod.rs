@@ -204,9 +204,10 @@ impl Collection {
         match mode {
             SortMode::NoOrder => (),
             SortMode::Builtin { column, reverse } => {
-                prepare_sort(self, column, item_type)?;
+                let today = self.timing_today()?.days_elapsed;
+                prepare_sort(self, column, item_type, today)?;
                 sql.push_str(" order by ");
-                write_order(sql, item_type, column, reverse)?;
+                write_order(sql, item_type, column, reverse, today)?;
             }
             SortMode::Custom(order_clause) => {
                 sql.push_str(" order by ");
@@ -331,9 +332,10 @@ fn write_order(
     item_type: ReturnItemType,
     column: Column,
     reverse: bool,
+    today: u32,
 ) -> Result<()> {
     let order = match item_type {
-        ReturnItemType::Cards => card_order_from_sort_column(column),
+        ReturnItemType::Cards => card_order_from_sort_column(column, today),
         ReturnItemType::Notes => note_order_from_sort_column(column),
     };
     require!(!order.is_empty(), "Can't sort {item_type:?} by {column:?}.");
@@ -350,7 +352,7 @@ fn write_order(
     Ok(())
 }
 
-fn card_order_from_sort_column(column: Column) -> Cow<'static, str> {
+fn card_order_from_sort_column(column: Column, today: u32) -> Cow<'static, str> {
     match column {
         Column::CardMod => "c.mod asc".into(),
         Column::Cards => concat!(
@@ -368,9 +370,14 @@ fn card_order_from_sort_column(column: Column) -> Cow<'static, str> {
         Column::NoteMod => "n.mod asc, c.ord asc".into(),
         Column::Notetype => "(select pos from sort_order where ntid = n.mid) asc".into(),
         Column::Reps => "c.reps asc".into(),
+        Column::Retrievability => format!(
+            "c.type = {} desc, extract_recall(c.data, c.ivl, c.due, c.odue, {today}) asc",
+            CardType::Review as i8
+        )
+        .into(),
         Column::SortField => "n.sfld collate nocase asc, c.ord asc".into(),
         Column::Tags => "n.tags asc".into(),
-        Column::Answer | Column::Custom | Column::Question => "".into(),
+        Column::Answer | Column::Custom | Column::MediaSize | Column::Question => "".into(),
     }
 }
 
@@ -383,17 +390,24 @@ fn note_order_from_sort_column(column: Column) -> Cow<'static, str> {
         | Column::Ease
         | Column::Interval
         | Column::Lapses
-        | Column::Reps => "(select pos from sort_order where nid = n.id) asc".into(),
+        | Column::Reps
+        | Column::Retrievability => "(select pos from sort_order where nid = n.id) asc".into(),
         Column::NoteCreation => "n.id asc".into(),
         Column::NoteMod => "n.mod asc".into(),
         Column::Notetype => "(select pos from sort_order where ntid = n.mid) asc".into(),
         Column::SortField => "n.sfld collate nocase asc".into(),
         Column::Tags => "n.tags asc".into(),
-        Column::Answer | Column::Custom | Column::Question => "".into(),
+        Column::Answer | Column::Custom | Column::MediaSize | Column::Question => "".into(),
     }
 }
 
-fn prepare_sort(col: &mut Collection, column: Column, item_type: ReturnItemType) -> Result<()> {
+fn prepare_sort(
+    col: &mut Collection,
+    column: Column,
+    item_type: ReturnItemType,
+    today: u32,
+) -> Result<()> {
+    let retrievability_sql;
     let sql = match item_type {
         ReturnItemType::Cards => match column {
             Column::Cards => include_str!("template_order.sql"),
@@ -410,6 +424,11 @@ fn prepare_sort(col: &mut Collection, column: Column, item_type: ReturnItemType)
             Column::Interval => include_str!("note_interval_order.sql"),
             Column::Lapses => include_str!("note_lapses_order.sql"),
             Column::Reps => include_str!("note_reps_order.sql"),
+            Column::Retrievability => {
+                retrievability_sql =
+                    format!(include_str!("note_retrievability_order.sql"), today = today);
+                &retrievability_sql
+            }
             Column::Notetype => include_str!("notetype_order.sql"),
             _ => return Ok(()),
         },
@@ -0,0 +1,11 @@
+DROP TABLE IF EXISTS sort_order;
+CREATE TEMPORARY TABLE sort_order (
+  pos integer PRIMARY KEY,
+  nid integer NOT NULL UNIQUE
+);
+INSERT INTO sort_order (nid)
+SELECT nid
+FROM cards
+WHERE type = 2
+GROUP BY nid
+ORDER BY AVG(extract_recall(data, ivl, due, odue, {today}));
\ No newline at end of file
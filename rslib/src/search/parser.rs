@@ -101,6 +101,7 @@ pub enum PropertyKind {
     Ease(f32),
     Position(u32),
     Rated(i32, RatingKind),
+    Retrievability(f32),
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -392,7 +393,7 @@ fn parse_resched(s: &str) -> ParseResult<SearchNode> {
     })
 }
 
-/// eg prop:ivl>3, prop:ease!=2.5
+/// eg prop:ivl>3, prop:ease!=2.5, prop:r<0.8
 fn parse_prop(prop_clause: &str) -> ParseResult<SearchNode> {
     let (tail, prop) = alt::<_, _, ParseError, _>((
         tag("ivl"),
@@ -403,6 +404,7 @@ fn parse_prop(prop_clause: &str) -> ParseResult<SearchNode> {
         tag("pos"),
         tag("rated"),
         tag("resched"),
+        tag("r"),
     ))(prop_clause)
     .map_err(|_| {
         parse_failure(
@@ -442,6 +444,7 @@ fn parse_prop(prop_clause: &str) -> ParseResult<SearchNode> {
         "reps" => PropertyKind::Reps(parse_u32(num, prop_clause)?),
         "lapses" => PropertyKind::Lapses(parse_u32(num, prop_clause)?),
         "pos" => PropertyKind::Position(parse_u32(num, prop_clause)?),
+        "r" => PropertyKind::Retrievability(parse_f32(num, prop_clause)?),
         _ => unreachable!(),
     };
 
@@ -903,6 +906,21 @@ mod test {
                 kind: PropertyKind::Ease(3.3)
             })]
         );
+        assert_eq!(
+            parse("prop:r<0.8")?,
+            vec![Search(Property {
+                operator: "<".into(),
+                kind: PropertyKind::Retrievability(0.8)
+            })]
+        );
+        // "r" must not shadow longer property names
+        assert_eq!(
+            parse("prop:reps=2")?,
+            vec![Search(Property {
+                operator: "=".into(),
+                kind: PropertyKind::Reps(2)
+            })]
+        );
 
         Ok(())
     }
@@ -1178,5 +1196,9 @@ mod test {
             failkind("prop:ease<1,3"),
             SearchErrorKind::InvalidNumber { .. }
         ));
+        assert!(matches!(
+            failkind("prop:r>"),
+            SearchErrorKind::InvalidNumber { .. }
+        ));
     }
 }
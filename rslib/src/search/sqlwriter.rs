@@ -308,6 +308,13 @@ impl SqlWriter<'_> {
                 write!(self.sql, "factor {} {}", op, (ease * 1000.0) as u32).unwrap()
             }
             PropertyKind::Rated(days, ease) => self.write_rated(op, i64::from(*days), ease)?,
+            PropertyKind::Retrievability(r) => write!(
+                self.sql,
+                "(c.type = {t} and extract_recall(c.data, c.ivl, c.due, c.odue, {today}) {op} {r})",
+                t = CardType::Review as u8,
+                today = timing.days_elapsed,
+            )
+            .unwrap(),
         }
 
         Ok(())
@@ -921,6 +928,13 @@ mod test {
             )
         );
         assert_eq!(s(ctx, "prop:rated>-5:3").0, s(ctx, "rated:5:3").0);
+        assert_eq!(
+            s(ctx, "prop:r<0.8").0,
+            format!(
+                "((c.type = 2 and extract_recall(c.data, c.ivl, c.due, c.odue, {}) < 0.8))",
+                timing.days_elapsed
+            )
+        );
 
         // note types by name
         assert_eq!(
@@ -25,6 +25,7 @@ impl Collection {
 
         let (average_secs, total_secs) = average_and_total_secs_strings(&revlog);
         let (due_date, due_position) = self.due_date_and_position(&card)?;
+        let retrievability = card.retrievability(self.timing_today()?.days_elapsed);
 
         Ok(pb::stats::CardStatsResponse {
             card_id: card.id.into(),
@@ -44,6 +45,7 @@ impl Collection {
             card_type: nt.get_template(card.template_idx)?.name.clone(),
             notetype: nt.name.clone(),
             revlog: revlog.iter().rev().map(stats_revlog_entry).collect(),
+            retrievability,
         })
     }
 
@@ -105,6 +107,7 @@ fn stats_revlog_entry(entry: &RevlogEntry) -> pb::stats::card_stats_response::St
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::browser_table::Column;
     use crate::collection::open_test_collection;
     use crate::search::SortMode;
 
@@ -122,4 +125,45 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn retrievability() -> Result<()> {
+        let mut col = open_test_collection();
+
+        let nt = col.get_notetype_by_name("Basic")?.unwrap();
+        let mut note = nt.new_note();
+        col.add_note(&mut note, DeckId(1))?;
+        let cid = col.search_cards("", SortMode::NoOrder)?[0];
+        assert_eq!(col.card_stats(cid)?.retrievability, None);
+
+        // due today after a 10 day interval
+        let mut card = col.storage.get_card(cid)?.unwrap();
+        card.ctype = CardType::Review;
+        card.queue = CardQueue::Review;
+        card.interval = 10;
+        card.due = col.timing_today()?.days_elapsed as i32;
+        col.storage.update_card(&card)?;
+
+        let retrievability = col.card_stats(cid)?.retrievability.unwrap();
+        assert!((retrievability - 0.9).abs() < 0.001);
+        assert_eq!(
+            col.search_cards("prop:r<0.95", SortMode::NoOrder)?,
+            vec![cid]
+        );
+        assert!(col
+            .search_cards("prop:r<0.85", SortMode::NoOrder)?
+            .is_empty());
+        assert_eq!(
+            col.search_cards(
+                "",
+                SortMode::Builtin {
+                    column: Column::Retrievability,
+                    reverse: false
+                }
+            )?,
+            vec![cid]
+        );
+
+        Ok(())
+    }
 }
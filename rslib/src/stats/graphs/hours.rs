@@ -31,7 +31,9 @@ impl GraphsContext {
         'outer: for review in &self.revlog {
             if matches!(
                 review.review_kind,
-                RevlogReviewKind::Filtered | RevlogReviewKind::Manual
+                RevlogReviewKind::Filtered
+                    | RevlogReviewKind::Manual
+                    | RevlogReviewKind::Rescheduled
             ) {
                 continue;
             }
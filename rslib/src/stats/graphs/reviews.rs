@@ -9,7 +9,10 @@ impl GraphsContext {
     pub(super) fn review_counts_and_times(&self) -> ReviewCountsAndTimes {
         let mut data = ReviewCountsAndTimes::default();
         for review in &self.revlog {
-            if review.review_kind == RevlogReviewKind::Manual {
+            if matches!(
+                review.review_kind,
+                RevlogReviewKind::Manual | RevlogReviewKind::Rescheduled
+            ) {
                 continue;
             }
             let day = (review.id.as_secs().elapsed_secs_since(self.next_day_start) / 86_400) as i32;
@@ -37,7 +40,7 @@ impl GraphsContext {
                     count.filtered += 1;
                     time.filtered += review.taken_millis;
                 }
-                RevlogReviewKind::Manual => unreachable!(),
+                RevlogReviewKind::Manual | RevlogReviewKind::Rescheduled => unreachable!(),
             }
         }
         data
@@ -13,10 +13,11 @@ use serde_derive::Serialize;
 use serde_json::Value;
 
 use crate::prelude::*;
+use crate::scheduler::states::MemoryState;
 use crate::serde::default_on_invalid;
 
 /// Helper for serdeing the card data column.
-#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 #[serde(default)]
 pub(crate) struct CardData {
     #[serde(
@@ -30,6 +31,20 @@ pub(crate) struct CardData {
     /// state.
     #[serde(default, rename = "cd", skip_serializing_if = "meta_is_empty")]
     pub(crate) custom_data: String,
+    /// Memory model stability.
+    #[serde(
+        rename = "s",
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "default_on_invalid"
+    )]
+    pub(crate) stability: Option<f32>,
+    /// Memory model difficulty.
+    #[serde(
+        rename = "d",
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "default_on_invalid"
+    )]
+    pub(crate) difficulty: Option<f32>,
 }
 
 impl CardData {
@@ -37,6 +52,20 @@ impl CardData {
         Self {
             original_position: card.original_position,
             custom_data: card.custom_data.clone(),
+            stability: card.memory_state.map(|m| m.stability),
+            difficulty: card.memory_state.map(|m| m.difficulty),
+        }
+    }
+
+    /// Only returns a memory state if both values are present.
+    pub(crate) fn memory_state(&self) -> Option<MemoryState> {
+        if let (Some(stability), Some(difficulty)) = (self.stability, self.difficulty) {
+            Some(MemoryState {
+                stability,
+                difficulty,
+            })
+        } else {
+            None
         }
     }
 
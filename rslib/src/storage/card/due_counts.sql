@@ -0,0 +1,15 @@
+SELECT max(
+    (
+      CASE
+        WHEN odid = 0 THEN due
+        ELSE odue
+      END
+    ) - :today,
+    0
+  ) AS days,
+  count()
+FROM cards
+WHERE queue IN (:review_queue, :day_learn_queue)
+  AND id != :excluded_card
+  AND days BETWEEN :lower AND :upper
+GROUP BY days
@@ -2,6 +2,7 @@
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
 use crate::card::CardQueue;
+use crate::card::CardType;
 use crate::decks::FilteredSearchOrder;
 use crate::decks::FilteredSearchTerm;
 
@@ -26,6 +27,16 @@ then (ivl / cast({today}-due+0.001 as real)) else 100000+due end)",
             );
             &temp_string
         }
+        FilteredSearchOrder::RetrievabilityAscending => {
+            temp_string = format!(
+                "
+(case when c.type = {rev_type} then extract_recall(c.data, c.ivl, c.due, c.odue, {today})
+else 2 end)",
+                rev_type = CardType::Review as i8,
+                today = today
+            );
+            &temp_string
+        }
     };
 
     format!("{} limit {}", order, term.limit)
@@ -4,9 +4,11 @@
 pub(crate) mod data;
 pub(crate) mod filtered;
 
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::fmt;
+use std::ops::RangeInclusive;
 use std::result;
 
 use rusqlite::named_params;
@@ -79,6 +81,7 @@ fn row_to_card(row: &Row) -> result::Result<Card, rusqlite::Error> {
         original_deck_id: row.get(15)?,
         flags: row.get(16)?,
         original_position: data.original_position,
+        memory_state: data.memory_state(),
         custom_data: data.custom_data,
     })
 }
@@ -407,6 +410,14 @@ impl super::SqliteStorage {
             .map_err(Into::into)
     }
 
+    /// The home deck of each note's cards, as (note, deck) pairs.
+    pub(crate) fn all_note_home_decks(&self) -> Result<Vec<(NoteId, DeckId)>> {
+        self.db
+            .prepare("select distinct nid, (case when odid = 0 then did else odid end) from cards")?
+            .query_and_then([], |r| Ok((r.get(0)?, r.get(1)?)))?
+            .collect()
+    }
+
     pub(crate) fn all_cards_of_note(&self, nid: NoteId) -> Result<Vec<Card>> {
         self.db
             .prepare_cached(concat!(include_str!("get_card.sql"), " where nid = ?"))?
@@ -602,6 +613,31 @@ impl super::SqliteStorage {
             .unwrap()
     }
 
+    /// The number of review and interday learning cards due on each of `days`,
+    /// keyed by days from today. Overdue cards count as due today.
+    /// `excluded_card` is not counted.
+    pub(crate) fn review_due_counts(
+        &self,
+        today: u32,
+        excluded_card: CardId,
+        days: RangeInclusive<u32>,
+    ) -> Result<HashMap<u32, u32>> {
+        self.db
+            .prepare_cached(include_str!("due_counts.sql"))?
+            .query_and_then(
+                named_params! {
+                    ":review_queue": CardQueue::Review as i8,
+                    ":day_learn_queue": CardQueue::DayLearn as i8,
+                    ":today": today,
+                    ":excluded_card": excluded_card,
+                    ":lower": days.start(),
+                    ":upper": days.end(),
+                },
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?
+            .collect()
+    }
+
     pub(crate) fn all_cards_at_or_above_position(&self, start: u32) -> Result<Vec<Card>> {
         self.with_searched_cards_table(false, || {
             self.db
@@ -692,6 +728,7 @@ enum ReviewOrderSubclause {
     EaseAscending,
     EaseDescending,
     RelativeOverdueness { today: u32 },
+    RetrievabilityAscending { today: u32 },
 }
 
 impl fmt::Display for ReviewOrderSubclause {
@@ -709,6 +746,10 @@ impl fmt::Display for ReviewOrderSubclause {
                 temp_string = format!("ivl / cast({today}-due+0.001 as real)", today = today);
                 &temp_string
             }
+            ReviewOrderSubclause::RetrievabilityAscending { today } => {
+                temp_string = format!("extract_recall(data, ivl, due, odue, {today}) asc");
+                &temp_string
+            }
         };
         write!(f, "{}", clause)
     }
@@ -726,6 +767,9 @@ fn review_order_sql(order: ReviewCardOrder, today: u32) -> String {
         ReviewCardOrder::RelativeOverdueness => {
             vec![ReviewOrderSubclause::RelativeOverdueness { today }]
         }
+        ReviewCardOrder::RetrievabilityAscending => {
+            vec![ReviewOrderSubclause::RetrievabilityAscending { today }]
+        }
     };
     subclauses.push(ReviewOrderSubclause::Random);
 
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS revlog_algorithms (
+  id integer PRIMARY KEY NOT NULL,
+  algorithm integer NOT NULL
+);
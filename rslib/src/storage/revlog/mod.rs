@@ -7,6 +7,7 @@ use rusqlite::params;
 use rusqlite::types::FromSql;
 use rusqlite::types::FromSqlError;
 use rusqlite::types::ValueRef;
+use rusqlite::OptionalExtension;
 use rusqlite::Row;
 
 use super::SqliteStorage;
@@ -14,6 +15,7 @@ use crate::error::Result;
 use crate::prelude::*;
 use crate::revlog::RevlogEntry;
 use crate::revlog::RevlogReviewKind;
+use crate::scheduler::states::SchedulingAlgorithmKind;
 
 pub(crate) struct StudiedToday {
     pub cards: u32,
@@ -99,6 +101,40 @@ impl SqliteStorage {
         self.db
             .prepare_cached("delete from revlog where id = ?")?
             .execute([id])?;
+        self.remove_revlog_algorithm(id)
+    }
+
+    /// The algorithm that produced the entry's interval. Only entries
+    /// scheduled by something other than SM-2 are recorded, so entries from
+    /// older clients are treated as SM-2.
+    pub(crate) fn revlog_algorithm(&self, id: RevlogId) -> Result<SchedulingAlgorithmKind> {
+        let algorithm: Option<i32> = self
+            .db
+            .prepare_cached("select algorithm from revlog_algorithms where id = ?")?
+            .query_row([id], |row| row.get(0))
+            .optional()?;
+        Ok(algorithm
+            .and_then(SchedulingAlgorithmKind::from_i32)
+            .unwrap_or(SchedulingAlgorithmKind::Sm2))
+    }
+
+    pub(crate) fn set_revlog_algorithm(
+        &self,
+        id: RevlogId,
+        algorithm: SchedulingAlgorithmKind,
+    ) -> Result<()> {
+        self.db
+            .prepare_cached(
+                "insert or replace into revlog_algorithms (id, algorithm) values (?, ?)",
+            )?
+            .execute(params![id, algorithm as i32])?;
+        Ok(())
+    }
+
+    pub(crate) fn remove_revlog_algorithm(&self, id: RevlogId) -> Result<()> {
+        self.db
+            .prepare_cached("delete from revlog_algorithms where id = ?")?
+            .execute([id])?;
         Ok(())
     }
 
@@ -143,12 +179,19 @@ impl SqliteStorage {
         let start = day_cutoff.adding_secs(-86_400).as_millis();
         self.db
             .prepare_cached(include_str!("studied_today.sql"))?
-            .query_map([start.0, RevlogReviewKind::Manual as i64], |row| {
-                Ok(StudiedToday {
-                    cards: row.get(0)?,
-                    seconds: row.get(1)?,
-                })
-            })?
+            .query_map(
+                [
+                    start.0,
+                    RevlogReviewKind::Manual as i64,
+                    RevlogReviewKind::Rescheduled as i64,
+                ],
+                |row| {
+                    Ok(StudiedToday {
+                        cards: row.get(0)?,
+                        seconds: row.get(1)?,
+                    })
+                },
+            )?
             .next()
             .unwrap()
             .map_err(Into::into)
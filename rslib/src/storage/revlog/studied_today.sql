@@ -2,4 +2,4 @@ SELECT COUNT(),
   coalesce(sum(time) / 1000.0, 0.0)
 FROM revlog
 WHERE id > ?
-  AND type != ?
\ No newline at end of file
+  AND type NOT IN (?, ?)
\ No newline at end of file
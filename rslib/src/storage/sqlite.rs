@@ -15,6 +15,7 @@ use rusqlite::params;
 use rusqlite::Connection;
 use unicase::UniCase;
 
+use super::card::data::CardData;
 use super::upgrades::SCHEMA_MAX_VERSION;
 use super::upgrades::SCHEMA_MIN_VERSION;
 use super::upgrades::SCHEMA_STARTING_VERSION;
@@ -22,6 +23,7 @@ use super::SchemaVersion;
 use crate::config::schema11::schema11_config_as_string;
 use crate::error::DbErrorKind;
 use crate::prelude::*;
+use crate::scheduler::states::algorithm::review_card_retrievability;
 use crate::scheduler::timing::local_minutes_west_for_stamp;
 use crate::scheduler::timing::v1_creation_date;
 use crate::text::without_combining;
@@ -67,6 +69,7 @@ fn open_or_create_collection_db(path: &Path) -> Result<Connection> {
     add_regexp_tags_function(&db)?;
     add_without_combining_function(&db)?;
     add_fnvhash_function(&db)?;
+    add_extract_recall_function(&db)?;
 
     db.create_collation("unicase", unicase_compare)?;
 
@@ -114,6 +117,26 @@ fn add_fnvhash_function(db: &Connection) -> rusqlite::Result<()> {
     })
 }
 
+/// Adds sql function extract_recall(data, ivl, due, odue, today) -> the
+/// predicted chance of recalling a review card on day `today`.
+/// Caller must ensure the card is a review card.
+fn add_extract_recall_function(db: &Connection) -> rusqlite::Result<()> {
+    db.create_scalar_function(
+        "extract_recall",
+        5,
+        FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let data: CardData = ctx.get(0)?;
+            let interval: u32 = ctx.get(1)?;
+            let due: i32 = ctx.get(2)?;
+            let original_due: i32 = ctx.get(3)?;
+            let today: u32 = ctx.get(4)?;
+            let due = if original_due != 0 { original_due } else { due };
+            Ok(review_card_retrievability(data.memory_state(), interval, due, today) as f64)
+        },
+    )
+}
+
 /// Adds sql function regexp(regex, string) -> is_match
 /// Taken from the rusqlite docs
 type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
@@ -290,6 +313,12 @@ impl SqliteStorage {
             storage.commit_trx()?;
         }
 
+        // not part of a schema version, as older clients ignore the table; it
+        // is kept when downgrading, so full syncs and exports preserve it
+        storage
+            .db
+            .execute_batch(include_str!("revlog/algorithms.sql"))?;
+
         Ok(storage)
     }
 
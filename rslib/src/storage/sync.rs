@@ -61,6 +61,26 @@ impl SqliteStorage {
         }
         Ok(())
     }
+
+    /// Assign the provided usn to all objects pending sync, as if they had
+    /// been sent to a server.
+    pub(crate) fn mark_pending_objects_synced(&self, usn: Usn) -> Result<()> {
+        for table in [
+            "cards",
+            "notes",
+            "revlog",
+            "graves",
+            "decks",
+            "deck_config",
+            "tags",
+            "notetypes",
+        ] {
+            self.db
+                .prepare_cached(&format!("update {table} set usn=? where usn=-1"))?
+                .execute([usn])?;
+        }
+        Ok(())
+    }
 }
 
 impl Usn {
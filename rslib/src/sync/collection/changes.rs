@@ -5,6 +5,7 @@
 //! all in a single request.
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 
 use serde::Deserialize;
 use serde::Serialize;
@@ -79,6 +80,12 @@ where
             "sending"
         );
 
+        let sent_decks: HashSet<_> = local
+            .decks_and_config
+            .decks
+            .iter()
+            .map(|deck| deck.id())
+            .collect();
         self.progress.local_update += local.notetypes.len()
             + local.decks_and_config.decks.len()
             + local.decks_and_config.config.len()
@@ -103,6 +110,9 @@ where
             + remote.decks_and_config.config.len()
             + remote.tags.len();
 
+        self.conflicts.decks = self
+            .col
+            .deck_conflicts(&sent_decks, &remote.decks_and_config.decks)?;
         self.col.apply_changes(remote, state.server_usn)?;
         self.fire_progress_cb(true);
         Ok(())
@@ -13,9 +13,14 @@ use crate::card::CardType;
 use crate::notes::Note;
 use crate::prelude::*;
 use crate::revlog::RevlogEntry;
+use crate::scheduler::states::SchedulingAlgorithmKind;
 use crate::serde::deserialize_int_from_number;
 use crate::storage::card::data::card_data_string;
 use crate::storage::card::data::CardData;
+use crate::sync::collection::conflicts::cards_differ;
+use crate::sync::collection::conflicts::notes_differ;
+use crate::sync::collection::conflicts::SyncConflict;
+use crate::sync::collection::conflicts::SyncConflicts;
 use crate::sync::collection::normal::ClientSyncState;
 use crate::sync::collection::normal::NormalSyncProgress;
 use crate::sync::collection::normal::NormalSyncer;
@@ -38,6 +43,10 @@ pub struct Chunk {
     pub done: bool,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub revlog: Vec<RevlogEntry>,
+    /// The algorithm of each entry in `revlog` that was not scheduled by
+    /// SM-2. Older clients ignore this, and don't send it.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub revlog_algorithms: Vec<(RevlogId, i32)>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub cards: Vec<CardEntry>,
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
@@ -110,7 +119,8 @@ where
                 chunk.cards.len() + chunk.notes.len() + chunk.revlog.len();
 
             let done = chunk.done;
-            self.col.apply_chunk(chunk, state.pending_usn)?;
+            self.col
+                .apply_chunk(chunk, state.pending_usn, &mut self.conflicts)?;
 
             self.fire_progress_cb(true);
 
@@ -160,11 +170,18 @@ impl Collection {
 
     /// pending_usn is used to decide whether the local objects are newer.
     /// If the provided objects are not modified locally, the USN inside
-    /// the individual objects is used.
-    pub(in crate::sync) fn apply_chunk(&mut self, chunk: Chunk, pending_usn: Usn) -> Result<()> {
+    /// the individual objects is used. Objects modified on both sides are
+    /// added to `conflicts`.
+    pub(in crate::sync) fn apply_chunk(
+        &mut self,
+        chunk: Chunk,
+        pending_usn: Usn,
+        conflicts: &mut SyncConflicts,
+    ) -> Result<()> {
         self.merge_revlog(chunk.revlog)?;
-        self.merge_cards(chunk.cards, pending_usn)?;
-        self.merge_notes(chunk.notes, pending_usn)
+        self.merge_revlog_algorithms(chunk.revlog_algorithms)?;
+        self.merge_cards(chunk.cards, pending_usn, &mut conflicts.cards)?;
+        self.merge_notes(chunk.notes, pending_usn, &mut conflicts.notes)
     }
 
     fn merge_revlog(&self, entries: Vec<RevlogEntry>) -> Result<()> {
@@ -174,48 +191,91 @@ impl Collection {
         Ok(())
     }
 
-    fn merge_cards(&self, entries: Vec<CardEntry>, pending_usn: Usn) -> Result<()> {
+    fn merge_revlog_algorithms(&self, entries: Vec<(RevlogId, i32)>) -> Result<()> {
+        for (id, algorithm) in entries {
+            if let Some(algorithm) = SchedulingAlgorithmKind::from_i32(algorithm) {
+                self.storage.set_revlog_algorithm(id, algorithm)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn merge_cards(
+        &self,
+        entries: Vec<CardEntry>,
+        pending_usn: Usn,
+        conflicts: &mut Vec<SyncConflict<Card>>,
+    ) -> Result<()> {
         for entry in entries {
-            self.add_or_update_card_if_newer(entry, pending_usn)?;
+            if let Some(conflict) = self.add_or_update_card_if_newer(entry, pending_usn)? {
+                conflicts.push(conflict);
+            }
         }
         Ok(())
     }
 
-    fn add_or_update_card_if_newer(&self, entry: CardEntry, pending_usn: Usn) -> Result<()> {
-        let proceed = if let Some(existing_card) = self.storage.get_card(entry.id)? {
-            !existing_card.usn.is_pending_sync(pending_usn) || existing_card.mtime < entry.mtime
+    fn add_or_update_card_if_newer(
+        &self,
+        entry: CardEntry,
+        pending_usn: Usn,
+    ) -> Result<Option<SyncConflict<Card>>> {
+        let card: Card = entry.into();
+        let mut conflict = None;
+        let proceed = if let Some(existing_card) = self.storage.get_card(card.id)? {
+            let pending = existing_card.usn.is_pending_sync(pending_usn);
+            let proceed = !pending || existing_card.mtime < card.mtime;
+            if pending && cards_differ(&existing_card, &card) {
+                conflict = Some(SyncConflict::new(existing_card, card.clone(), !proceed));
+            }
+            proceed
         } else {
             true
         };
         if proceed {
-            let card = entry.into();
             self.storage.add_or_update_card(&card)?;
         }
-        Ok(())
+        Ok(conflict)
     }
 
-    fn merge_notes(&mut self, entries: Vec<NoteEntry>, pending_usn: Usn) -> Result<()> {
+    fn merge_notes(
+        &mut self,
+        entries: Vec<NoteEntry>,
+        pending_usn: Usn,
+        conflicts: &mut Vec<SyncConflict<Note>>,
+    ) -> Result<()> {
         for entry in entries {
-            self.add_or_update_note_if_newer(entry, pending_usn)?;
+            if let Some(conflict) = self.add_or_update_note_if_newer(entry, pending_usn)? {
+                conflicts.push(conflict);
+            }
         }
         Ok(())
     }
 
-    fn add_or_update_note_if_newer(&mut self, entry: NoteEntry, pending_usn: Usn) -> Result<()> {
-        let proceed = if let Some(existing_note) = self.storage.get_note(entry.id)? {
-            !existing_note.usn.is_pending_sync(pending_usn) || existing_note.mtime < entry.mtime
+    fn add_or_update_note_if_newer(
+        &mut self,
+        entry: NoteEntry,
+        pending_usn: Usn,
+    ) -> Result<Option<SyncConflict<Note>>> {
+        let mut note: Note = entry.into();
+        let mut conflict = None;
+        let proceed = if let Some(existing_note) = self.storage.get_note(note.id)? {
+            let pending = existing_note.usn.is_pending_sync(pending_usn);
+            let proceed = !pending || existing_note.mtime < note.mtime;
+            if pending && notes_differ(&existing_note, &note) {
+                conflict = Some(SyncConflict::new(existing_note, note.clone(), !proceed));
+            }
+            proceed
         } else {
             true
         };
         if proceed {
-            let mut note: Note = entry.into();
             let nt = self
                 .get_notetype(note.notetype_id)?
                 .or_invalid("note missing notetype")?;
             note.prepare_for_update(&nt, false)?;
             self.storage.add_or_update_note(&note)?;
         }
-        Ok(())
+        Ok(conflict)
     }
 
     // Local->remote chunks
@@ -275,6 +335,12 @@ impl Collection {
         }
 
         // the fetch associated objects, and return
+        for &id in &revlog_ids {
+            let algorithm = self.storage.revlog_algorithm(id)?;
+            if algorithm != SchedulingAlgorithmKind::Sm2 {
+                chunk.revlog_algorithms.push((id, algorithm as i32));
+            }
+        }
         chunk.revlog = revlog_ids
             .into_iter()
             .map(|id| {
@@ -312,10 +378,7 @@ impl Collection {
 
 impl From<CardEntry> for Card {
     fn from(e: CardEntry) -> Self {
-        let CardData {
-            original_position,
-            custom_data,
-        } = CardData::from_str(&e.data);
+        let data = CardData::from_str(&e.data);
         Card {
             id: e.id,
             note_id: e.nid,
@@ -334,8 +397,9 @@ impl From<CardEntry> for Card {
             original_due: e.odue,
             original_deck_id: e.odid,
             flags: e.flags,
-            original_position,
-            custom_data,
+            original_position: data.original_position,
+            memory_state: data.memory_state(),
+            custom_data: data.custom_data,
         }
     }
 }
@@ -412,7 +476,8 @@ pub fn server_apply_chunk(
     col: &mut Collection,
     state: &mut ServerSyncState,
 ) -> Result<()> {
-    col.apply_chunk(req.chunk, state.client_usn)
+    // the client reports the conflicts to the user
+    col.apply_chunk(req.chunk, state.client_usn, &mut SyncConflicts::default())
 }
 
 impl Usn {
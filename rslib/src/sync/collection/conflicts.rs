@@ -0,0 +1,132 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! When an object has been modified on both sides since the last sync, only
+//! the more recently modified version is kept. The versions that were
+//! discarded are collected during a normal sync and returned to the client,
+//! so it can show the user what was overwritten, and put back any edits they
+//! want to keep.
+
+use std::collections::HashSet;
+
+use crate::card::Card;
+use crate::decks::DeckSchema11;
+use crate::notes::Note;
+use crate::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncConflict<T> {
+    /// The version now in the collection.
+    pub kept: T,
+    /// The version that was overwritten.
+    pub discarded: T,
+    /// True if the local version was kept, and the remote one discarded.
+    pub kept_local: bool,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SyncConflicts {
+    pub notes: Vec<SyncConflict<Note>>,
+    pub cards: Vec<SyncConflict<Card>>,
+    pub decks: Vec<SyncConflict<Deck>>,
+}
+
+impl SyncConflicts {
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty() && self.cards.is_empty() && self.decks.is_empty()
+    }
+}
+
+impl<T> SyncConflict<T> {
+    pub(in crate::sync) fn new(local: T, remote: T, kept_local: bool) -> Self {
+        let (kept, discarded) = if kept_local {
+            (local, remote)
+        } else {
+            (remote, local)
+        };
+        Self {
+            kept,
+            discarded,
+            kept_local,
+        }
+    }
+}
+
+/// Modification times and usns are ignored, so the same edit made on both
+/// sides is not reported.
+pub(in crate::sync) fn notes_differ(a: &Note, b: &Note) -> bool {
+    a.notetype_id != b.notetype_id || a.fields() != b.fields() || a.tags != b.tags
+}
+
+pub(in crate::sync) fn cards_differ(a: &Card, b: &Card) -> bool {
+    a != &Card {
+        mtime: a.mtime,
+        usn: a.usn,
+        ..b.clone()
+    }
+}
+
+/// Only the name and options are compared, as the study counts are changed by
+/// reviewing on either side.
+fn decks_differ(a: &Deck, b: &Deck) -> bool {
+    a.name != b.name || a.kind != b.kind
+}
+
+impl Collection {
+    /// Compare the decks the client has just sent with the ones received from
+    /// the server. Must be called before the received decks are merged.
+    pub(in crate::sync) fn deck_conflicts(
+        &self,
+        sent: &HashSet<DeckId>,
+        received: &[DeckSchema11],
+    ) -> Result<Vec<SyncConflict<Deck>>> {
+        let mut conflicts = vec![];
+        for remote in received {
+            if !sent.contains(&remote.id()) {
+                continue;
+            }
+            let Some(local) = self.storage.get_deck(remote.id())? else {
+                continue;
+            };
+            let remote: Deck = remote.clone().into();
+            if decks_differ(&local, &remote) {
+                // mirrors merge_decks()
+                let kept_local = local.mtime_secs > remote.mtime_secs;
+                conflicts.push(SyncConflict::new(local, remote, kept_local));
+            }
+        }
+        Ok(conflicts)
+    }
+
+    /// Write the discarded versions back to the collection as new changes, so
+    /// they will be sent on the next sync. Objects that have since been
+    /// deleted are skipped. Returns the number of objects restored.
+    pub fn restore_sync_conflicts(&mut self, conflicts: SyncConflicts) -> Result<OpOutput<usize>> {
+        self.transact(Op::RestoreSyncConflicts, |col| {
+            let usn = col.usn()?;
+            let mut count = 0;
+            for conflict in conflicts.notes {
+                if col.storage.get_note(conflict.discarded.id)?.is_some() {
+                    let mut note = conflict.discarded;
+                    col.update_note_inner(&mut note)?;
+                    count += 1;
+                }
+            }
+            for conflict in conflicts.cards {
+                if let Some(original) = col.storage.get_card(conflict.discarded.id)? {
+                    let mut card = conflict.discarded;
+                    col.update_card_inner(&mut card, original, usn)?;
+                    count += 1;
+                }
+            }
+            for conflict in conflicts.decks {
+                if let Some(original) = col.storage.get_deck(conflict.discarded.id)? {
+                    let mut deck = conflict.discarded;
+                    col.update_deck_inner(&mut deck, original, usn)?;
+                    count += 1;
+                }
+            }
+            Ok(count)
+        })
+    }
+}
@@ -28,11 +28,22 @@ impl Collection {
         self.full_download_with_server(server).await
     }
 
-    pub(crate) async fn full_download_with_server(self, server: HttpSyncClient) -> Result<()> {
+    pub(crate) async fn full_download_with_server(self, mut server: HttpSyncClient) -> Result<()> {
+        let resumable = server
+            .meta_with_redirect()
+            .await?
+            .0
+            .supports_resumable_full_sync();
         let col_path = self.col_path.clone();
         let _col_folder = col_path.parent().or_invalid("couldn't get col_folder")?;
         self.close(None)?;
-        let out_data = server.download(EmptyInput::request()).await?.data;
+        let out_data = if resumable {
+            server
+                .download_resumable(&col_path.with_extension("partial"))
+                .await?
+        } else {
+            server.download(EmptyInput::request()).await?.data
+        };
         // check file ok
         let temp_file = new_tempfile_in_parent_of(&col_path)?;
         write_file(temp_file.path(), out_data)?;
@@ -7,6 +7,7 @@ use serde::Deserialize;
 use serde::Serialize;
 use tracing::debug;
 
+use crate::config::BoolKey;
 use crate::config::SchedulerVersion;
 use crate::prelude::*;
 use crate::sync::collection::normal::ClientSyncState;
@@ -19,8 +20,10 @@ use crate::sync::error::OrHttpErr;
 use crate::sync::http_client::HttpSyncClient;
 use crate::sync::request::IntoSyncRequest;
 use crate::sync::request::SyncRequest;
+use crate::sync::version::SyncVersion;
 use crate::sync::version::SYNC_VERSION_09_V2_SCHEDULER;
 use crate::sync::version::SYNC_VERSION_10_V2_TIMEZONE;
+use crate::sync::version::SYNC_VERSION_CLIENT_DEFAULT;
 use crate::sync::version::SYNC_VERSION_MAX;
 use crate::sync::version::SYNC_VERSION_MIN;
 use crate::version::sync_client_version;
@@ -43,6 +46,15 @@ pub struct SyncMeta {
     pub host_number: u32,
     #[serde(default)]
     pub empty: bool,
+    /// The highest sync version the server supports. Not sent by servers
+    /// prior to version 12.
+    #[serde(rename = "maxVersion", default)]
+    pub max_sync_version: u8,
+    /// True if the collection syncs with peers instead of a server. Their
+    /// usns can't be compared with a server's, so a full sync is required
+    /// when only one side is true.
+    #[serde(default)]
+    pub peer: bool,
     #[serde(skip)]
     pub v2_scheduler_or_later: bool,
     #[serde(skip)]
@@ -50,6 +62,10 @@ pub struct SyncMeta {
 }
 
 impl SyncMeta {
+    pub(in crate::sync) fn supports_resumable_full_sync(&self) -> bool {
+        SyncVersion(self.max_sync_version).is_resumable()
+    }
+
     pub(in crate::sync) fn compared_to_remote(
         &self,
         remote: SyncMeta,
@@ -58,7 +74,7 @@ impl SyncMeta {
         let local = self;
         let required = if remote.modified == local.modified {
             SyncActionRequired::NoChanges
-        } else if remote.schema != local.schema {
+        } else if remote.schema != local.schema || remote.peer != local.peer {
             let upload_ok = !local.empty || remote.empty;
             let download_ok = !remote.empty || local.empty;
             SyncActionRequired::FullSyncRequired {
@@ -131,6 +147,8 @@ impl Collection {
             should_continue: true,
             host_number: 0,
             empty: !self.storage.have_at_least_one_card()?,
+            max_sync_version: SYNC_VERSION_MAX,
+            peer: self.get_config_bool(BoolKey::SyncsWithPeers),
             v2_scheduler_or_later: self.scheduler_version() == SchedulerVersion::V2,
             v2_timezone: self.get_creation_utc_offset().is_some(),
         })
@@ -161,7 +179,7 @@ pub fn server_meta(req: MetaRequest, col: &mut Collection) -> HttpResult<SyncMet
 impl MetaRequest {
     pub fn request() -> SyncRequest<Self> {
         MetaRequest {
-            sync_version: SYNC_VERSION_MAX,
+            sync_version: SYNC_VERSION_CLIENT_DEFAULT,
             client_version: sync_client_version().into(),
         }
         .try_into_sync_request()
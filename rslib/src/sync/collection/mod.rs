@@ -3,6 +3,7 @@
 
 pub mod changes;
 pub mod chunks;
+pub mod conflicts;
 pub mod download;
 pub mod finish;
 pub mod graves;
@@ -10,6 +11,7 @@ pub mod meta;
 pub mod normal;
 pub mod progress;
 pub mod protocol;
+pub mod resumable;
 pub mod sanity;
 pub mod start;
 pub mod status;
@@ -9,6 +9,7 @@ use crate::error::AnkiError;
 use crate::error::SyncError;
 use crate::error::SyncErrorKind;
 use crate::prelude::Usn;
+use crate::sync::collection::conflicts::SyncConflicts;
 use crate::sync::collection::progress::SyncStage;
 use crate::sync::collection::protocol::EmptyInput;
 use crate::sync::collection::protocol::SyncProtocol;
@@ -21,6 +22,7 @@ pub struct NormalSyncer<'a, F> {
     pub(in crate::sync) server: HttpSyncClient,
     pub(in crate::sync) progress: NormalSyncProgress,
     pub(in crate::sync) progress_fn: F,
+    pub(in crate::sync) conflicts: SyncConflicts,
 }
 
 #[derive(Default, Debug, Clone, Copy)]
@@ -67,6 +69,7 @@ where
             server,
             progress: NormalSyncProgress::default(),
             progress_fn,
+            conflicts: SyncConflicts::default(),
         }
     }
 
@@ -140,7 +143,10 @@ where
         debug!("finalize");
         self.finalize(&state).await?;
         state.required = SyncActionRequired::NoChanges;
-        Ok(state.into())
+        Ok(SyncOutput {
+            conflicts: std::mem::take(&mut self.conflicts),
+            ..state.into()
+        })
     }
 }
 
@@ -150,6 +156,9 @@ pub struct SyncOutput {
     pub server_message: String,
     pub host_number: u32,
     pub new_endpoint: Option<String>,
+    /// Objects modified on both sides, where one side's changes were
+    /// discarded.
+    pub conflicts: SyncConflicts,
 }
 
 impl From<ClientSyncState> for SyncOutput {
@@ -159,6 +168,7 @@ impl From<ClientSyncState> for SyncOutput {
             server_message: s.server_message,
             host_number: s.host_number,
             new_endpoint: s.new_endpoint,
+            conflicts: SyncConflicts::default(),
         }
     }
 }
@@ -18,6 +18,10 @@ use crate::sync::collection::graves::ApplyGravesRequest;
 use crate::sync::collection::graves::Graves;
 use crate::sync::collection::meta::MetaRequest;
 use crate::sync::collection::meta::SyncMeta;
+use crate::sync::collection::resumable::DownloadChunkRequest;
+use crate::sync::collection::resumable::DownloadStartRequest;
+use crate::sync::collection::resumable::TransferInfo;
+use crate::sync::collection::resumable::UploadStartResponse;
 use crate::sync::collection::sanity::SanityCheckRequest;
 use crate::sync::collection::sanity::SanityCheckResponse;
 use crate::sync::collection::start::StartRequest;
@@ -45,6 +49,12 @@ pub enum SyncMethod {
     Abort,
     Upload,
     Download,
+    UploadStart,
+    UploadChunk,
+    UploadFinish,
+    DownloadStart,
+    DownloadChunk,
+    DownloadFinish,
 }
 
 pub trait AsSyncEndpoint: Into<&'static str> {
@@ -89,6 +99,27 @@ pub trait SyncProtocol: Send + Sync + 'static {
     async fn abort(&self, req: SyncRequest<EmptyInput>) -> HttpResult<SyncResponse<()>>;
     async fn upload(&self, req: SyncRequest<Vec<u8>>) -> HttpResult<SyncResponse<UploadResponse>>;
     async fn download(&self, req: SyncRequest<EmptyInput>) -> HttpResult<SyncResponse<Vec<u8>>>;
+    async fn upload_start(
+        &self,
+        req: SyncRequest<TransferInfo>,
+    ) -> HttpResult<SyncResponse<UploadStartResponse>>;
+    /// The data is an encoded [UploadChunkRequest].
+    ///
+    /// [UploadChunkRequest]: crate::sync::collection::resumable::UploadChunkRequest
+    async fn upload_chunk(&self, req: SyncRequest<Vec<u8>>) -> HttpResult<SyncResponse<()>>;
+    async fn upload_finish(
+        &self,
+        req: SyncRequest<EmptyInput>,
+    ) -> HttpResult<SyncResponse<UploadResponse>>;
+    async fn download_start(
+        &self,
+        req: SyncRequest<DownloadStartRequest>,
+    ) -> HttpResult<SyncResponse<TransferInfo>>;
+    async fn download_chunk(
+        &self,
+        req: SyncRequest<DownloadChunkRequest>,
+    ) -> HttpResult<SyncResponse<Vec<u8>>>;
+    async fn download_finish(&self, req: SyncRequest<EmptyInput>) -> HttpResult<SyncResponse<()>>;
 }
 
 /// The sync protocol expects '{}' to be sent in requests without args.
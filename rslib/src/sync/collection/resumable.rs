@@ -0,0 +1,136 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Messages for transferring a full upload or download in chunks, added in
+//! sync version 12. An upload is started with the size and checksum of the
+//! file, and the server replies with how much of it it already has, so a
+//! previous attempt can be continued. A download is started by the server
+//! taking a snapshot of the collection, which the client then fetches piece
+//! by piece, and removed once the client has finished with it. A client that
+//! already has part of a snapshot asks for it again, and continues from where
+//! it stopped if the server still has it. In both cases, the checksum of the
+//! complete file is verified before it is used.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+use crate::io::read_file;
+use crate::io::write_file;
+use crate::media::files::sha1_of_data;
+use crate::prelude::*;
+use crate::sync::error::HttpResult;
+use crate::sync::error::OrHttpErr;
+
+/// The amount of data sent in each request.
+pub(crate) const FULL_SYNC_CHUNK_BYTES: usize = 8 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TransferInfo {
+    pub size: u64,
+    /// Hex-encoded SHA1 of the complete file.
+    pub sha1: String,
+}
+
+impl TransferInfo {
+    pub fn of_data(data: &[u8]) -> Self {
+        Self {
+            size: data.len() as u64,
+            sha1: hex::encode(sha1_of_data(data)),
+        }
+    }
+
+    /// The info saved alongside a partial transfer at `path`, if any.
+    pub(crate) fn load_for(path: &Path) -> Option<Self> {
+        let data = read_file(info_path(path)).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    /// Save the info alongside a partial transfer at `path`, so that it can
+    /// be continued after a restart.
+    pub(crate) fn save_for(&self, path: &Path) -> Result<()> {
+        write_file(info_path(path), serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+}
+
+/// Where the info of a partial transfer at `path` is saved.
+pub(crate) fn info_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".json");
+    name.into()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DownloadStartRequest {
+    /// The snapshot the client already has part of, if a previous attempt was
+    /// interrupted.
+    #[serde(default)]
+    pub resume: Option<TransferInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UploadStartResponse {
+    /// The number of bytes the server already has. Zero unless a previous
+    /// upload of the same file was interrupted.
+    pub offset: u64,
+}
+
+/// Sent as an 8 byte big-endian offset, followed by the data, so the chunk
+/// doesn't need to be encoded into JSON.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UploadChunkRequest {
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
+
+impl UploadChunkRequest {
+    pub fn into_bytes(self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(8 + self.data.len());
+        out.extend_from_slice(&self.offset.to_be_bytes());
+        out.extend(self.data);
+        out
+    }
+
+    pub fn from_bytes(mut bytes: Vec<u8>) -> HttpResult<Self> {
+        if bytes.len() < 8 {
+            None.or_bad_request("missing chunk offset")?;
+        }
+        let data = bytes.split_off(8);
+        Ok(Self {
+            offset: u64::from_be_bytes(bytes.try_into().unwrap()),
+            data,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DownloadChunkRequest {
+    pub offset: u64,
+    pub length: u64,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn chunk_encoding() {
+        let chunk = UploadChunkRequest {
+            offset: 0x0102,
+            data: b"abc".to_vec(),
+        };
+        let bytes = chunk.into_bytes();
+        assert_eq!(&bytes[..8], &[0, 0, 0, 0, 0, 0, 1, 2]);
+        assert_eq!(
+            UploadChunkRequest::from_bytes(bytes).unwrap(),
+            UploadChunkRequest {
+                offset: 0x0102,
+                data: b"abc".to_vec(),
+            }
+        );
+        assert!(UploadChunkRequest::from_bytes(vec![1, 2]).is_err());
+    }
+}
@@ -3,16 +3,24 @@
 
 #![cfg(test)]
 
+use std::fs;
 use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
 
 use axum::http::StatusCode;
+use futures::future::join;
+use futures::future::join_all;
 use once_cell::sync::Lazy;
 use reqwest::Url;
 use serde_json::json;
 use tempfile::tempdir;
 use tempfile::TempDir;
+use tokio::sync::oneshot;
 use tokio::sync::Mutex;
 use tokio::sync::MutexGuard;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
 use tracing::Instrument;
 use tracing::Span;
 use wiremock::matchers::method;
@@ -22,16 +30,22 @@ use wiremock::MockServer;
 use wiremock::ResponseTemplate;
 
 use crate::card::CardQueue;
+use crate::collection::open_test_collection;
 use crate::collection::CollectionBuilder;
+use crate::config::BoolKey;
 use crate::deckconfig::DeckConfig;
 use crate::decks::DeckKind;
 use crate::error::SyncError;
 use crate::error::SyncErrorKind;
 use crate::log::set_global_logger;
+use crate::media::files::add_hash_suffix_to_file_stem;
+use crate::media::files::sha1_of_data;
 use crate::notetype::all_stock_notetypes;
 use crate::prelude::*;
 use crate::revlog::RevlogEntry;
+use crate::scheduler::states::SchedulingAlgorithmKind;
 use crate::search::SortMode;
+use crate::storage::SchemaVersion;
 use crate::sync::collection::graves::ApplyGravesRequest;
 use crate::sync::collection::meta::MetaRequest;
 use crate::sync::collection::normal::NormalSyncProgress;
@@ -41,11 +55,21 @@ use crate::sync::collection::normal::SyncOutput;
 use crate::sync::collection::progress::FullSyncProgress;
 use crate::sync::collection::protocol::EmptyInput;
 use crate::sync::collection::protocol::SyncProtocol;
+use crate::sync::collection::resumable::DownloadChunkRequest;
+use crate::sync::collection::resumable::DownloadStartRequest;
+use crate::sync::collection::resumable::TransferInfo;
+use crate::sync::collection::resumable::UploadChunkRequest;
+use crate::sync::collection::resumable::FULL_SYNC_CHUNK_BYTES;
 use crate::sync::collection::start::StartRequest;
 use crate::sync::collection::upload::UploadResponse;
 use crate::sync::collection::upload::CORRUPT_MESSAGE;
 use crate::sync::http_client::HttpSyncClient;
+use crate::sync::http_server::peer::PeerServer;
+use crate::sync::http_server::peer::MAX_PAIRING_FAILURES;
+use crate::sync::http_server::quota::Quota;
+use crate::sync::http_server::user_store::UserStore;
 use crate::sync::http_server::SimpleServer;
+use crate::sync::login::sync_login;
 use crate::sync::login::HostKeyRequest;
 use crate::sync::login::SyncAuth;
 use crate::sync::request::IntoSyncRequest;
@@ -432,6 +456,194 @@ async fn invalid_uploads_should_be_handled() -> Result<()> {
     .await
 }
 
+#[tokio::test]
+async fn interrupted_full_syncs_can_resume() -> Result<()> {
+    with_active_server(|client| async move {
+        let ctx = SyncTestContext::new(client);
+        let mut col1 = ctx.col1();
+        col1_setup(&mut col1);
+        let path = col1.col_path.clone();
+        col1.close(Some(SchemaVersion::V18))?;
+        let data = std::fs::read(path)?;
+        let info = TransferInfo::of_data(&data);
+        let chunk = |offset: usize, end: usize| {
+            UploadChunkRequest {
+                offset: offset as u64,
+                data: data[offset..end].to_vec(),
+            }
+            .into_bytes()
+            .try_into_sync_request()
+        };
+
+        // the connection drops after the first half is sent
+        let half = data.len() / 2;
+        let start = ctx
+            .client
+            .upload_start(info.clone().try_into_sync_request()?)
+            .await?
+            .json()?;
+        assert_eq!(start.offset, 0);
+        ctx.client.upload_chunk(chunk(0, half)?).await?;
+
+        // the next attempt continues from where it stopped
+        let start = ctx
+            .client
+            .upload_start(info.try_into_sync_request()?)
+            .await?
+            .json()?;
+        assert_eq!(start.offset, half as u64);
+        ctx.client.upload_chunk(chunk(half, data.len())?).await?;
+        let resp = ctx.client.upload_finish(EmptyInput::request()).await?;
+        assert_eq!(resp.upload_response(), UploadResponse::Ok);
+
+        // a download's final chunk can be fetched again if its response is lost
+        let download_chunk = |offset: u64| {
+            DownloadChunkRequest {
+                offset,
+                length: FULL_SYNC_CHUNK_BYTES as u64,
+            }
+            .try_into_sync_request()
+        };
+        let info = ctx
+            .client
+            .download_start(DownloadStartRequest::default().try_into_sync_request()?)
+            .await?
+            .json()?;
+        let first = ctx.client.download_chunk(download_chunk(0)?).await?.data;
+        let again = ctx.client.download_chunk(download_chunk(0)?).await?.data;
+        assert_eq!(first, again);
+        assert_eq!(first.len() as u64, info.size);
+
+        // and a later attempt can continue with the same snapshot
+        let resumed = ctx
+            .client
+            .download_start(
+                DownloadStartRequest {
+                    resume: Some(info.clone()),
+                }
+                .try_into_sync_request()?,
+            )
+            .await?
+            .json()?;
+        assert_eq!(resumed, info);
+        ctx.client.download_finish(EmptyInput::request()).await?;
+
+        // the upload arrived intact
+        ctx.full_download(ctx.col2()).await;
+        assert_eq!(ctx.col2().storage.get_all_note_ids()?.len(), 1);
+
+        // a snapshot is not resumed once a sync has changed the collection
+        let info = ctx
+            .client
+            .download_start(DownloadStartRequest::default().try_into_sync_request()?)
+            .await?
+            .json()?;
+        let mut col2 = ctx.col2();
+        col2.get_or_create_normal_deck("changed")?;
+        ctx.normal_sync(&mut col2).await;
+        let resumed = ctx
+            .client
+            .download_start(
+                DownloadStartRequest {
+                    resume: Some(info.clone()),
+                }
+                .try_into_sync_request()?,
+            )
+            .await?
+            .json()?;
+        assert_ne!(resumed, info);
+        ctx.client.download_finish(EmptyInput::request()).await?;
+
+        // the download snapshot is removed once the client has finished
+        let err = ctx
+            .client
+            .download_chunk(
+                DownloadChunkRequest {
+                    offset: 0,
+                    length: 1,
+                }
+                .try_into_sync_request()?,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err.code, StatusCode::CONFLICT);
+
+        // data that doesn't match the checksum is rejected
+        let info = TransferInfo {
+            size: 3,
+            sha1: hex::encode([0; 20]),
+        };
+        ctx.client
+            .upload_start(info.try_into_sync_request()?)
+            .await?;
+        ctx.client
+            .upload_chunk(
+                UploadChunkRequest {
+                    offset: 0,
+                    data: b"abc".to_vec(),
+                }
+                .into_bytes()
+                .try_into_sync_request()?,
+            )
+            .await?;
+        let resp = ctx.client.upload_finish(EmptyInput::request()).await?;
+        assert!(matches!(resp.upload_response(), UploadResponse::Err(_)));
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn conflicting_edits_are_reported_and_can_be_restored() -> Result<()> {
+    with_active_server(|client| async move {
+        let ctx = SyncTestContext::new(client);
+        upload_download(&ctx).await?;
+        let mut col1 = ctx.col1();
+        let mut col2 = ctx.col2();
+        let nid = *col1.storage.get_all_note_ids()?.iter().next().unwrap();
+
+        // a note and deck are changed on one device, a minute before the other
+        let mut note = col1.storage.get_note(nid)?.unwrap();
+        note.set_field(0, "first")?;
+        col1.update_note(&mut note)?;
+        note.mtime.0 -= 60;
+        col1.storage.update_note(&note)?;
+        col1.rename_deck(DeckId(1), "first")?;
+        let mut deck = col1.storage.get_deck(DeckId(1))?.unwrap();
+        deck.mtime_secs.0 -= 60;
+        col1.storage.update_deck(&deck)?;
+        assert!(ctx.normal_sync(&mut col1).await.conflicts.is_empty());
+
+        let mut note = col2.storage.get_note(nid)?.unwrap();
+        note.set_field(0, "second")?;
+        col2.update_note(&mut note)?;
+        col2.rename_deck(DeckId(1), "second")?;
+
+        // the later changes win, and the earlier ones are reported
+        let out = ctx.normal_sync(&mut col2).await;
+        assert_eq!(out.conflicts.notes.len(), 1);
+        let conflict = &out.conflicts.notes[0];
+        assert!(conflict.kept_local);
+        assert_eq!(conflict.kept.fields()[0], "second");
+        assert_eq!(conflict.discarded.fields()[0], "first");
+        assert_eq!(out.conflicts.decks.len(), 1);
+        assert_eq!(out.conflicts.decks[0].discarded.name.human_name(), "first");
+        assert!(out.conflicts.cards.is_empty());
+
+        // restoring them makes them new changes, which are synced
+        assert_eq!(col2.restore_sync_conflicts(out.conflicts)?.output, 2);
+        ctx.normal_sync(&mut col2).await;
+        ctx.normal_sync(&mut col1).await;
+        assert_eq!(col1.storage.get_note(nid)?.unwrap().fields()[0], "first");
+        assert_eq!(
+            col1.storage.get_deck(DeckId(1))?.unwrap().name.human_name(),
+            "first"
+        );
+        Ok(())
+    })
+    .await
+}
+
 #[tokio::test]
 async fn meta_redirect_is_handled() -> Result<()> {
     with_active_server(|client| async move {
@@ -463,6 +675,628 @@ async fn meta_redirect_is_handled() -> Result<()> {
     .await
 }
 
+#[tokio::test]
+async fn users_are_locked_independently() -> Result<()> {
+    let _ = set_global_logger(None);
+    let base_folder = tempdir()?;
+    UserStore::open(base_folder.path())?.add_user("other", "pass")?;
+    std::env::set_var("SYNC_USER1", "user:pass");
+    let server = Arc::new(SimpleServer::new_with_workers(base_folder.path(), 2).unwrap());
+    let login = |username: &str| {
+        let server = server.clone();
+        let request = HostKeyRequest {
+            username: username.into(),
+            password: "pass".into(),
+        };
+        async move { server.get_host_key(request).await }
+    };
+    let meta_request = |hkey: &str| {
+        let mut req = MetaRequest::request();
+        req.sync_key = hkey.into();
+        req
+    };
+    let busy_key = login("user").await.unwrap().json()?.key;
+    let other_key = login("other").await.unwrap().json()?.key;
+
+    // simulate a long-running operation such as a large upload
+    let (started_tx, started_rx) = oneshot::channel();
+    let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+    let busy = tokio::spawn({
+        let server = server.clone();
+        let mut req = EmptyInput::request();
+        req.sync_key = busy_key.clone();
+        async move {
+            server
+                .with_authenticated_user(req, move |_user, _req| {
+                    started_tx.send(()).unwrap();
+                    release_rx.recv().unwrap();
+                    Ok(())
+                })
+                .await
+        }
+    });
+    started_rx.await.unwrap();
+
+    // a request from the same user has to wait its turn
+    let queued = tokio::spawn({
+        let server = server.clone();
+        let req = meta_request(&busy_key);
+        async move { server.meta(req).await }
+    });
+
+    // but many concurrent requests and logins from another user are not held up
+    let others = (0..20).map(|_| {
+        let server = server.clone();
+        let req = meta_request(&other_key);
+        tokio::spawn(async move { server.meta(req).await })
+    });
+    let logins = (0..10).map(|_| tokio::spawn(login("other")));
+    let (others, logins) = timeout(
+        Duration::from_secs(30),
+        join(join_all(others), join_all(logins)),
+    )
+    .await
+    .expect("other user was blocked");
+    for resp in others {
+        resp.unwrap().unwrap().json()?;
+    }
+    for resp in logins {
+        assert_eq!(resp.unwrap().unwrap().json()?.key, other_key);
+    }
+    assert!(!queued.is_finished());
+
+    release_tx.send(()).unwrap();
+    busy.await.unwrap().unwrap();
+    queued.await.unwrap().unwrap().json()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn admin_routes() -> Result<()> {
+    std::env::set_var("SYNC_ADMIN_TOKEN", "admin");
+    with_active_server(|client| async move {
+        client.meta(MetaRequest::request()).await?;
+        let http = reqwest::Client::new();
+        let url = |path| client.endpoint.join(path).unwrap();
+
+        let resp = http.get(url("admin/users")).send().await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let resp = http
+            .get(url("metrics"))
+            .bearer_auth("wrong")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let users: serde_json::Value = http
+            .get(url("admin/users"))
+            .bearer_auth("admin")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(users[0]["name"], "user");
+        assert_eq!(users[0]["busy"], false);
+        assert_eq!(users[0]["media_files"], 0);
+
+        let metrics = http
+            .get(url("metrics"))
+            .bearer_auth("admin")
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(metrics
+            .contains(r#"anki_sync_requests_total{path="/sync/meta",status="200"} 1"#));
+        assert!(metrics
+            .contains(r#"anki_sync_requests_total{path="/admin/users",status="403"} 1"#));
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn backups_can_be_restored() -> Result<()> {
+    std::env::set_var("SYNC_ADMIN_TOKEN", "admin");
+    with_active_server(|client| async move {
+        let http = reqwest::Client::new();
+        let url = |path| client.endpoint.join(path).unwrap();
+        let list_backups = || async {
+            http.get(url("admin/users/user/backups"))
+                .bearer_auth("admin")
+                .send()
+                .await
+                .unwrap()
+                .json::<Vec<String>>()
+                .await
+                .unwrap()
+        };
+        let ctx = SyncTestContext::new(client.partial_clone());
+        let mut col1 = ctx.col1();
+        col1_setup(&mut col1);
+        ctx.full_upload(col1).await;
+        // the empty collection the upload replaced was not backed up
+        assert!(list_backups().await.is_empty());
+
+        // a backup is made after a normal sync
+        let mut col1 = ctx.col1();
+        col1_setup(&mut col1);
+        ctx.normal_sync(&mut col1).await;
+        let backups = list_backups().await;
+        assert_eq!(backups.len(), 1);
+
+        // but not if one was made recently
+        col1_setup(&mut col1);
+        ctx.normal_sync(&mut col1).await;
+        assert_eq!(col1.storage.get_all_note_ids()?.len(), 3);
+        assert_eq!(list_backups().await.len(), 1);
+
+        let resp = http
+            .post(url("admin/users/user/restore"))
+            .bearer_auth("admin")
+            .json(&json!({ "backup": "missing.colpkg" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let resp = http
+            .post(url("admin/users/user/restore"))
+            .bearer_auth("admin")
+            .json(&json!({ "backup": backups[0] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // the client is forced to do a one-way sync
+        let out = ctx.normal_sync(&mut col1).await;
+        assert!(matches!(
+            out.required,
+            SyncActionRequired::FullSyncRequired { .. }
+        ));
+        ctx.full_download(col1).await;
+        assert_eq!(ctx.col1().storage.get_all_note_ids()?.len(), 2);
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn full_uploads_are_backed_up() -> Result<()> {
+    std::env::set_var("SYNC_ADMIN_TOKEN", "admin");
+    with_active_server(|client| async move {
+        let http = reqwest::Client::new();
+        let url = |path| client.endpoint.join(path).unwrap();
+        let list_backups = || async {
+            http.get(url("admin/users/user/backups"))
+                .bearer_auth("admin")
+                .send()
+                .await
+                .unwrap()
+                .json::<Vec<String>>()
+                .await
+                .unwrap()
+        };
+        let ctx = SyncTestContext::new(client.partial_clone());
+        let mut col1 = ctx.col1();
+        col1_setup(&mut col1);
+        ctx.full_upload(col1).await;
+        assert!(list_backups().await.is_empty());
+
+        // replacing the collection backs it up, even though no time has passed
+        let mut col1 = ctx.col1();
+        col1_setup(&mut col1);
+        ctx.full_upload(col1).await;
+        assert_eq!(list_backups().await.len(), 1);
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn quotas_are_enforced() -> Result<()> {
+    let _ = set_global_logger(None);
+    let base_folder = tempdir()?;
+    let store = UserStore::open(base_folder.path())?;
+    store.add_user("limited", "pass")?;
+    store.set_quota(
+        "limited",
+        Quota {
+            collection_bytes: Some(1),
+            ..Default::default()
+        },
+    )?;
+    let hkey = store.check_password("limited", "pass")?.unwrap();
+    std::env::set_var("SYNC_USER1", "user:pass");
+    let (addr, server_fut) = SimpleServer::make_server(None, base_folder.path()).unwrap();
+    tokio::spawn(server_fut.instrument(Span::current()));
+    let ctx = SyncTestContext::new(HttpSyncClient::new(SyncAuth {
+        hkey,
+        endpoint: Some(Url::try_from(format!("http://{addr}/").as_str()).unwrap()),
+    }));
+
+    let err = ctx
+        .col1()
+        .full_upload_with_server(ctx.cloned_client())
+        .await
+        .unwrap_err();
+    assert_eq!(unwrap_sync_err_kind(err), SyncErrorKind::QuotaExceeded);
+
+    // lifting the limit takes effect immediately
+    store.set_quota("limited", Quota::default())?;
+    ctx.full_upload(ctx.col1()).await;
+
+    // requests beyond the rate limit are rejected
+    store.set_quota(
+        "limited",
+        Quota {
+            requests_per_minute: Some(1),
+            ..Default::default()
+        },
+    )?;
+    ctx.client.meta(MetaRequest::request()).await?;
+    let err = ctx.client.meta(MetaRequest::request()).await.unwrap_err();
+    assert_eq!(
+        unwrap_sync_err_kind(AnkiError::from(err)),
+        SyncErrorKind::RateLimited
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn shared_decks_are_merged() -> Result<()> {
+    let _ = set_global_logger(None);
+    let base_folder = tempdir()?;
+    let store = UserStore::open(base_folder.path())?;
+    for name in ["publisher", "subscriber"] {
+        store.add_user(name, "pass")?;
+    }
+    store.share_deck("publisher", "Shared")?;
+    store.subscribe("subscriber", "publisher", "Shared")?;
+    std::env::set_var("SYNC_USER1", "user:pass");
+    let (addr, server_fut) = SimpleServer::make_server(None, base_folder.path()).unwrap();
+    tokio::spawn(server_fut.instrument(Span::current()));
+    let context_for = |name| -> Result<SyncTestContext> {
+        Ok(SyncTestContext::new(HttpSyncClient::new(SyncAuth {
+            hkey: store.check_password(name, "pass")?.unwrap(),
+            endpoint: Some(Url::try_from(format!("http://{addr}/").as_str()).unwrap()),
+        })))
+    };
+    let publisher = context_for("publisher")?;
+    let subscriber = context_for("subscriber")?;
+
+    // the publisher adds a note to the shared deck, and another to a private one
+    let mut pub_col = publisher.col1();
+    col1_setup(&mut pub_col);
+    let deck = pub_col.get_or_create_normal_deck("Shared")?;
+    let nt = pub_col.get_notetype_by_name("Basic")?.unwrap();
+    let mut note = nt.new_note();
+    note.set_field(0, "shared")?;
+    pub_col.add_note(&mut note, deck.id)?;
+    // so a later edit is considered newer
+    note.mtime.0 -= 10;
+    pub_col.storage.update_note(&note)?;
+    publisher.full_upload(pub_col).await;
+
+    // the subscriber receives only the shared note
+    let mut sub_col = subscriber.col1();
+    let out = subscriber.normal_sync(&mut sub_col).await;
+    assert!(matches!(
+        out.required,
+        SyncActionRequired::FullSyncRequired { .. }
+    ));
+    subscriber.full_download(sub_col).await;
+    let mut sub_col = subscriber.col1();
+    assert_eq!(
+        sub_col.storage.get_all_note_ids()?,
+        [note.id].into_iter().collect()
+    );
+    let cid = sub_col.storage.all_cards_of_note(note.id)?[0].id;
+    sub_col.set_due_date(&[cid], "5", None)?;
+    subscriber.normal_sync(&mut sub_col).await;
+
+    // an edit by the publisher arrives in a normal sync, without affecting
+    // the subscriber's scheduling
+    let mut pub_col = publisher.col1();
+    note.set_field(0, "edited")?;
+    pub_col.update_note(&mut note)?;
+    publisher.normal_sync(&mut pub_col).await;
+    let out = subscriber.normal_sync(&mut sub_col).await;
+    assert_eq!(out.required, SyncActionRequired::NoChanges);
+    assert_eq!(
+        sub_col.storage.get_note(note.id)?.unwrap().fields()[0],
+        "edited"
+    );
+    assert_eq!(
+        sub_col.storage.get_card(cid)?.unwrap().queue,
+        CardQueue::Review
+    );
+
+    // the subscriber's copy is read-only, so their edits are reverted once
+    // the server has received them
+    let mut sub_note = sub_col.storage.get_note(note.id)?.unwrap();
+    sub_note.set_field(0, "mine")?;
+    sub_col.update_note(&mut sub_note)?;
+    subscriber.normal_sync(&mut sub_col).await;
+    subscriber.normal_sync(&mut sub_col).await;
+    assert_eq!(
+        sub_col.storage.get_note(note.id)?.unwrap().fields()[0],
+        "edited"
+    );
+    assert_eq!(
+        sub_col.storage.get_card(cid)?.unwrap().queue,
+        CardQueue::Review
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn shared_media_does_not_replace_subscriber_media() -> Result<()> {
+    let _ = set_global_logger(None);
+    let base_folder = tempdir()?;
+    let store = UserStore::open(base_folder.path())?;
+    for name in ["publisher", "subscriber"] {
+        store.add_user(name, "pass")?;
+    }
+    store.share_deck("publisher", "Shared")?;
+    store.subscribe("subscriber", "publisher", "Shared")?;
+    std::env::set_var("SYNC_USER1", "user:pass");
+    let (addr, server_fut) = SimpleServer::make_server(None, base_folder.path()).unwrap();
+    tokio::spawn(server_fut.instrument(Span::current()));
+    let context_for = |name| -> Result<SyncTestContext> {
+        Ok(SyncTestContext::new(HttpSyncClient::new(SyncAuth {
+            hkey: store.check_password(name, "pass")?.unwrap(),
+            endpoint: Some(Url::try_from(format!("http://{addr}/").as_str()).unwrap()),
+        })))
+    };
+    let publisher = context_for("publisher")?;
+    let subscriber = context_for("subscriber")?;
+
+    // both sides have an a.jpg, with different contents
+    publisher.media1().add_file("a.jpg", b"publisher")?;
+    publisher.sync_media1().await?;
+    subscriber.media1().add_file("a.jpg", b"subscriber")?;
+    subscriber.sync_media1().await?;
+
+    let mut pub_col = publisher.col1();
+    col1_setup(&mut pub_col);
+    let deck = pub_col.get_or_create_normal_deck("Shared")?;
+    let nt = pub_col.get_notetype_by_name("Basic")?.unwrap();
+    let mut note = nt.new_note();
+    note.set_field(0, "<img src=\"a.jpg\">")?;
+    pub_col.add_note(&mut note, deck.id)?;
+    publisher.full_upload(pub_col).await;
+
+    // the shared file is renamed, and the shared note refers to the new name
+    let mut sub_col = subscriber.col1();
+    subscriber.normal_sync(&mut sub_col).await;
+    subscriber.full_download(sub_col).await;
+    subscriber.sync_media1().await?;
+    let renamed = add_hash_suffix_to_file_stem("a.jpg", &sha1_of_data(b"publisher"));
+    let sub_col = subscriber.col1();
+    assert_eq!(
+        sub_col.storage.get_note(note.id)?.unwrap().fields()[0],
+        format!("<img src=\"{renamed}\">")
+    );
+    let folder = sub_col.media()?.media_folder;
+    assert_eq!(fs::read(folder.join("a.jpg"))?, b"subscriber");
+    assert_eq!(fs::read(folder.join(renamed))?, b"publisher");
+
+    Ok(())
+}
+
+/// Host the collection, and point the context's client at it, logged in with
+/// the pairing key. Hosting stops once the sender is used and the task has
+/// finished.
+async fn host_peer(
+    ctx: &mut SyncTestContext,
+    col: Collection,
+) -> (oneshot::Sender<()>, JoinHandle<()>) {
+    let (stop, stopped) = oneshot::channel::<()>();
+    let (addr, server_fut) = PeerServer::new(col, "pairing key", ctx.folder.path().join("backups"))
+        .unwrap()
+        .make_server(PeerServer::bind("127.0.0.1:0").unwrap(), async {
+            let _ = stopped.await;
+        })
+        .unwrap();
+    let task = tokio::spawn(async move { server_fut.await.unwrap() });
+    let endpoint = format!("http://{addr}/");
+    let auth = sync_login("", "pairing key", Some(endpoint.clone()))
+        .await
+        .unwrap();
+    ctx.client = HttpSyncClient::new(SyncAuth {
+        endpoint: Some(Url::try_from(endpoint.as_str()).unwrap()),
+        ..auth
+    });
+    (stop, task)
+}
+
+#[tokio::test]
+async fn peers_can_sync_directly() -> Result<()> {
+    let _ = set_global_logger(None);
+    let mut ctx = SyncTestContext::new(HttpSyncClient::new(SyncAuth::default()));
+    let mut col1 = ctx.col1();
+    col1_setup(&mut col1);
+
+    // short pairing keys are refused
+    assert!(PeerServer::new(open_test_collection(), "short", ctx.folder.path()).is_err());
+
+    // the peer starts with a copy of the host's collection
+    let (stop, task) = host_peer(&mut ctx, col1).await;
+    let mut col2 = ctx.col2();
+    let out = ctx.normal_sync(&mut col2).await;
+    assert!(matches!(
+        out.required,
+        SyncActionRequired::FullSyncRequired { .. }
+    ));
+    ctx.full_download(col2).await;
+
+    // after which its changes are synced normally
+    let mut col2 = ctx.col2();
+    let nt = col2.get_notetype_by_name("Basic")?.unwrap();
+    let mut note = nt.new_note();
+    note.set_field(0, "from peer")?;
+    col2.add_note(&mut note, DeckId(1))?;
+    let out = ctx.normal_sync(&mut col2).await;
+    assert_eq!(out.required, SyncActionRequired::NoChanges);
+    stop.send(()).unwrap();
+    task.await.unwrap();
+
+    // changes made on the host while not hosting are sent the next time
+    let mut col1 = ctx.col1();
+    assert_eq!(col1.storage.get_all_note_ids()?.len(), 2);
+    let nt = col1.get_notetype_by_name("Basic")?.unwrap();
+    let mut note = nt.new_note();
+    note.set_field(0, "from host")?;
+    col1.add_note(&mut note, DeckId(1))?;
+    let (stop, task) = host_peer(&mut ctx, col1).await;
+    let out = ctx.normal_sync(&mut col2).await;
+    assert_eq!(out.required, SyncActionRequired::NoChanges);
+    assert_eq!(col2.storage.get_all_note_ids()?.len(), 3);
+
+    // other devices need the pairing key, and it is only used to log in
+    let login = |password| sync_login("", password, Some(ctx.client.endpoint.to_string()));
+    let login_failed = |res: Result<SyncAuth>| {
+        matches!(
+            res,
+            Err(AnkiError::SyncError {
+                source: SyncError {
+                    kind: SyncErrorKind::AuthFailed,
+                    ..
+                }
+            })
+        )
+    };
+    assert!(login_failed(login("wrong").await));
+    let mut client = ctx.client.partial_clone();
+    for key in ["wrong", "pairing key"] {
+        client.sync_key = key.into();
+        let err = client.meta(MetaRequest::request()).await.unwrap_err();
+        assert_eq!(err.code, StatusCode::FORBIDDEN);
+    }
+
+    // logging in again replaces the peer's previous session key
+    let old_key = ctx.client.sync_key.clone();
+    ctx.client.sync_key = login("pairing key").await?.hkey;
+    client.sync_key = old_key;
+    let err = client.meta(MetaRequest::request()).await.unwrap_err();
+    assert_eq!(err.code, StatusCode::FORBIDDEN);
+
+    // repeated guesses lock out even the right key, but not existing sessions
+    for _ in 0..MAX_PAIRING_FAILURES {
+        assert!(login_failed(login("wrong").await));
+    }
+    assert!(login_failed(login("pairing key").await));
+    ctx.client.meta(MetaRequest::request()).await.unwrap();
+
+    stop.send(()).unwrap();
+    task.await.unwrap();
+    Ok(())
+}
+
+#[tokio::test]
+async fn hosting_peers_requires_full_server_sync() -> Result<()> {
+    with_active_server(|server_client| async move {
+        let mut ctx = SyncTestContext::new(server_client.partial_clone());
+        let mut col1 = ctx.col1();
+        col1_setup(&mut col1);
+        ctx.normal_sync(&mut col1).await;
+        ctx.full_upload(col1).await;
+
+        // an edit made before hosting is marked as synced with the peer
+        let mut col1 = ctx.col1();
+        let nt = col1.get_notetype_by_name("Basic")?.unwrap();
+        let mut note = nt.new_note();
+        note.set_field(0, "before hosting")?;
+        col1.add_note(&mut note, DeckId(1))?;
+        let (stop, task) = host_peer(&mut ctx, col1).await;
+        stop.send(()).unwrap();
+        task.await.unwrap();
+
+        // so the server can't be synced with normally, which would leave it out
+        ctx.client = server_client;
+        let mut col1 = ctx.col1();
+        let out = ctx.normal_sync(&mut col1).await;
+        assert!(matches!(
+            out.required,
+            SyncActionRequired::FullSyncRequired { .. }
+        ));
+        ctx.full_upload(col1).await;
+        let col2 = ctx.col2();
+        ctx.full_download(col2).await;
+        let col2 = ctx.col2();
+        assert_eq!(col2.storage.get_all_note_ids()?.len(), 2);
+
+        // and once back on the server, later syncs are normal again
+        let mut col1 = ctx.col1();
+        assert!(!col1.get_config_bool(BoolKey::SyncsWithPeers));
+        let out = ctx.normal_sync(&mut col1).await;
+        assert!(!matches!(
+            out.required,
+            SyncActionRequired::FullSyncRequired { .. }
+        ));
+
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn revlog_algorithms_survive_single_request_full_syncs() -> Result<()> {
+    let _ = set_global_logger(None);
+    let mut ctx = SyncTestContext::new(HttpSyncClient::new(SyncAuth::default()));
+    let mut col1 = ctx.col1();
+    col1_setup(&mut col1);
+    col1.storage.add_revlog_entry(
+        &RevlogEntry {
+            id: RevlogId(123),
+            cid: CardId(456),
+            usn: Usn(-1),
+            interval: 10,
+            ..Default::default()
+        },
+        true,
+    )?;
+    col1.storage
+        .set_revlog_algorithm(RevlogId(123), SchedulingAlgorithmKind::MemoryModel)?;
+
+    // peers don't support chunked transfers, so the whole file is sent at once
+    let col2 = ctx.col2();
+    let (stop, task) = host_peer(&mut ctx, col2).await;
+    let meta = ctx.client.meta(MetaRequest::request()).await?.json()?;
+    assert!(!meta.supports_resumable_full_sync());
+
+    // neither the uploaded copy nor the local one loses the algorithm
+    ctx.full_upload(col1).await;
+    stop.send(()).unwrap();
+    task.await.unwrap();
+    for col in [ctx.col1(), ctx.col2()] {
+        assert_eq!(
+            col.storage.revlog_algorithm(RevlogId(123))?,
+            SchedulingAlgorithmKind::MemoryModel
+        );
+    }
+
+    // and neither does a downloaded copy
+    let col1 = ctx.col1();
+    let (stop, task) = host_peer(&mut ctx, col1).await;
+    ctx.full_download(ctx.col2()).await;
+    stop.send(()).unwrap();
+    task.await.unwrap();
+    assert_eq!(
+        ctx.col2().storage.revlog_algorithm(RevlogId(123))?,
+        SchedulingAlgorithmKind::MemoryModel
+    );
+
+    Ok(())
+}
+
 pub(in crate::sync) struct SyncTestContext {
     pub folder: TempDir,
     pub client: HttpSyncClient,
@@ -602,6 +1436,8 @@ async fn regular_sync(ctx: &SyncTestContext) -> Result<()> {
         },
         true,
     )?;
+    col1.storage
+        .set_revlog_algorithm(RevlogId(123), SchedulingAlgorithmKind::MemoryModel)?;
 
     // config + creation
     col1.set_config("test", &"test1")?;
@@ -658,6 +1494,10 @@ async fn regular_sync(ctx: &SyncTestContext) -> Result<()> {
             col1.storage.get_revlog_entry(revlogid)?,
             col2.storage.get_revlog_entry(revlogid)?,
         );
+        assert_eq!(
+            col2.storage.revlog_algorithm(revlogid)?,
+            SchedulingAlgorithmKind::MemoryModel
+        );
         assert_eq!(
             col1.storage.get_all_config()?,
             col2.storage.get_all_config()?
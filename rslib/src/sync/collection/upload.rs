@@ -12,6 +12,7 @@ use futures::StreamExt;
 use tokio_util::io::ReaderStream;
 
 use crate::collection::CollectionBuilder;
+use crate::config::BoolKey;
 use crate::error::SyncErrorKind;
 use crate::io::atomic_rename;
 use crate::io::new_tempfile_in_parent_of;
@@ -40,7 +41,19 @@ impl Collection {
         self.full_upload_with_server(server).await
     }
 
-    pub(crate) async fn full_upload_with_server(mut self, server: HttpSyncClient) -> Result<()> {
+    pub(crate) async fn full_upload_with_server(
+        mut self,
+        mut server: HttpSyncClient,
+    ) -> Result<()> {
+        let remote = server.meta_with_redirect().await?.0;
+        let resumable = remote.supports_resumable_full_sync();
+        if self.get_config_bool(BoolKey::SyncsWithPeers) != remote.peer {
+            // the upload replaces the remote collection, so it takes on its role
+            self.transact_no_undo(|col| {
+                col.set_config_bool_inner(BoolKey::SyncsWithPeers, remote.peer)
+                    .map(|_| ())
+            })?;
+        }
         self.before_upload()?;
         let col_path = self.col_path.clone();
         self.close(Some(SchemaVersion::V18))?;
@@ -54,11 +67,15 @@ impl Collection {
             )?;
         }
 
-        match server
-            .upload(col_data.try_into_sync_request()?)
-            .await?
-            .upload_response()
-        {
+        let response = if resumable {
+            server.upload_resumable(col_data).await?
+        } else {
+            server
+                .upload(col_data.try_into_sync_request()?)
+                .await?
+                .upload_response()
+        };
+        match response {
             UploadResponse::Ok => Ok(()),
             UploadResponse::Err(msg) => {
                 Err(AnkiError::sync_error(msg, SyncErrorKind::ServerMessage))
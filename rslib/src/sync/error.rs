@@ -47,7 +47,10 @@ impl IntoResponse for HttpError {
             context,
             source,
         } = self;
-        if code.is_server_error() && code != StatusCode::NOT_IMPLEMENTED {
+        if code.is_server_error()
+            && code != StatusCode::NOT_IMPLEMENTED
+            && code != StatusCode::INSUFFICIENT_STORAGE
+        {
             tracing::error!(context, ?source, httpstatus = code.as_u16(),);
         } else {
             tracing::info!(context, ?source, httpstatus = code.as_u16(),);
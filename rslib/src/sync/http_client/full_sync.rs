@@ -1,39 +1,70 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use std::fs::OpenOptions;
 use std::future::Future;
+use std::io::Write;
+use std::path::Path;
 use std::time::Duration;
 
+use reqwest::StatusCode;
 use tokio::select;
 use tokio::time::interval;
+use tokio::time::sleep;
+use tracing::debug;
 
+use crate::error::FileIoSnafu;
+use crate::error::FileOp;
+use crate::error::SyncErrorKind;
+use crate::io::read_file;
+use crate::io::remove_file;
+use crate::io::write_file;
+use crate::prelude::*;
 use crate::sync::collection::progress::FullSyncProgress;
 use crate::sync::collection::progress::FullSyncProgressFn;
 use crate::sync::collection::protocol::EmptyInput;
 use crate::sync::collection::protocol::SyncMethod;
+use crate::sync::collection::protocol::SyncProtocol;
+use crate::sync::collection::resumable::info_path;
+use crate::sync::collection::resumable::DownloadChunkRequest;
+use crate::sync::collection::resumable::DownloadStartRequest;
+use crate::sync::collection::resumable::TransferInfo;
+use crate::sync::collection::resumable::UploadChunkRequest;
+use crate::sync::collection::resumable::FULL_SYNC_CHUNK_BYTES;
 use crate::sync::collection::upload::UploadResponse;
+use crate::sync::error::HttpError;
 use crate::sync::error::HttpResult;
 use crate::sync::http_client::io_monitor::IoMonitor;
 use crate::sync::http_client::HttpSyncClient;
+use crate::sync::request::IntoSyncRequest;
 use crate::sync::request::SyncRequest;
 use crate::sync::response::SyncResponse;
+use crate::sync::version::SyncVersion;
+use crate::sync::version::SYNC_VERSION_12_RESUMABLE_FULL_SYNC;
+
+/// Attempts made at each request of a chunked full sync before giving up.
+const CHUNK_ATTEMPTS: u32 = 5;
+const RETRY_DELAY: Duration = Duration::from_secs(if cfg!(test) { 0 } else { 2 });
 
 impl HttpSyncClient {
     pub fn set_full_sync_progress_fn(&mut self, func: Option<FullSyncProgressFn>) {
         *self.full_sync_progress_fn.lock().unwrap() = func;
     }
 
+    fn take_full_sync_progress_fn(&self) -> FullSyncProgressFn {
+        self.full_sync_progress_fn
+            .lock()
+            .unwrap()
+            .take()
+            .expect("progress func was not set")
+    }
+
     fn full_sync_progress_monitor(&self, sending: bool) -> (IoMonitor, impl Future<Output = ()>) {
         let mut progress = FullSyncProgress {
             transferred_bytes: 0,
             total_bytes: 0,
         };
-        let mut progress_fn = self
-            .full_sync_progress_fn
-            .lock()
-            .unwrap()
-            .take()
-            .expect("progress func was not set");
+        let mut progress_fn = self.take_full_sync_progress_fn();
         let io_monitor = IoMonitor::new();
         let io_monitor2 = io_monitor.clone();
         let update_progress = async move {
@@ -80,4 +111,159 @@ impl HttpSyncClient {
             out = output => out
         }
     }
+
+    /// The chunked methods are only understood by servers that have
+    /// advertised sync version 12 or later.
+    pub(super) async fn resumable_request<I, O>(
+        &self,
+        method: SyncMethod,
+        mut request: SyncRequest<I>,
+    ) -> HttpResult<SyncResponse<O>> {
+        request.sync_version = SyncVersion(SYNC_VERSION_12_RESUMABLE_FULL_SYNC);
+        self.request(method, request).await
+    }
+
+    /// Upload the collection in chunks, continuing from where a previous
+    /// attempt to upload the same data stopped. Each chunk is retried if the
+    /// connection drops.
+    pub(in crate::sync) async fn upload_resumable(&self, data: Vec<u8>) -> Result<UploadResponse> {
+        let mut progress_fn = self.take_full_sync_progress_fn();
+        let total = data.len();
+        let start = TransferInfo::of_data(&data).try_into_sync_request()?;
+        let mut offset = with_retries(|| self.upload_start(start.clone()))
+            .await?
+            .json()?
+            .offset as usize;
+        while offset < total {
+            progress_fn(progress(offset, total), true);
+            let end = (offset + FULL_SYNC_CHUNK_BYTES).min(total);
+            let chunk = UploadChunkRequest {
+                offset: offset as u64,
+                data: data[offset..end].to_vec(),
+            }
+            .into_bytes()
+            .try_into_sync_request()?;
+            with_retries(|| self.upload_chunk(chunk.clone())).await?;
+            offset = end;
+        }
+        progress_fn(progress(total, total), false);
+        // not retried, as a repeat would find the upload already consumed
+        Ok(self
+            .upload_finish(EmptyInput::request())
+            .await?
+            .upload_response())
+    }
+
+    /// Download the collection in chunks, retrying each if the connection
+    /// drops, and check the result against the server's checksum. Chunks are
+    /// saved to `partial_path` as they arrive, so if the download fails, the
+    /// next attempt continues from where it stopped, provided the server
+    /// still has the same snapshot.
+    pub(in crate::sync) async fn download_resumable(&self, partial_path: &Path) -> Result<Vec<u8>> {
+        let mut progress_fn = self.take_full_sync_progress_fn();
+        let resume = TransferInfo::load_for(partial_path).filter(|_| partial_path.exists());
+        let start = DownloadStartRequest {
+            resume: resume.clone(),
+        }
+        .try_into_sync_request()?;
+        let info = with_retries(|| self.download_start(start.clone()))
+            .await?
+            .json()?;
+        if resume.as_ref() != Some(&info) {
+            write_file(partial_path, b"")?;
+            info.save_for(partial_path)?;
+        }
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(partial_path)
+            .context(FileIoSnafu {
+                path: partial_path,
+                op: FileOp::Open,
+            })?;
+        let total = info.size;
+        let mut offset = file
+            .metadata()
+            .context(FileIoSnafu {
+                path: partial_path,
+                op: FileOp::Read,
+            })?
+            .len();
+        while offset < total {
+            progress_fn(progress(offset as usize, total as usize), true);
+            let req = DownloadChunkRequest {
+                offset,
+                length: FULL_SYNC_CHUNK_BYTES as u64,
+            }
+            .try_into_sync_request()?;
+            let chunk = with_retries(|| self.download_chunk(req.clone()))
+                .await?
+                .data;
+            if chunk.is_empty() {
+                return Err(AnkiError::sync_error(
+                    "download ended early",
+                    SyncErrorKind::Other,
+                ));
+            }
+            file.write_all(&chunk).context(FileIoSnafu {
+                path: partial_path,
+                op: FileOp::Write,
+            })?;
+            offset += chunk.len() as u64;
+        }
+        drop(file);
+        progress_fn(progress(total as usize, total as usize), false);
+        let data = read_file(partial_path)?;
+        remove_file(partial_path)?;
+        remove_file(info_path(partial_path))?;
+        if TransferInfo::of_data(&data) != info {
+            return Err(AnkiError::sync_error(
+                "downloaded collection did not match its checksum",
+                SyncErrorKind::Other,
+            ));
+        }
+        // if this fails, the snapshot is replaced by the next download
+        if let Err(err) = with_retries(|| self.download_finish(EmptyInput::request())).await {
+            debug!(?err, "failed to finish download");
+        }
+        Ok(data)
+    }
+}
+
+fn progress(transferred_bytes: usize, total_bytes: usize) -> FullSyncProgress {
+    FullSyncProgress {
+        transferred_bytes,
+        total_bytes,
+    }
+}
+
+/// Retries op after errors that suggest a dropped connection or an overloaded
+/// server, waiting a little longer each time.
+async fn with_retries<F, Fut, T>(mut op: F) -> HttpResult<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = HttpResult<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Err(err) if attempt < CHUNK_ATTEMPTS && is_transient(&err) => {
+                debug!(?err, attempt, "retrying full sync request");
+                sleep(RETRY_DELAY * attempt).await;
+                attempt += 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+fn is_transient(err: &HttpError) -> bool {
+    // transport errors are reported as SEE_OTHER
+    matches!(
+        err.code,
+        StatusCode::SEE_OTHER
+            | StatusCode::REQUEST_TIMEOUT
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
 }
@@ -15,6 +15,10 @@ use crate::sync::collection::meta::SyncMeta;
 use crate::sync::collection::protocol::EmptyInput;
 use crate::sync::collection::protocol::SyncMethod;
 use crate::sync::collection::protocol::SyncProtocol;
+use crate::sync::collection::resumable::DownloadChunkRequest;
+use crate::sync::collection::resumable::DownloadStartRequest;
+use crate::sync::collection::resumable::TransferInfo;
+use crate::sync::collection::resumable::UploadStartResponse;
 use crate::sync::collection::sanity::SanityCheckRequest;
 use crate::sync::collection::sanity::SanityCheckResponse;
 use crate::sync::collection::start::StartRequest;
@@ -103,6 +107,43 @@ impl SyncProtocol for HttpSyncClient {
     async fn download(&self, req: SyncRequest<EmptyInput>) -> HttpResult<SyncResponse<Vec<u8>>> {
         self.download_inner(req).await
     }
+
+    async fn upload_start(
+        &self,
+        req: SyncRequest<TransferInfo>,
+    ) -> HttpResult<SyncResponse<UploadStartResponse>> {
+        self.resumable_request(SyncMethod::UploadStart, req).await
+    }
+
+    async fn upload_chunk(&self, req: SyncRequest<Vec<u8>>) -> HttpResult<SyncResponse<()>> {
+        self.resumable_request(SyncMethod::UploadChunk, req).await
+    }
+
+    async fn upload_finish(
+        &self,
+        req: SyncRequest<EmptyInput>,
+    ) -> HttpResult<SyncResponse<UploadResponse>> {
+        self.resumable_request(SyncMethod::UploadFinish, req).await
+    }
+
+    async fn download_start(
+        &self,
+        req: SyncRequest<DownloadStartRequest>,
+    ) -> HttpResult<SyncResponse<TransferInfo>> {
+        self.resumable_request(SyncMethod::DownloadStart, req).await
+    }
+
+    async fn download_chunk(
+        &self,
+        req: SyncRequest<DownloadChunkRequest>,
+    ) -> HttpResult<SyncResponse<Vec<u8>>> {
+        self.resumable_request(SyncMethod::DownloadChunk, req).await
+    }
+
+    async fn download_finish(&self, req: SyncRequest<EmptyInput>) -> HttpResult<SyncResponse<()>> {
+        self.resumable_request(SyncMethod::DownloadFinish, req)
+            .await
+    }
 }
 
 #[async_trait]
@@ -0,0 +1,233 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::fs;
+use std::sync::Arc;
+
+use axum::extract::Path;
+use axum::extract::State;
+use axum::headers::authorization::Bearer;
+use axum::headers::Authorization;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::get;
+use axum::routing::post;
+use axum::Json;
+use axum::Router;
+use axum::TypedHeader;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use tokio::task::spawn_blocking;
+use tracing::warn;
+
+use crate::prelude::*;
+use crate::sync::error::HttpResult;
+use crate::sync::error::OrHttpErr;
+use crate::sync::http_server::secrets_match;
+use crate::sync::http_server::user::User;
+use crate::sync::http_server::SimpleServer;
+use crate::sync::media::database::server::ServerMediaDatabase;
+
+type AdminAuth = Option<TypedHeader<Authorization<Bearer>>>;
+
+/// Routes for monitoring and managing the server. They are disabled unless
+/// SYNC_ADMIN_TOKEN is set, and require it to be passed as a bearer token.
+pub(super) fn admin_router() -> Router<Arc<SimpleServer>> {
+    Router::new()
+        .route("/admin/users", get(users_handler))
+        .route("/admin/users/:name/backups", get(backups_handler))
+        .route("/admin/users/:name/restore", post(restore_handler))
+        .route("/metrics", get(metrics_handler))
+}
+
+#[derive(Serialize, Debug)]
+pub(super) struct UserStats {
+    name: String,
+    /// True if a request for the user was being processed, in which case
+    /// the remaining fields are not available.
+    busy: bool,
+    collection_bytes: Option<u64>,
+    /// Not available if the media database of a user who is not loaded
+    /// could not be read.
+    media_files: Option<u32>,
+    media_bytes: Option<u64>,
+    /// Falls back on the collection's modification time if the user has not
+    /// synced since the server started.
+    last_sync: Option<TimestampSecs>,
+    /// The session key of an in-progress normal sync.
+    sync_session: Option<String>,
+}
+
+async fn users_handler(
+    State(server): State<Arc<SimpleServer>>,
+    auth: AdminAuth,
+) -> HttpResult<Json<Vec<UserStats>>> {
+    check_admin_token(&server, auth)?;
+    let (names, loaded, base_folder) = {
+        let state = server.state.lock().unwrap();
+        let (names, loaded) = state.all_users()?;
+        (names, loaded, state.base_folder.clone())
+    };
+    // users are not loaded for the listing, but the files are still read
+    // off the async runtime
+    spawn_blocking(move || {
+        names
+            .into_iter()
+            .map(|name| match loaded.get(&name).map(|user| user.try_lock()) {
+                Some(Ok(user)) => user_stats(&user),
+                // don't make the admin wait for a large upload to complete
+                Some(Err(_)) => Ok(UserStats {
+                    name,
+                    busy: true,
+                    collection_bytes: None,
+                    media_files: None,
+                    media_bytes: None,
+                    last_sync: None,
+                    sync_session: None,
+                }),
+                None => {
+                    let folder = base_folder.join(&name);
+                    let collection = collection_stats(&folder);
+                    let (media_files, media_bytes) = unloaded_media_stats(&folder);
+                    Ok(UserStats {
+                        name,
+                        busy: false,
+                        collection_bytes: Some(collection.bytes),
+                        media_files,
+                        media_bytes,
+                        last_sync: collection.modified,
+                        sync_session: None,
+                    })
+                }
+            })
+            .collect::<HttpResult<_>>()
+    })
+    .await
+    .or_internal_err("user stats")?
+    .map(Json)
+}
+
+async fn backups_handler(
+    State(server): State<Arc<SimpleServer>>,
+    Path(name): Path<String>,
+    auth: AdminAuth,
+) -> HttpResult<Json<Vec<String>>> {
+    check_admin_token(&server, auth)?;
+    let user = server.state.lock().unwrap().existing_user(&name)?;
+    server
+        .with_user(user, |user| user.backups())
+        .await
+        .map(Json)
+}
+
+#[derive(Deserialize, Debug)]
+struct RestoreRequest {
+    /// A file name returned by the backups route.
+    backup: String,
+}
+
+/// Waits for any in-progress request of the user to complete, and aborts an
+/// active normal sync.
+async fn restore_handler(
+    State(server): State<Arc<SimpleServer>>,
+    Path(name): Path<String>,
+    auth: AdminAuth,
+    Json(req): Json<RestoreRequest>,
+) -> HttpResult<()> {
+    check_admin_token(&server, auth)?;
+    let user = server.state.lock().unwrap().existing_user(&name)?;
+    server
+        .with_user(user, move |user| user.restore_backup(&req.backup))
+        .await
+}
+
+async fn metrics_handler(
+    State(server): State<Arc<SimpleServer>>,
+    auth: AdminAuth,
+) -> HttpResult<Response> {
+    check_admin_token(&server, auth)?;
+    Ok((
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        server.metrics.render(),
+    )
+        .into_response())
+}
+
+fn check_admin_token(server: &SimpleServer, auth: AdminAuth) -> HttpResult<()> {
+    // the prelude's OrNotFound has a method of the same name
+    let expected = OrHttpErr::or_not_found(server.admin_token.as_deref(), "admin routes disabled")?;
+    match auth {
+        Some(TypedHeader(auth)) if secrets_match(auth.token(), expected) => Ok(()),
+        _ => None.or_forbidden("invalid admin token"),
+    }
+}
+
+struct CollectionStats {
+    bytes: u64,
+    modified: Option<TimestampSecs>,
+}
+
+/// Read from the file's metadata, so the collection does not need to be
+/// opened.
+fn collection_stats(user_folder: &std::path::Path) -> CollectionStats {
+    let collection = fs::metadata(user_folder.join("collection.anki2")).ok();
+    CollectionStats {
+        bytes: collection
+            .as_ref()
+            .map(|meta| meta.len())
+            .unwrap_or_default(),
+        modified: collection
+            .and_then(|meta| meta.modified().ok())
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|elapsed| TimestampSecs(elapsed.as_secs() as i64)),
+    }
+}
+
+/// The media totals of a user who is not loaded, read without loading them. A
+/// user who has never synced media has none.
+fn unloaded_media_stats(user_folder: &std::path::Path) -> (Option<u32>, Option<u64>) {
+    let path = user_folder.join("media.db");
+    if !path.exists() {
+        return (Some(0), Some(0));
+    }
+    match ServerMediaDatabase::open_read_only(&path) {
+        Ok(db) => (db.nonempty_file_count().ok(), db.total_bytes().ok()),
+        Err(err) => {
+            warn!(?err, ?path, "unable to read media database");
+            (None, None)
+        }
+    }
+}
+
+fn user_stats(user: &User) -> HttpResult<UserStats> {
+    let collection = collection_stats(&user.folder);
+    Ok(UserStats {
+        name: user.name.clone(),
+        busy: false,
+        collection_bytes: Some(collection.bytes),
+        media_files: Some(
+            user.media
+                .db
+                .nonempty_file_count()
+                .or_internal_err("media count")?,
+        ),
+        media_bytes: Some(user.media.db.total_bytes().or_internal_err("media bytes")?),
+        last_sync: user.last_sync.or(collection.modified),
+        sync_session: user.sync_state.as_ref().map(|state| state.skey.clone()),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn media_stats_of_unloaded_users() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        assert_eq!(unloaded_media_stats(dir.path()), (Some(0), Some(0)));
+        drop(ServerMediaDatabase::new(&dir.path().join("media.db"))?);
+        assert_eq!(unloaded_media_stats(dir.path()), (Some(0), Some(0)));
+        Ok(())
+    }
+}
@@ -6,35 +6,31 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use media::sanity::MediaSanityCheckResponse;
 use media::upload::MediaUploadResponse;
+use tracing::warn;
 
 use crate::prelude::*;
-use crate::sync::collection::changes::server_apply_changes;
 use crate::sync::collection::changes::ApplyChangesRequest;
 use crate::sync::collection::changes::UnchunkedChanges;
-use crate::sync::collection::chunks::server_apply_chunk;
-use crate::sync::collection::chunks::server_chunk;
 use crate::sync::collection::chunks::ApplyChunkRequest;
 use crate::sync::collection::chunks::Chunk;
-use crate::sync::collection::download::server_download;
-use crate::sync::collection::finish::server_finish;
-use crate::sync::collection::graves::server_apply_graves;
 use crate::sync::collection::graves::ApplyGravesRequest;
 use crate::sync::collection::graves::Graves;
-use crate::sync::collection::meta::server_meta;
 use crate::sync::collection::meta::MetaRequest;
 use crate::sync::collection::meta::SyncMeta;
 use crate::sync::collection::protocol::EmptyInput;
 use crate::sync::collection::protocol::SyncProtocol;
-use crate::sync::collection::sanity::server_sanity_check;
+use crate::sync::collection::resumable::DownloadChunkRequest;
+use crate::sync::collection::resumable::DownloadStartRequest;
+use crate::sync::collection::resumable::TransferInfo;
+use crate::sync::collection::resumable::UploadChunkRequest;
+use crate::sync::collection::resumable::UploadStartResponse;
 use crate::sync::collection::sanity::SanityCheckRequest;
 use crate::sync::collection::sanity::SanityCheckResponse;
-use crate::sync::collection::sanity::SanityCheckStatus;
-use crate::sync::collection::start::server_start;
 use crate::sync::collection::start::StartRequest;
-use crate::sync::collection::upload::handle_received_upload;
 use crate::sync::collection::upload::UploadResponse;
 use crate::sync::error::HttpResult;
 use crate::sync::error::OrHttpErr;
+use crate::sync::http_server::hosted::HostedCollection;
 use crate::sync::http_server::SimpleServer;
 use crate::sync::login::HostKeyRequest;
 use crate::sync::login::HostKeyResponse;
@@ -55,133 +51,192 @@ impl SyncProtocol for Arc<SimpleServer> {
         &self,
         req: SyncRequest<HostKeyRequest>,
     ) -> HttpResult<SyncResponse<HostKeyResponse>> {
-        self.get_host_key(req.json()?)
+        self.get_host_key(req.json()?).await
     }
 
     async fn meta(&self, req: SyncRequest<MetaRequest>) -> HttpResult<SyncResponse<SyncMeta>> {
-        self.with_authenticated_user(req, |user, req| {
-            let req = req.json()?;
-            user.with_col(|col| server_meta(req, col))
+        let server = self.clone();
+        self.with_authenticated_user(req, move |user, req| {
+            server.merge_subscribed_decks(user);
+            user.handle_meta(req)
         })
         .await
         .and_then(SyncResponse::try_from_obj)
     }
 
     async fn start(&self, req: SyncRequest<StartRequest>) -> HttpResult<SyncResponse<Graves>> {
-        self.with_authenticated_user(req, |user, req| {
-            let skey = req.skey()?;
-            let req = req.json()?;
-            user.start_new_sync(skey)?;
-            user.with_sync_state(skey, |col, state| server_start(req, col, state))
-                .and_then(SyncResponse::try_from_obj)
-        })
-        .await
+        self.with_authenticated_user(req, |user, req| user.handle_start(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
     }
 
     async fn apply_graves(
         &self,
         req: SyncRequest<ApplyGravesRequest>,
     ) -> HttpResult<SyncResponse<()>> {
-        self.with_authenticated_user(req, |user, req| {
-            let skey = req.skey()?;
-            let req = req.json()?;
-            user.with_sync_state(skey, |col, state| server_apply_graves(req, col, state))
-                .and_then(SyncResponse::try_from_obj)
-        })
-        .await
+        self.with_authenticated_user(req, |user, req| user.handle_apply_graves(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
     }
 
     async fn apply_changes(
         &self,
         req: SyncRequest<ApplyChangesRequest>,
     ) -> HttpResult<SyncResponse<UnchunkedChanges>> {
-        self.with_authenticated_user(req, |user, req| {
-            let skey = req.skey()?;
-            let req = req.json()?;
-            user.with_sync_state(skey, |col, state| server_apply_changes(req, col, state))
-                .and_then(SyncResponse::try_from_obj)
-        })
-        .await
+        self.with_authenticated_user(req, |user, req| user.handle_apply_changes(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
     }
 
     async fn chunk(&self, req: SyncRequest<EmptyInput>) -> HttpResult<SyncResponse<Chunk>> {
-        self.with_authenticated_user(req, |user, req| {
-            let skey = req.skey()?;
-            let _ = req.json()?;
-            user.with_sync_state(skey, server_chunk)
-                .and_then(SyncResponse::try_from_obj)
-        })
-        .await
+        self.with_authenticated_user(req, |user, req| user.handle_chunk(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
     }
 
     async fn apply_chunk(
         &self,
         req: SyncRequest<ApplyChunkRequest>,
     ) -> HttpResult<SyncResponse<()>> {
-        self.with_authenticated_user(req, |user, req| {
-            let skey = req.skey()?;
-            let req = req.json()?;
-            user.with_sync_state(skey, |col, state| server_apply_chunk(req, col, state))
-                .and_then(SyncResponse::try_from_obj)
-        })
-        .await
+        self.with_authenticated_user(req, |user, req| user.handle_apply_chunk(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
     }
 
     async fn sanity_check(
         &self,
         req: SyncRequest<SanityCheckRequest>,
     ) -> HttpResult<SyncResponse<SanityCheckResponse>> {
-        self.with_authenticated_user(req, |user, req| {
-            let skey = req.skey()?;
-            let req = req.json()?;
-            let resp = user.with_sync_state(skey, |col, _state| server_sanity_check(req, col))?;
-            if resp.status == SanityCheckStatus::Bad {
-                // don't wait for an abort to roll back
-                let _ = user.col.take();
-            }
-            SyncResponse::try_from_obj(resp)
-        })
-        .await
+        self.with_authenticated_user(req, |user, req| user.handle_sanity_check(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
     }
 
     async fn finish(
         &self,
         req: SyncRequest<EmptyInput>,
     ) -> HttpResult<SyncResponse<TimestampMillis>> {
-        self.with_authenticated_user(req, |user, req| {
-            let _ = req.json()?;
-            let now = user.with_sync_state(req.skey()?, |col, _state| server_finish(col))?;
-            user.sync_state = None;
+        let server = self.clone();
+        self.with_authenticated_user(req, move |user, req| {
+            let now = user.handle_finish(req)?;
+            user.last_sync = Some(TimestampSecs::now());
+            // the sync has already been committed, so don't fail it
+            if let Err(err) = user.finish_download() {
+                warn!(?err, "removing download snapshot failed");
+            }
+            if let Err(err) = user.maybe_backup() {
+                warn!(?err, "backup failed");
+            }
+            server.export_shared_decks(user, false);
             SyncResponse::try_from_obj(now)
         })
         .await
     }
 
     async fn abort(&self, req: SyncRequest<EmptyInput>) -> HttpResult<SyncResponse<()>> {
+        self.with_authenticated_user(req, |user, req| user.handle_abort(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
+    }
+
+    async fn upload(&self, req: SyncRequest<Vec<u8>>) -> HttpResult<SyncResponse<UploadResponse>> {
+        let server = self.clone();
+        self.with_authenticated_user(req, move |user, req| {
+            user.abort_stateful_sync_if_active();
+            user.quota.check_collection(req.data.len() as u64)?;
+            // a snapshot of the replaced collection can't be resumed
+            user.finish_download()?;
+            let resp = user.handle_upload(req.data)?;
+            if matches!(resp, UploadResponse::Ok) {
+                user.last_sync = Some(TimestampSecs::now());
+                server.export_shared_decks(user, true);
+            }
+            Ok(SyncResponse::from_upload_response(resp))
+        })
+        .await
+    }
+
+    async fn download(&self, req: SyncRequest<EmptyInput>) -> HttpResult<SyncResponse<Vec<u8>>> {
         self.with_authenticated_user(req, |user, req| {
-            let _ = req.json()?;
+            let data = user.handle_download(req)?;
+            user.last_sync = Some(TimestampSecs::now());
+            Ok(SyncResponse::from_vec(data))
+        })
+        .await
+    }
+
+    async fn upload_start(
+        &self,
+        req: SyncRequest<TransferInfo>,
+    ) -> HttpResult<SyncResponse<UploadStartResponse>> {
+        self.with_authenticated_user(req, |user, req| {
+            req.sync_version.ensure_resumable()?;
             user.abort_stateful_sync_if_active();
-            SyncResponse::try_from_obj(())
+            SyncResponse::try_from_obj(user.start_upload(req.json()?)?)
         })
         .await
     }
 
-    async fn upload(&self, req: SyncRequest<Vec<u8>>) -> HttpResult<SyncResponse<UploadResponse>> {
+    async fn upload_chunk(&self, req: SyncRequest<Vec<u8>>) -> HttpResult<SyncResponse<()>> {
         self.with_authenticated_user(req, |user, req| {
+            req.sync_version.ensure_resumable()?;
+            user.receive_upload_chunk(UploadChunkRequest::from_bytes(req.data)?)?;
+            SyncResponse::try_from_obj(())
+        })
+        .await
+    }
+
+    async fn upload_finish(
+        &self,
+        req: SyncRequest<EmptyInput>,
+    ) -> HttpResult<SyncResponse<UploadResponse>> {
+        let server = self.clone();
+        self.with_authenticated_user(req, move |user, req| {
+            req.sync_version.ensure_resumable()?;
             user.abort_stateful_sync_if_active();
-            user.ensure_col_open()?;
-            handle_received_upload(&mut user.col, req.data).map(SyncResponse::from_upload_response)
+            let resp = user.finish_upload()?;
+            if matches!(resp, UploadResponse::Ok) {
+                user.last_sync = Some(TimestampSecs::now());
+                server.export_shared_decks(user, true);
+            }
+            Ok(SyncResponse::from_upload_response(resp))
         })
         .await
     }
 
-    async fn download(&self, req: SyncRequest<EmptyInput>) -> HttpResult<SyncResponse<Vec<u8>>> {
+    async fn download_start(
+        &self,
+        req: SyncRequest<DownloadStartRequest>,
+    ) -> HttpResult<SyncResponse<TransferInfo>> {
         self.with_authenticated_user(req, |user, req| {
+            req.sync_version.ensure_resumable()?;
             let schema_version = req.sync_version.collection_schema();
-            let _ = req.json()?;
+            let resume = req.json()?.resume;
             user.abort_stateful_sync_if_active();
-            user.ensure_col_open()?;
-            server_download(&mut user.col, schema_version).map(SyncResponse::from_vec)
+            let info = user.start_download(schema_version, resume)?;
+            user.last_sync = Some(TimestampSecs::now());
+            SyncResponse::try_from_obj(info)
+        })
+        .await
+    }
+
+    async fn download_chunk(
+        &self,
+        req: SyncRequest<DownloadChunkRequest>,
+    ) -> HttpResult<SyncResponse<Vec<u8>>> {
+        self.with_authenticated_user(req, |user, req| {
+            req.sync_version.ensure_resumable()?;
+            Ok(SyncResponse::from_vec(user.download_chunk(req.json()?)?))
+        })
+        .await
+    }
+
+    async fn download_finish(&self, req: SyncRequest<EmptyInput>) -> HttpResult<SyncResponse<()>> {
+        self.with_authenticated_user(req, |user, req| {
+            req.sync_version.ensure_resumable()?;
+            let _ = req.json()?;
+            user.finish_download()?;
+            SyncResponse::try_from_obj(())
         })
         .await
     }
@@ -225,7 +280,7 @@ impl MediaSyncProtocol for Arc<SimpleServer> {
     ) -> HttpResult<SyncResponse<JsonResult<MediaUploadResponse>>> {
         self.with_authenticated_user(req, |user, req| {
             SyncResponse::try_from_obj(JsonResult::ok(
-                user.media.process_uploaded_changes(req.data)?,
+                user.media.process_uploaded_changes(req.data, &user.quota)?,
             ))
         })
         .await
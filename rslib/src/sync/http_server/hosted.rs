@@ -0,0 +1,198 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use tracing::info;
+
+use crate::error;
+use crate::prelude::*;
+use crate::sync::collection::changes::server_apply_changes;
+use crate::sync::collection::changes::ApplyChangesRequest;
+use crate::sync::collection::changes::UnchunkedChanges;
+use crate::sync::collection::chunks::server_apply_chunk;
+use crate::sync::collection::chunks::server_chunk;
+use crate::sync::collection::chunks::ApplyChunkRequest;
+use crate::sync::collection::chunks::Chunk;
+use crate::sync::collection::download::server_download;
+use crate::sync::collection::finish::server_finish;
+use crate::sync::collection::graves::server_apply_graves;
+use crate::sync::collection::graves::ApplyGravesRequest;
+use crate::sync::collection::graves::Graves;
+use crate::sync::collection::meta::server_meta;
+use crate::sync::collection::meta::MetaRequest;
+use crate::sync::collection::meta::SyncMeta;
+use crate::sync::collection::protocol::EmptyInput;
+use crate::sync::collection::sanity::server_sanity_check;
+use crate::sync::collection::sanity::SanityCheckRequest;
+use crate::sync::collection::sanity::SanityCheckResponse;
+use crate::sync::collection::sanity::SanityCheckStatus;
+use crate::sync::collection::start::server_start;
+use crate::sync::collection::start::ServerSyncState;
+use crate::sync::collection::start::StartRequest;
+use crate::sync::collection::upload::handle_received_upload;
+use crate::sync::collection::upload::UploadResponse;
+use crate::sync::error::HttpResult;
+use crate::sync::error::OrHttpErr;
+use crate::sync::request::SyncRequest;
+
+/// A collection served to sync clients: a user's collection on the sync
+/// server, or a collection hosted for peers. The collection is opened on
+/// demand, and closed again when a sync fails or is aborted, so that its
+/// changes are rolled back. The handle_* methods carry out the parts of the
+/// sync protocol that both servers share.
+pub(in crate::sync) trait HostedCollection {
+    /// The collection if it is open, and the normal sync in progress.
+    fn parts(&mut self) -> (&mut Option<Collection>, &mut Option<ServerSyncState>);
+
+    fn open_collection(&self) -> HttpResult<Collection>;
+
+    /// Back up the collection before a full upload replaces it.
+    fn backup_before_upload(&mut self) -> HttpResult<()>;
+
+    fn ensure_col_open(&mut self) -> HttpResult<()> {
+        if self.parts().0.is_none() {
+            let col = self.open_collection()?;
+            *self.parts().0 = Some(col);
+        }
+        Ok(())
+    }
+
+    /// Run op with access to the collection. If a sync is active, it's aborted.
+    fn with_col<F, T>(&mut self, op: F) -> HttpResult<T>
+    where
+        F: FnOnce(&mut Collection) -> HttpResult<T>,
+    {
+        self.abort_stateful_sync_if_active();
+        self.ensure_col_open()?;
+        op(self.parts().0.as_mut().unwrap())
+    }
+
+    /// Run op with the existing sync state created by start_new_sync(). If
+    /// there is no existing state, or the current state's key does not
+    /// match, abort the request with a conflict.
+    fn with_sync_state<F, T>(&mut self, skey: &str, op: F) -> HttpResult<T>
+    where
+        F: FnOnce(&mut Collection, &mut ServerSyncState) -> error::Result<T>,
+    {
+        match self.parts().1 {
+            None => None.or_conflict("no active sync")?,
+            Some(state) => {
+                if state.skey != skey {
+                    None.or_conflict("active sync with different key")?;
+                }
+            }
+        };
+
+        self.ensure_col_open()?;
+        let (col, state) = self.parts();
+        let col_ref = col.as_mut().or_internal_err("open col")?;
+        let state_ref = state.as_mut().unwrap();
+        // Failures in a sync op are usually caused by referential integrity issues (eg
+        // they've sent a note without sending its associated notetype).
+        // Returning HTTP 400 will inform the client that a DB check+full sync
+        // is required to fix the issue.
+        op(col_ref, state_ref)
+            .map_err(|e| {
+                *col = None;
+                *state = None;
+                e
+            })
+            .or_bad_request("op failed in sync_state")
+    }
+
+    fn abort_stateful_sync_if_active(&mut self) {
+        let (col, state) = self.parts();
+        if state.is_some() {
+            info!("aborting active sync");
+            *state = None;
+            *col = None;
+        }
+    }
+
+    fn start_new_sync(&mut self, skey: &str) -> HttpResult<()> {
+        self.abort_stateful_sync_if_active();
+        *self.parts().1 = Some(ServerSyncState::new(skey));
+        Ok(())
+    }
+
+    fn handle_meta(&mut self, req: SyncRequest<MetaRequest>) -> HttpResult<SyncMeta> {
+        let req = req.json()?;
+        self.with_col(|col| server_meta(req, col))
+    }
+
+    fn handle_start(&mut self, req: SyncRequest<StartRequest>) -> HttpResult<Graves> {
+        let skey = req.skey()?;
+        let req = req.json()?;
+        self.start_new_sync(skey)?;
+        self.with_sync_state(skey, |col, state| server_start(req, col, state))
+    }
+
+    fn handle_apply_graves(&mut self, req: SyncRequest<ApplyGravesRequest>) -> HttpResult<()> {
+        let skey = req.skey()?;
+        let req = req.json()?;
+        self.with_sync_state(skey, |col, state| server_apply_graves(req, col, state))
+    }
+
+    fn handle_apply_changes(
+        &mut self,
+        req: SyncRequest<ApplyChangesRequest>,
+    ) -> HttpResult<UnchunkedChanges> {
+        let skey = req.skey()?;
+        let req = req.json()?;
+        self.with_sync_state(skey, |col, state| server_apply_changes(req, col, state))
+    }
+
+    fn handle_chunk(&mut self, req: SyncRequest<EmptyInput>) -> HttpResult<Chunk> {
+        let skey = req.skey()?;
+        let _ = req.json()?;
+        self.with_sync_state(skey, server_chunk)
+    }
+
+    fn handle_apply_chunk(&mut self, req: SyncRequest<ApplyChunkRequest>) -> HttpResult<()> {
+        let skey = req.skey()?;
+        let req = req.json()?;
+        self.with_sync_state(skey, |col, state| server_apply_chunk(req, col, state))
+    }
+
+    fn handle_sanity_check(
+        &mut self,
+        req: SyncRequest<SanityCheckRequest>,
+    ) -> HttpResult<SanityCheckResponse> {
+        let skey = req.skey()?;
+        let req = req.json()?;
+        let resp = self.with_sync_state(skey, |col, _state| server_sanity_check(req, col))?;
+        if resp.status == SanityCheckStatus::Bad {
+            // don't wait for an abort to roll back
+            *self.parts().0 = None;
+        }
+        Ok(resp)
+    }
+
+    /// Commits the sync and ends it.
+    fn handle_finish(&mut self, req: SyncRequest<EmptyInput>) -> HttpResult<TimestampMillis> {
+        let _ = req.json()?;
+        let now = self.with_sync_state(req.skey()?, |col, _state| server_finish(col))?;
+        *self.parts().1 = None;
+        Ok(now)
+    }
+
+    fn handle_abort(&mut self, req: SyncRequest<EmptyInput>) -> HttpResult<()> {
+        let _ = req.json()?;
+        self.abort_stateful_sync_if_active();
+        Ok(())
+    }
+
+    /// Replaces the collection with the uploaded one, after backing it up.
+    fn handle_upload(&mut self, data: Vec<u8>) -> HttpResult<UploadResponse> {
+        self.abort_stateful_sync_if_active();
+        self.backup_before_upload()?;
+        handle_received_upload(self.parts().0, data)
+    }
+
+    fn handle_download(&mut self, req: SyncRequest<EmptyInput>) -> HttpResult<Vec<u8>> {
+        let schema_version = req.sync_version.collection_schema();
+        let _ = req.json()?;
+        self.abort_stateful_sync_if_active();
+        self.ensure_col_open()?;
+        server_download(self.parts().0, schema_version)
+    }
+}
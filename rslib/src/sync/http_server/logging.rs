@@ -1,37 +1,80 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use std::sync::Arc;
 use std::time::Duration;
+use std::time::Instant;
 
 use axum::body::Body;
+use axum::body::HttpBody;
+use axum::extract::MatchedPath;
+use axum::extract::State;
+use axum::http::header::CONTENT_LENGTH;
 use axum::http::Request;
+use axum::middleware;
+use axum::middleware::Next;
 use axum::response::Response;
 use axum::Router;
 use tower_http::trace::TraceLayer;
 use tracing::info_span;
 use tracing::Span;
 
-pub fn with_logging_layer(router: Router) -> Router {
-    router.layer(
-        TraceLayer::new_for_http()
-            .make_span_with(|request: &Request<Body>| {
-                info_span!(
-                    "request",
-                    uri = request.uri().path(),
-                    ip = tracing::field::Empty,
-                    uid = tracing::field::Empty,
-                    client = tracing::field::Empty,
-                    session = tracing::field::Empty,
-                )
-            })
-            .on_request(())
-            .on_response(|response: &Response, latency: Duration, _span: &Span| {
-                tracing::info!(
-                    elap_ms = latency.as_millis() as u32,
-                    httpstatus = response.status().as_u16(),
-                    "finished"
-                );
-            })
-            .on_failure(()),
-    )
+use crate::sync::http_server::metrics::Metrics;
+
+pub(super) fn with_logging_layer(router: Router, metrics: Arc<Metrics>) -> Router {
+    router
+        .layer(middleware::from_fn_with_state(metrics, record_metrics))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(|request: &Request<Body>| {
+                    info_span!(
+                        "request",
+                        uri = request.uri().path(),
+                        ip = tracing::field::Empty,
+                        uid = tracing::field::Empty,
+                        client = tracing::field::Empty,
+                        session = tracing::field::Empty,
+                    )
+                })
+                .on_request(())
+                .on_response(|response: &Response, latency: Duration, _span: &Span| {
+                    tracing::info!(
+                        elap_ms = latency.as_millis() as u32,
+                        httpstatus = response.status().as_u16(),
+                        "finished"
+                    );
+                })
+                .on_failure(()),
+        )
+}
+
+async fn record_metrics(
+    State(metrics): State<Arc<Metrics>>,
+    request: Request<Body>,
+    next: Next<Body>,
+) -> Response {
+    let start = Instant::now();
+    // Only successful requests are recorded under their full path, so that
+    // requests for nonexistent methods can't add unlimited labels.
+    let path = request.uri().path().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".into());
+    let request_bytes = request
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok()?.parse().ok())
+        .unwrap_or_default();
+    let response = next.run(request).await;
+    let status = response.status();
+    metrics.record(
+        if status.is_success() { &path } else { &route },
+        status.as_u16(),
+        start.elapsed(),
+        request_bytes,
+        response.body().size_hint().exact().unwrap_or_default(),
+    );
+    response
 }
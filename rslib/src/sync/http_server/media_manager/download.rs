@@ -1,13 +1,6 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
-use std::fs;
-use std::io::ErrorKind;
-
-use snafu::ResultExt;
-
-use crate::error::FileIoSnafu;
-use crate::error::FileOp;
 use crate::sync::error::HttpResult;
 use crate::sync::error::OrHttpErr;
 use crate::sync::http_server::media_manager::ServerMediaManager;
@@ -25,10 +18,13 @@ impl ServerMediaManager {
     fn gather_file_data(&mut self, entries: &[MediaEntry]) -> HttpResult<Vec<(String, Vec<u8>)>> {
         let mut out = vec![];
         for entry in entries {
-            let path = self.media_folder.join(&entry.nfc_filename);
-            match fs::read(&path) {
-                Ok(data) => out.push((entry.nfc_filename.clone(), data)),
-                Err(err) if err.kind() == ErrorKind::NotFound => {
+            match self
+                .store
+                .read(&entry.nfc_filename, &entry.sha1)
+                .or_internal_err("gather file data")?
+            {
+                Some(data) => out.push((entry.nfc_filename.clone(), data)),
+                None => {
                     self.db
                         .forget_missing_file(entry)
                         .or_internal_err("forget missing")?;
@@ -37,12 +33,6 @@ impl ServerMediaManager {
                         entry.nfc_filename
                     ))?;
                 }
-                Err(err) => Err(err)
-                    .context(FileIoSnafu {
-                        path,
-                        op: FileOp::Read,
-                    })
-                    .or_internal_err("gather file data")?,
             }
         }
         Ok(out)
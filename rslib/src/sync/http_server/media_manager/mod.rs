@@ -2,30 +2,35 @@
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
 pub mod download;
+pub mod store;
 pub mod upload;
 
 use std::path::Path;
-use std::path::PathBuf;
+use std::sync::Arc;
 
-use crate::io::create_dir_all;
 use crate::prelude::*;
 use crate::sync::error::HttpResult;
 use crate::sync::error::OrHttpErr;
+use crate::sync::http_server::media_manager::store::MediaStore;
+use crate::sync::http_server::media_manager::store::MediaStoreKind;
 use crate::sync::media::changes::MediaChange;
 use crate::sync::media::database::server::ServerMediaDatabase;
 use crate::sync::media::sanity::MediaSanityCheckResponse;
 
 pub(crate) struct ServerMediaManager {
-    pub media_folder: PathBuf,
+    pub store: Arc<dyn MediaStore>,
     pub db: ServerMediaDatabase,
 }
 
 impl ServerMediaManager {
-    pub(crate) fn new(user_folder: &Path) -> HttpResult<ServerMediaManager> {
-        let media_folder = user_folder.join("media");
-        create_dir_all(&media_folder).or_internal_err("media folder create")?;
+    pub(crate) fn new(
+        user_folder: &Path,
+        store_kind: MediaStoreKind,
+    ) -> HttpResult<ServerMediaManager> {
         Ok(Self {
-            media_folder,
+            store: store_kind
+                .open(user_folder)
+                .or_internal_err("open media store")?,
             db: ServerMediaDatabase::new(&user_folder.join("media.db"))
                 .or_internal_err("open media db")?,
         })
@@ -0,0 +1,257 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Where the server keeps the contents of media files. The media database
+//! remains the record of which files exist and what their checksums are; a
+//! store only holds the data, so the default flat folder can be swapped for
+//! something that copes better with large numbers of files.
+//!
+//! Switching a server from the flat store to the sharded one requires
+//! running `users migrate-media` while the server is stopped. Until then, a
+//! user whose flat folder still holds files can't be opened, and nor can a
+//! sharded user be opened with the flat store, as there is no way back.
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use snafu::whatever;
+use snafu::ResultExt;
+use snafu::Whatever;
+use tracing::info;
+
+use crate::error;
+use crate::error::FileIoSnafu;
+use crate::error::FileOp;
+use crate::invalid_input;
+use crate::io::atomic_rename;
+use crate::io::create_dir_all;
+use crate::io::new_tempfile_in;
+use crate::io::read_dir_files;
+use crate::io::read_file;
+use crate::io::remove_file;
+use crate::io::write_file;
+use crate::media::files::sha1_of_data;
+
+pub trait MediaStore: Send + Sync {
+    /// Returns None if the store does not have the file.
+    fn read(&self, fname: &str, sha1: &[u8]) -> error::Result<Option<Vec<u8>>>;
+
+    /// Store a new or replaced file. When a file is replaced, its previous
+    /// contents are removed first.
+    fn write(&self, fname: &str, sha1: &[u8], data: &[u8]) -> error::Result<()>;
+
+    /// Called when a file is deleted or replaced. If `shared` is true, another
+    /// file still has the same contents. Must succeed if the file is already
+    /// gone, as an earlier attempt may have been interrupted.
+    fn remove(&self, fname: &str, sha1: &[u8], shared: bool) -> error::Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaStoreKind {
+    /// All files in a single folder, under their own names.
+    Flat,
+    /// Files named by their checksum, spread over 256 subfolders. Identical
+    /// files are only stored once.
+    Sharded,
+}
+
+impl Default for MediaStoreKind {
+    fn default() -> Self {
+        MediaStoreKind::Flat
+    }
+}
+
+impl MediaStoreKind {
+    pub(crate) fn from_env() -> error::Result<Self, Whatever> {
+        match env::var("SYNC_MEDIA_STORE").as_deref() {
+            Err(_) | Ok("flat") => Ok(Self::Flat),
+            Ok("sharded") => Ok(Self::Sharded),
+            Ok(other) => {
+                whatever!("SYNC_MEDIA_STORE should be 'flat' or 'sharded', not '{other}'")
+            }
+        }
+    }
+
+    /// Opens the store in a user's folder. Fails if the user's media is in
+    /// the other kind of store.
+    pub(crate) fn open(self, user_folder: &Path) -> error::Result<Arc<dyn MediaStore>> {
+        let flat_folder = user_folder.join("media");
+        let sharded_folder = user_folder.join("media.store");
+        Ok(match self {
+            Self::Flat => {
+                if sharded_folder.exists() {
+                    invalid_input!(
+                        "media in {} is in a sharded store; set SYNC_MEDIA_STORE=sharded",
+                        user_folder.display()
+                    );
+                }
+                Arc::new(FlatMediaStore::new(flat_folder)?)
+            }
+            Self::Sharded => {
+                if flat_folder.exists() && read_dir_files(&flat_folder)?.next().is_some() {
+                    invalid_input!(
+                        "media in {} has not been migrated; stop the server and run 'users migrate-media'",
+                        user_folder.display()
+                    );
+                }
+                Arc::new(ShardedMediaStore::new(sharded_folder)?)
+            }
+        })
+    }
+}
+
+/// Moves the files in a user's flat media folder into a sharded store, and
+/// returns how many were moved. Must not be run while the server is using
+/// the folder. An interrupted migration can be run again.
+pub(crate) fn migrate_to_sharded(user_folder: &Path) -> error::Result<usize> {
+    let flat_folder = user_folder.join("media");
+    if !flat_folder.exists() {
+        return Ok(0);
+    }
+    ShardedMediaStore::new(user_folder.join("media.store"))?.migrate_from_flat_folder(&flat_folder)
+}
+
+pub struct FlatMediaStore {
+    folder: PathBuf,
+}
+
+impl FlatMediaStore {
+    pub fn new(folder: PathBuf) -> error::Result<Self> {
+        create_dir_all(&folder)?;
+        Ok(Self { folder })
+    }
+}
+
+impl MediaStore for FlatMediaStore {
+    fn read(&self, fname: &str, _sha1: &[u8]) -> error::Result<Option<Vec<u8>>> {
+        read_if_exists(&self.folder.join(fname))
+    }
+
+    fn write(&self, fname: &str, _sha1: &[u8], data: &[u8]) -> error::Result<()> {
+        write_file(self.folder.join(fname), data).map_err(Into::into)
+    }
+
+    fn remove(&self, fname: &str, _sha1: &[u8], _shared: bool) -> error::Result<()> {
+        remove_if_exists(&self.folder.join(fname))
+    }
+}
+
+pub struct ShardedMediaStore {
+    folder: PathBuf,
+}
+
+impl ShardedMediaStore {
+    pub fn new(folder: PathBuf) -> error::Result<Self> {
+        create_dir_all(&folder)?;
+        Ok(Self { folder })
+    }
+
+    /// eg 'ab/abcdef...'
+    fn path(&self, sha1: &[u8]) -> PathBuf {
+        let hex = hex::encode(sha1);
+        self.folder.join(&hex[..2]).join(hex)
+    }
+
+    fn migrate_from_flat_folder(&self, flat_folder: &Path) -> error::Result<usize> {
+        let mut count = 0;
+        for entry in read_dir_files(flat_folder)? {
+            let entry = entry?;
+            let path = entry.path();
+            let data = read_file(&path)?;
+            let fname = entry.file_name().to_string_lossy().into_owned();
+            self.write(&fname, &sha1_of_data(&data), &data)?;
+            remove_if_exists(&path)?;
+            count += 1;
+        }
+        fs::remove_dir(flat_folder).context(FileIoSnafu {
+            path: flat_folder,
+            op: FileOp::Remove,
+        })?;
+        info!(count, "moved media into sharded store");
+        Ok(count)
+    }
+}
+
+impl MediaStore for ShardedMediaStore {
+    fn read(&self, _fname: &str, sha1: &[u8]) -> error::Result<Option<Vec<u8>>> {
+        read_if_exists(&self.path(sha1))
+    }
+
+    /// Written via a temporary file, so an interrupted write can not leave a
+    /// truncated file under a valid checksum.
+    fn write(&self, _fname: &str, sha1: &[u8], data: &[u8]) -> error::Result<()> {
+        let path = self.path(sha1);
+        if path.exists() {
+            return Ok(());
+        }
+        let shard = path.parent().unwrap();
+        create_dir_all(shard)?;
+        let mut file = new_tempfile_in(shard)?;
+        file.write_all(data).context(FileIoSnafu {
+            path: file.path(),
+            op: FileOp::Write,
+        })?;
+        atomic_rename(file, &path, false).map_err(Into::into)
+    }
+
+    fn remove(&self, _fname: &str, sha1: &[u8], shared: bool) -> error::Result<()> {
+        if shared {
+            return Ok(());
+        }
+        remove_if_exists(&self.path(sha1))
+    }
+}
+
+fn read_if_exists(path: &Path) -> error::Result<Option<Vec<u8>>> {
+    match read_file(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(err) if err.is_not_found() => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn remove_if_exists(path: &Path) -> error::Result<()> {
+    match remove_file(path) {
+        Err(err) if !err.is_not_found() => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn sharded_store() -> error::Result<()> {
+        let dir = tempdir()?;
+        create_dir_all(dir.path().join("media"))?;
+        write_file(dir.path().join("media").join("a.jpg"), "a")?;
+        // unmigrated files must be moved over first
+        assert!(MediaStoreKind::Sharded.open(dir.path()).is_err());
+        assert_eq!(migrate_to_sharded(dir.path())?, 1);
+        assert!(!dir.path().join("media").exists());
+        let store = MediaStoreKind::Sharded.open(dir.path())?;
+        let sha1 = sha1_of_data(b"a");
+        assert_eq!(store.read("a.jpg", &sha1)?, Some(b"a".to_vec()));
+        // there is no way back to the flat store
+        assert!(MediaStoreKind::Flat.open(dir.path()).is_err());
+        // running the migration again is harmless
+        assert_eq!(migrate_to_sharded(dir.path())?, 0);
+
+        // identical files share storage
+        store.write("b.jpg", &sha1, b"a")?;
+        store.remove("a.jpg", &sha1, true)?;
+        assert_eq!(store.read("b.jpg", &sha1)?, Some(b"a".to_vec()));
+        store.remove("b.jpg", &sha1, false)?;
+        assert_eq!(store.read("b.jpg", &sha1)?, None);
+        // removing again is not an error
+        store.remove("b.jpg", &sha1, false)?;
+        Ok(())
+    }
+}
@@ -1,38 +1,64 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
-use std::fs;
-use std::io::ErrorKind;
-use std::path::Path;
-
-use snafu::ResultExt;
 use tracing::info;
 
 use crate::error;
-use crate::error::FileIoError;
-use crate::error::FileIoSnafu;
-use crate::error::FileOp;
-use crate::io::write_file;
+use crate::error::AnkiError;
+use crate::error::SyncErrorKind;
+use crate::prelude::Usn;
 use crate::sync::error::HttpResult;
 use crate::sync::error::OrHttpErr;
 use crate::sync::http_server::media_manager::ServerMediaManager;
+use crate::sync::http_server::quota::quota_exceeded;
+use crate::sync::http_server::quota::Quota;
 use crate::sync::media::database::server::entry::upload::UploadedChangeResult;
 use crate::sync::media::upload::MediaUploadResponse;
 use crate::sync::media::zip::unzip_and_validate_files;
+use crate::sync::media::zip::UploadedChange;
 
 impl ServerMediaManager {
     pub fn process_uploaded_changes(
         &mut self,
         zip_data: Vec<u8>,
+        quota: &Quota,
     ) -> HttpResult<MediaUploadResponse> {
         let extracted = unzip_and_validate_files(&zip_data).or_bad_request("unzip files")?;
-        let folder = &self.media_folder;
+        let (processed, current_usn) = self.apply_changes(extracted, quota)?;
+        Ok(MediaUploadResponse {
+            processed,
+            current_usn,
+        })
+    }
+
+    /// Returns the number of changes processed, and the new usn.
+    pub(crate) fn apply_changes(
+        &mut self,
+        changes: Vec<UploadedChange>,
+        quota: &Quota,
+    ) -> HttpResult<(usize, Usn)> {
+        let store = &self.store;
         let mut processed = 0;
+        let mut within_quota = true;
         let new_usn = self
             .db
             .with_transaction(|db, meta| {
-                for change in extracted {
-                    match db.register_uploaded_change(meta, change)? {
+                let before = meta.clone();
+                let results = changes
+                    .into_iter()
+                    .map(|change| db.register_uploaded_change(meta, change))
+                    .collect::<error::Result<Vec<_>>>()?;
+                // nothing has been written to disk yet, so the changes can be
+                // rolled back
+                within_quota = quota.check_media(&before, meta);
+                if !within_quota {
+                    return Err(AnkiError::sync_error(
+                        "media quota exceeded",
+                        SyncErrorKind::QuotaExceeded,
+                    ));
+                }
+                for result in results {
+                    match result {
                         UploadedChangeResult::FileAlreadyDeleted { filename } => {
                             info!(filename, "already deleted");
                         }
@@ -44,8 +70,8 @@ impl ServerMediaManager {
                             data,
                             sha1,
                         } => {
-                            info!(filename, sha1 = hex::encode(sha1), "added");
-                            add_or_replace_file(&folder.join(filename), data)?;
+                            info!(filename, sha1 = hex::encode(&sha1), "added");
+                            store.write(&filename, &sha1, &data)?;
                         }
                         UploadedChangeResult::Replaced {
                             filename,
@@ -55,42 +81,31 @@ impl ServerMediaManager {
                         } => {
                             info!(
                                 filename,
-                                old_sha1 = hex::encode(old_sha1),
-                                new_sha1 = hex::encode(new_sha1),
+                                old_sha1 = hex::encode(&old_sha1),
+                                new_sha1 = hex::encode(&new_sha1),
                                 "replaced"
                             );
-                            add_or_replace_file(&folder.join(filename), data)?;
+                            let shared = db.checksum_in_use(&old_sha1)?;
+                            store.remove(&filename, &old_sha1, shared)?;
+                            store.write(&filename, &new_sha1, &data)?;
                         }
                         UploadedChangeResult::Removed { filename, sha1 } => {
-                            info!(filename, sha1 = hex::encode(sha1), "removed");
-                            remove_file(&folder.join(filename))?;
+                            info!(filename, sha1 = hex::encode(&sha1), "removed");
+                            let shared = db.checksum_in_use(&sha1)?;
+                            store.remove(&filename, &sha1, shared)?;
                         }
                     }
                     processed += 1;
                 }
                 Ok(())
             })
-            .or_internal_err("handle uploaded change")?;
-        Ok(MediaUploadResponse {
-            processed,
-            current_usn: new_usn,
-        })
-    }
-}
-
-fn add_or_replace_file(path: &Path, data: Vec<u8>) -> error::Result<(), FileIoError> {
-    write_file(path, data).map_err(Into::into)
-}
-
-fn remove_file(path: &Path) -> error::Result<(), FileIoError> {
-    if let Err(err) = fs::remove_file(path) {
-        // if transaction was previously aborted, the file may have already been deleted
-        if err.kind() != ErrorKind::NotFound {
-            return Err(err).context(FileIoSnafu {
-                path,
-                op: FileOp::Remove,
-            });
-        }
+            .or_else(|err| {
+                if within_quota {
+                    Err(err).or_internal_err("handle uploaded change")
+                } else {
+                    Err(quota_exceeded("media"))
+                }
+            })?;
+        Ok((processed, new_usn))
     }
-    Ok(())
 }
@@ -0,0 +1,144 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds of the request latency histogram, in seconds.
+const LATENCY_BUCKETS: [f64; 8] = [0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0, 30.0];
+
+/// Request statistics gathered by the logging layer, and reported in
+/// Prometheus' text format by the /metrics endpoint.
+#[derive(Default)]
+pub(super) struct Metrics {
+    requests: Mutex<BTreeMap<RequestKey, RequestStats>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct RequestKey {
+    path: String,
+    status: u16,
+}
+
+#[derive(Default)]
+struct RequestStats {
+    count: u64,
+    /// Cumulative counts for each of LATENCY_BUCKETS.
+    latency_buckets: [u64; LATENCY_BUCKETS.len()],
+    latency_sum: f64,
+    request_bytes: u64,
+    response_bytes: u64,
+}
+
+impl Metrics {
+    pub(super) fn record(
+        &self,
+        path: &str,
+        status: u16,
+        latency: Duration,
+        request_bytes: u64,
+        response_bytes: u64,
+    ) {
+        let mut requests = self.requests.lock().unwrap();
+        let stats = requests
+            .entry(RequestKey {
+                path: path.into(),
+                status,
+            })
+            .or_default();
+        let secs = latency.as_secs_f64();
+        stats.count += 1;
+        stats.latency_sum += secs;
+        for (bucket, bound) in stats.latency_buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if secs <= bound {
+                *bucket += 1;
+            }
+        }
+        stats.request_bytes += request_bytes;
+        stats.response_bytes += response_bytes;
+    }
+
+    pub(super) fn render(&self) -> String {
+        let requests = self.requests.lock().unwrap();
+        let mut out = String::new();
+        let mut family = |name: &str, kind: &str, help: &str, value: fn(&RequestStats) -> u64| {
+            writeln!(out, "# HELP {name} {help}\n# TYPE {name} {kind}").unwrap();
+            for (key, stats) in requests.iter() {
+                writeln!(out, "{name}{{{}}} {}", key.labels(), value(stats)).unwrap();
+            }
+        };
+        family(
+            "anki_sync_requests_total",
+            "counter",
+            "Requests handled, by path and HTTP status.",
+            |stats| stats.count,
+        );
+        family(
+            "anki_sync_request_bytes_total",
+            "counter",
+            "Size of request bodies, where the client provided it.",
+            |stats| stats.request_bytes,
+        );
+        family(
+            "anki_sync_response_bytes_total",
+            "counter",
+            "Size of response bodies, where known in advance.",
+            |stats| stats.response_bytes,
+        );
+
+        let name = "anki_sync_request_duration_seconds";
+        writeln!(
+            out,
+            "# HELP {name} Time taken to handle requests.\n# TYPE {name} histogram"
+        )
+        .unwrap();
+        for (key, stats) in requests.iter() {
+            let labels = key.labels();
+            for (count, bound) in stats.latency_buckets.iter().zip(LATENCY_BUCKETS) {
+                writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {count}").unwrap();
+            }
+            writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", stats.count).unwrap();
+            writeln!(out, "{name}_sum{{{labels}}} {}", stats.latency_sum).unwrap();
+            writeln!(out, "{name}_count{{{labels}}} {}", stats.count).unwrap();
+        }
+        out
+    }
+}
+
+impl RequestKey {
+    fn labels(&self) -> String {
+        format!("path=\"{}\",status=\"{}\"", self.path, self.status)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let metrics = Metrics::default();
+        metrics.record("/sync/meta", 200, Duration::from_millis(20), 30, 100);
+        metrics.record("/sync/meta", 200, Duration::from_millis(300), 30, 100);
+        metrics.record("/sync/:method", 400, Duration::from_millis(1), 0, 11);
+        let text = metrics.render();
+        for line in [
+            r#"anki_sync_requests_total{path="/sync/meta",status="200"} 2"#,
+            r#"anki_sync_requests_total{path="/sync/:method",status="400"} 1"#,
+            r#"anki_sync_request_duration_seconds_bucket{path="/sync/meta",status="200",le="0.01"} 0"#,
+            r#"anki_sync_request_duration_seconds_bucket{path="/sync/meta",status="200",le="0.05"} 1"#,
+            r#"anki_sync_request_duration_seconds_bucket{path="/sync/meta",status="200",le="0.5"} 2"#,
+            r#"anki_sync_request_duration_seconds_bucket{path="/sync/meta",status="200",le="+Inf"} 2"#,
+            r#"anki_sync_request_duration_seconds_count{path="/sync/meta",status="200"} 2"#,
+            r#"anki_sync_request_bytes_total{path="/sync/meta",status="200"} 60"#,
+            r#"anki_sync_response_bytes_total{path="/sync/:method",status="400"} 11"#,
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing {line} in:\n{text}"
+            );
+        }
+    }
+}
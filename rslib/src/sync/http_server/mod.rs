@@ -1,12 +1,22 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+mod admin;
 mod handlers;
+mod hosted;
 mod logging;
 mod media_manager;
+mod metrics;
+pub mod peer;
+pub(in crate::sync) mod quota;
+mod resumable;
 mod routes;
+mod shared_decks;
+mod tls;
 mod user;
+pub(in crate::sync) mod user_store;
 
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::env;
 use std::future::Future;
@@ -24,6 +34,9 @@ use snafu::whatever;
 use snafu::OptionExt;
 use snafu::ResultExt;
 use snafu::Whatever;
+use tokio::sync::Mutex as AsyncMutex;
+use tokio::sync::Semaphore;
+use tokio::task::spawn_blocking;
 use tracing::Span;
 
 use crate::error;
@@ -31,11 +44,23 @@ use crate::io::create_dir_all;
 use crate::media::files::sha1_of_data;
 use crate::sync::error::HttpResult;
 use crate::sync::error::OrHttpErr;
+use crate::sync::http_server::admin::admin_router;
 use crate::sync::http_server::logging::with_logging_layer;
-use crate::sync::http_server::media_manager::ServerMediaManager;
+use crate::sync::http_server::media_manager::store::migrate_to_sharded;
+use crate::sync::http_server::media_manager::store::MediaStoreKind;
+use crate::sync::http_server::metrics::Metrics;
+use crate::sync::http_server::quota::Quota;
+use crate::sync::http_server::quota::RateLimiter;
+use crate::sync::http_server::quota::MB;
 use crate::sync::http_server::routes::collection_sync_router;
 use crate::sync::http_server::routes::media_sync_router;
+#[cfg(feature = "rustls")]
+use crate::sync::http_server::tls::tls_incoming;
+use crate::sync::http_server::tls::TlsPaths;
 use crate::sync::http_server::user::User;
+use crate::sync::http_server::user_store::check_login;
+use crate::sync::http_server::user_store::StoredUser;
+use crate::sync::http_server::user_store::UserStore;
 use crate::sync::login::HostKeyRequest;
 use crate::sync::login::HostKeyResponse;
 use crate::sync::request::SyncRequest;
@@ -43,53 +68,160 @@ use crate::sync::request::MAXIMUM_SYNC_PAYLOAD_BYTES;
 use crate::sync::response::SyncResponse;
 
 pub struct SimpleServer {
+    /// Only held while looking up a user, so one user's requests do not
+    /// block another's.
     state: Mutex<SimpleServerInner>,
+    /// Bounds the number of collection and media operations that run at once.
+    workers: Arc<Semaphore>,
+    metrics: Arc<Metrics>,
+    /// If set, enables the admin routes.
+    admin_token: Option<String>,
 }
 
 pub struct SimpleServerInner {
-    /// hkey->user
-    users: HashMap<String, User>,
+    /// name->user. Requests for the same user are run one at a time.
+    users: HashMap<String, Arc<AsyncMutex<User>>>,
+    /// hkey->name, for users defined with SYNC_USERn env vars
+    env_users: HashMap<String, String>,
+    store: UserStore,
+    base_folder: PathBuf,
+    /// Applies to users without their own limits in the database.
+    default_quota: Quota,
+    media_store: MediaStoreKind,
+    /// Requests by host key.
+    requests: RateLimiter,
+    /// Login attempts by username, limited by the default quota.
+    logins: RateLimiter,
 }
 
 impl SimpleServerInner {
     fn new_from_env(base_folder: &Path) -> error::Result<Self, Whatever> {
-        let mut idx = 1;
-        let mut users: HashMap<String, User> = Default::default();
-        loop {
-            let envvar = format!("SYNC_USER{idx}");
-            match std::env::var(&envvar) {
-                Ok(val) => {
-                    let hkey = derive_hkey(&val);
-                    let (name, _) = val.split_once(':').with_whatever_context(|| {
-                        format!("{envvar} should be in 'username:password' format.")
-                    })?;
-                    let folder = base_folder.join(name);
-                    create_dir_all(&folder).whatever_context("creating SYNC_BASE")?;
-                    let media =
-                        ServerMediaManager::new(&folder).whatever_context("opening media")?;
-                    users.insert(
-                        hkey,
-                        User {
-                            name: name.into(),
-                            col: None,
-                            sync_state: None,
-                            media,
-                            folder,
-                        },
-                    );
-                    idx += 1;
-                }
-                Err(_) => break,
-            }
+        create_dir_all(base_folder).whatever_context("creating SYNC_BASE")?;
+        let store = UserStore::open(base_folder).whatever_context("opening user db")?;
+        let media_store = MediaStoreKind::from_env()?;
+        let mut users: HashMap<String, Arc<AsyncMutex<User>>> = Default::default();
+        let mut env_users: HashMap<String, String> = Default::default();
+        for (envvar, val) in env_user_vars() {
+            let hkey = derive_hkey(&val);
+            let (name, _) = val.split_once(':').with_whatever_context(|| {
+                format!("{envvar} should be in 'username:password' format.")
+            })?;
+            let user =
+                User::new(base_folder, name, media_store).whatever_context("opening user")?;
+            users.insert(name.into(), Arc::new(AsyncMutex::new(user)));
+            env_users.insert(hkey, name.into());
         }
-        if users.is_empty() {
-            whatever!("No users defined; SYNC_USER1 env var should be set.");
+        if users.is_empty()
+            && store
+                .list_users()
+                .whatever_context("listing users")?
+                .is_empty()
+        {
+            tracing::warn!(
+                "No users defined; add one with the 'users add' command, or set the SYNC_USER1 env var."
+            );
         }
-        Ok(Self { users })
+        Ok(Self {
+            users,
+            env_users,
+            store,
+            base_folder: base_folder.into(),
+            default_quota: Quota::from_env()?,
+            media_store,
+            requests: Default::default(),
+            logins: Default::default(),
+        })
+    }
+
+    /// Users in the database are looked up on each request, so that changes
+    /// made with the 'users' command apply without a restart.
+    fn user_for_host_key(&mut self, hkey: &str) -> HttpResult<(Arc<AsyncMutex<User>>, Quota)> {
+        let (name, quota) = match self.env_users.get(hkey) {
+            Some(name) => (name.clone(), Quota::default()),
+            None => self
+                .store
+                .user_for_host_key(hkey)
+                .or_internal_err("look up hkey")?
+                .or_forbidden("invalid hkey")?,
+        };
+        Ok((self.user_by_name(name)?, quota.or(self.default_quota)))
+    }
+
+    /// The names of all users, including those in the database that have not
+    /// connected since the server started, and the users that have been
+    /// loaded.
+    fn all_users(&self) -> HttpResult<(Vec<String>, HashMap<String, Arc<AsyncMutex<User>>>)> {
+        let mut names: Vec<String> = self.env_users.values().cloned().collect();
+        names.extend(
+            self.store
+                .list_users()
+                .or_internal_err("list users")?
+                .into_iter()
+                .map(|user| user.name),
+        );
+        names.sort();
+        names.dedup();
+        Ok((names, self.users.clone()))
+    }
+
+    /// Like user_by_name(), but fails if the user does not exist.
+    fn existing_user(&mut self, name: &str) -> HttpResult<Arc<AsyncMutex<User>>> {
+        let exists = self.env_users.values().any(|n| n == name)
+            || self
+                .store
+                .list_users()
+                .or_internal_err("list users")?
+                .iter()
+                .any(|user| user.name == name);
+        if !exists {
+            None.or_not_found("no such user")?;
+        }
+        self.user_by_name(name.into())
+    }
+
+    fn user_by_name(&mut self, name: String) -> HttpResult<Arc<AsyncMutex<User>>> {
+        Ok(match self.users.entry(name) {
+            Entry::Occupied(entry) => entry.get().clone(),
+            Entry::Vacant(entry) => {
+                let user = User::new(&self.base_folder, entry.key(), self.media_store)?;
+                entry.insert(Arc::new(AsyncMutex::new(user))).clone()
+            }
+        })
     }
 }
 
+/// The name and value of each SYNC_USERn env var.
+fn env_user_vars() -> Vec<(String, String)> {
+    (1..)
+        .map(|idx| format!("SYNC_USER{idx}"))
+        .map_while(|envvar| env::var(&envvar).ok().map(|val| (envvar, val)))
+        .collect()
+}
+
+/// The names of the users defined with SYNC_USERn env vars.
+pub(in crate::sync) fn env_user_names() -> Vec<String> {
+    env_user_vars()
+        .into_iter()
+        .filter_map(|(_, val)| val.split_once(':').map(|(name, _)| name.to_string()))
+        .collect()
+}
+
+/// Compares a secret provided by a client with the expected one, taking the
+/// same time however much of it matches.
+pub(in crate::sync) fn secrets_match(provided: &str, expected: &str) -> bool {
+    // hashing first means the length of the secret is not revealed either
+    let provided = sha1_of_data(provided.as_bytes());
+    let expected = sha1_of_data(expected.as_bytes());
+    provided
+        .iter()
+        .zip(expected.iter())
+        .fold(0, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
 // This is not what AnkiWeb does, but should suffice for this use case.
+// Only used for users defined in the environment; users in the database
+// get a random key instead.
 fn derive_hkey(user_and_pass: &str) -> String {
     hex::encode(sha1_of_data(user_and_pass.as_bytes()))
 }
@@ -101,42 +233,122 @@ impl SimpleServer {
         op: F,
     ) -> HttpResult<O>
     where
-        F: FnOnce(&mut User, SyncRequest<I>) -> HttpResult<O>,
+        F: FnOnce(&mut User, SyncRequest<I>) -> HttpResult<O> + Send + 'static,
+        I: Send + 'static,
+        O: Send + 'static,
+    {
+        let (user, quota) = {
+            let mut state = self.state.lock().unwrap();
+            let (user, quota) = state.user_for_host_key(&req.sync_key)?;
+            state
+                .requests
+                .check(&req.sync_key, quota.requests_per_minute)?;
+            (user, quota)
+        };
+        let span = Span::current();
+        span.record("client", &req.client_version);
+        span.record("session", &req.session_key);
+        self.with_user(user, move |user| {
+            user.quota = quota;
+            op(user, req)
+        })
+        .await
+    }
+
+    /// Run op on a worker thread once the user's earlier requests have
+    /// completed.
+    pub(in crate::sync) async fn with_user<F, O>(
+        &self,
+        user: Arc<AsyncMutex<User>>,
+        op: F,
+    ) -> HttpResult<O>
+    where
+        F: FnOnce(&mut User) -> HttpResult<O> + Send + 'static,
+        O: Send + 'static,
     {
-        let mut state = self.state.lock().unwrap();
-        let user = state
-            .users
-            .get_mut(&req.sync_key)
-            .or_forbidden("invalid hkey")?;
-        Span::current().record("uid", &user.name);
-        Span::current().record("client", &req.client_version);
-        Span::current().record("session", &req.session_key);
-        op(user, req)
+        let mut user = user.lock_owned().await;
+        let span = Span::current();
+        span.record("uid", &user.name);
+        let permit = self
+            .workers
+            .clone()
+            .acquire_owned()
+            .await
+            .or_internal_err("acquire worker")?;
+        // the op keeps running (and holding the user) if the client disconnects
+        spawn_blocking(move || {
+            let _permit = permit;
+            let _span = span.enter();
+            op(&mut user)
+        })
+        .await
+        .or_internal_err("sync op")?
     }
 
-    pub(in crate::sync) fn get_host_key(
+    /// Only the lookup is done under the lock; the password hash is checked
+    /// on a worker thread, so that logins do not hold up other requests.
+    pub(in crate::sync) async fn get_host_key(
         &self,
         request: HostKeyRequest,
     ) -> HttpResult<SyncResponse<HostKeyResponse>> {
-        let state = self.state.lock().unwrap();
         let key = derive_hkey(&format!("{}:{}", request.username, request.password));
-        if state.users.contains_key(&key) {
-            SyncResponse::try_from_obj(HostKeyResponse { key })
-        } else {
-            None.or_forbidden("invalid user/pass in get_host_key")
-        }
+        let hash_and_hkey = {
+            let mut state = self.state.lock().unwrap();
+            let limit = state.default_quota.requests_per_minute;
+            state.logins.check(&request.username, limit)?;
+            if state.env_users.contains_key(&key) {
+                return SyncResponse::try_from_obj(HostKeyResponse { key });
+            }
+            state
+                .store
+                .hash_for_user(&request.username)
+                .or_internal_err("look up user")?
+        };
+        let key = spawn_blocking(move || check_login(&request.password, hash_and_hkey))
+            .await
+            .or_internal_err("check password")?
+            .or_internal_err("check password")?
+            .or_forbidden("invalid user/pass in get_host_key")?;
+        SyncResponse::try_from_obj(HostKeyResponse { key })
     }
 
     pub fn new(base_folder: &Path) -> error::Result<Self, Whatever> {
+        let workers = match env::var("SYNC_WORKERS") {
+            Ok(val) => val
+                .parse()
+                .whatever_context("SYNC_WORKERS should be a number")?,
+            Err(_) => num_cpus::get(),
+        };
+        Self::new_with_workers(base_folder, workers)
+    }
+
+    pub(in crate::sync) fn new_with_workers(
+        base_folder: &Path,
+        workers: usize,
+    ) -> error::Result<Self, Whatever> {
         let inner = SimpleServerInner::new_from_env(base_folder)?;
         Ok(SimpleServer {
             state: Mutex::new(inner),
+            workers: Arc::new(Semaphore::new(workers.max(1))),
+            metrics: Default::default(),
+            admin_token: env::var("SYNC_ADMIN_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
         })
     }
 
+    /// Serves HTTPS if SYNC_TLS_CERT and SYNC_TLS_KEY are set.
     pub fn make_server(
         address: Option<&str>,
         base_folder: &Path,
+    ) -> error::Result<(SocketAddr, ServerFuture), Whatever> {
+        Self::make_server_with_tls(address, base_folder, TlsPaths::from_env()?)
+    }
+
+    pub(in crate::sync) fn make_server_with_tls(
+        address: Option<&str>,
+        base_folder: &Path,
+        tls: Option<TlsPaths>,
     ) -> error::Result<(SocketAddr, ServerFuture), Whatever> {
         let server =
             Arc::new(SimpleServer::new(base_folder).whatever_context("unable to create server")?);
@@ -144,21 +356,39 @@ impl SimpleServer {
         let listener = TcpListener::bind(address)
             .with_whatever_context(|_| format!("couldn't bind to {address}"))?;
         let addr = listener.local_addr().unwrap();
+        let metrics = server.metrics.clone();
         let server = with_logging_layer(
             Router::new()
                 .nest("/sync", collection_sync_router())
                 .nest("/msync", media_sync_router())
+                .merge(admin_router())
                 .with_state(server)
                 .layer(DefaultBodyLimit::max(*MAXIMUM_SYNC_PAYLOAD_BYTES)),
-        );
-        let future = axum::Server::from_tcp(listener)
-            .whatever_context("listen failed")?
-            .serve(server.into_make_service_with_connect_info::<SocketAddr>())
-            .with_graceful_shutdown(async {
-                let _ = tokio::signal::ctrl_c().await;
-            });
-        tracing::info!(%addr, "listening");
-        Ok((addr, Box::pin(future)))
+            metrics,
+        )
+        .into_make_service_with_connect_info::<SocketAddr>();
+        let shutdown = async {
+            let _ = tokio::signal::ctrl_c().await;
+        };
+        let https = tls.is_some();
+        let future: ServerFuture = match tls {
+            None => Box::pin(
+                axum::Server::from_tcp(listener)
+                    .whatever_context("listen failed")?
+                    .serve(server)
+                    .with_graceful_shutdown(shutdown),
+            ),
+            #[cfg(feature = "rustls")]
+            Some(tls) => Box::pin(
+                axum::Server::builder(tls_incoming(listener, tls)?)
+                    .serve(server)
+                    .with_graceful_shutdown(shutdown),
+            ),
+            #[cfg(not(feature = "rustls"))]
+            Some(_) => whatever!("serving HTTPS requires building with the rustls feature"),
+        };
+        tracing::info!(%addr, https, "listening");
+        Ok((addr, future))
     }
 
     #[snafu::report]
@@ -174,6 +404,154 @@ impl SimpleServer {
         server_fut.await.whatever_context("await server")?;
         Ok(())
     }
+
+    /// Manage the users in SYNC_BASE's user database. `args` are the
+    /// arguments following 'users'. The password for 'add' and 'passwd' is
+    /// expected to have been prompted for by the caller. 'migrate-media' must
+    /// only be run while the server is stopped.
+    #[snafu::report]
+    pub fn run_users_command(
+        args: &[String],
+        password: Option<String>,
+    ) -> error::Result<(), Whatever> {
+        let base_folder =
+            PathBuf::from(env::var("SYNC_BASE").whatever_context("missing SYNC_BASE")?);
+        create_dir_all(&base_folder).whatever_context("creating SYNC_BASE")?;
+        let store = UserStore::open(&base_folder).whatever_context("opening user db")?;
+        let stored_user = |name: &str| -> error::Result<StoredUser, Whatever> {
+            store
+                .list_users()
+                .whatever_context("listing users")?
+                .into_iter()
+                .find(|user| user.name == name)
+                .with_whatever_context(|| format!("user '{name}' does not exist"))
+        };
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match args.as_slice() {
+            ["list"] => {
+                for user in store.list_users().whatever_context("listing users")? {
+                    println!("{}\t{}", user.name, user.created.date_string());
+                }
+            }
+            ["quota", name] => {
+                let user = stored_user(name)?;
+                let show = |limit: Option<u64>| {
+                    limit.map_or_else(|| "default".to_string(), |limit| limit.to_string())
+                };
+                println!("collection-mb\t{}", show(user.quota.collection_bytes.map(|b| b / MB)));
+                println!("media-mb\t{}", show(user.quota.media_bytes.map(|b| b / MB)));
+                println!("media-files\t{}", show(user.quota.media_files));
+                println!(
+                    "requests-per-minute\t{}",
+                    show(user.quota.requests_per_minute)
+                );
+            }
+            ["quota", name, kind, limit] => {
+                let mut quota = stored_user(name)?.quota;
+                let limit = match *limit {
+                    "default" => None,
+                    limit => Some(
+                        limit
+                            .parse::<u64>()
+                            .whatever_context("limit should be a number or 'default'")?,
+                    ),
+                };
+                match *kind {
+                    "collection-mb" => quota.collection_bytes = limit.map(|mb| mb * MB),
+                    "media-mb" => quota.media_bytes = limit.map(|mb| mb * MB),
+                    "media-files" => quota.media_files = limit,
+                    "requests-per-minute" => quota.requests_per_minute = limit,
+                    _ => whatever!(
+                        "quota should be collection-mb, media-mb, media-files or requests-per-minute"
+                    ),
+                }
+                store
+                    .set_quota(name, quota)
+                    .whatever_context("setting quota")?;
+                println!("Updated quota of {name}.");
+            }
+            ["add", name] => {
+                let password = password.whatever_context("missing password")?;
+                store
+                    .add_user(name, &password)
+                    .whatever_context("adding user")?;
+                println!("Added {name}.");
+            }
+            ["passwd", name] => {
+                let password = password.whatever_context("missing password")?;
+                store
+                    .set_password(name, &password)
+                    .whatever_context("changing password")?;
+                println!("Changed password of {name}; their devices will need to log in again.");
+            }
+            ["remove", name] => {
+                store.remove_user(name).whatever_context("removing user")?;
+                println!(
+                    "Removed {name}. Their data in {} was left in place.",
+                    base_folder.join(name).display()
+                );
+            }
+            ["share", name, deck] => {
+                store
+                    .share_deck(name, deck)
+                    .whatever_context("sharing deck")?;
+                println!("Shared '{deck}'; subscribers will receive it after {name}'s next sync.");
+            }
+            ["unshare", name, deck] => {
+                store
+                    .unshare_deck(name, deck)
+                    .whatever_context("unsharing deck")?;
+                println!("Stopped sharing '{deck}'; subscribers keep their existing copy.");
+            }
+            ["shared"] => {
+                for share in store.shared_decks().whatever_context("listing shares")? {
+                    let subscribers = store
+                        .subscribers(share.id)
+                        .whatever_context("listing subscribers")?;
+                    println!(
+                        "{}\t{}\t{}",
+                        share.publisher,
+                        share.deck,
+                        subscribers.join(",")
+                    );
+                }
+            }
+            ["subscribe", name, publisher, deck] => {
+                store
+                    .subscribe(name, publisher, deck)
+                    .whatever_context("subscribing")?;
+                println!("{name} will receive '{deck}' on their next sync.");
+            }
+            ["unsubscribe", name, publisher, deck] => {
+                store
+                    .unsubscribe(name, publisher, deck)
+                    .whatever_context("unsubscribing")?;
+                println!("{name} will no longer receive updates to '{deck}'.");
+            }
+            ["migrate-media", names @ ..] => {
+                let names: Vec<String> = if names.is_empty() {
+                    store
+                        .list_users()
+                        .whatever_context("listing users")?
+                        .into_iter()
+                        .map(|user| user.name)
+                        .chain(env_user_names())
+                        .collect()
+                } else {
+                    names.iter().map(|name| name.to_string()).collect()
+                };
+                for name in names {
+                    let count = migrate_to_sharded(&base_folder.join(&name))
+                        .with_whatever_context(|_| format!("migrating media of {name}"))?;
+                    println!("Moved {count} media files of {name} into the sharded store.");
+                }
+            }
+            _ => whatever!(
+                "usage: users add|passwd|remove <name> | users list | users quota <name> [collection-mb|media-mb|media-files|requests-per-minute <limit|default>] | users share|unshare <publisher> <deck> | users shared | users subscribe|unsubscribe <name> <publisher> <deck> | users migrate-media [<name>...]"
+            ),
+        }
+        Ok(())
+    }
 }
 
 pub type ServerFuture = Pin<Box<dyn Future<Output = error::Result<(), hyper::Error>> + Send>>;
@@ -0,0 +1,380 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Syncing directly with another device on the same network, without a sync
+//! server. One device hosts its collection with [PeerServer], and the other
+//! syncs with it as it would with a server, using an [HttpSyncClient] pointed
+//! at the host. The peer logs in with the pairing key shown on the host as its
+//! password, and is given a key for the session. After too many failed logins,
+//! pairing is refused until hosting is restarted. Media is not synced.
+//!
+//! The host acts as its peers' server, so changes made on the host are marked
+//! as synced when hosting begins. A collection should either sync with peers
+//! or with a server, but not both; as with changing servers, switching between
+//! them requires a full sync. The schema is marked modified when a collection
+//! starts hosting, and [SyncMeta::peer] makes a full sync required when only
+//! one side syncs with peers.
+//!
+//! [HttpSyncClient]: crate::sync::http_client::HttpSyncClient
+//! [SyncMeta::peer]: crate::sync::collection::meta::SyncMeta::peer
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::IpAddr;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use axum::extract::DefaultBodyLimit;
+use axum::http::StatusCode;
+use axum::Router;
+use snafu::ResultExt;
+use snafu::Whatever;
+use tokio::task::spawn_blocking;
+use tracing::info;
+
+use crate::collection::CollectionBuilder;
+use crate::config::BoolKey;
+use crate::error;
+use crate::io::create_dir_all;
+use crate::prelude::*;
+use crate::sync::collection::changes::ApplyChangesRequest;
+use crate::sync::collection::changes::UnchunkedChanges;
+use crate::sync::collection::chunks::ApplyChunkRequest;
+use crate::sync::collection::chunks::Chunk;
+use crate::sync::collection::graves::ApplyGravesRequest;
+use crate::sync::collection::graves::Graves;
+use crate::sync::collection::meta::MetaRequest;
+use crate::sync::collection::meta::SyncMeta;
+use crate::sync::collection::protocol::EmptyInput;
+use crate::sync::collection::protocol::SyncProtocol;
+use crate::sync::collection::resumable::DownloadChunkRequest;
+use crate::sync::collection::resumable::DownloadStartRequest;
+use crate::sync::collection::resumable::TransferInfo;
+use crate::sync::collection::resumable::UploadStartResponse;
+use crate::sync::collection::sanity::SanityCheckRequest;
+use crate::sync::collection::sanity::SanityCheckResponse;
+use crate::sync::collection::start::ServerSyncState;
+use crate::sync::collection::start::StartRequest;
+use crate::sync::collection::upload::UploadResponse;
+use crate::sync::error::HttpResult;
+use crate::sync::error::OrHttpErr;
+use crate::sync::http_server::hosted::HostedCollection;
+use crate::sync::http_server::routes::collection_sync_router;
+use crate::sync::http_server::secrets_match;
+use crate::sync::http_server::user_store::new_host_key;
+use crate::sync::http_server::ServerFuture;
+use crate::sync::login::HostKeyRequest;
+use crate::sync::login::HostKeyResponse;
+use crate::sync::request::SyncRequest;
+use crate::sync::request::MAXIMUM_SYNC_PAYLOAD_BYTES;
+use crate::sync::response::SyncResponse;
+use crate::sync::version::SYNC_VERSION_11_DIRECT_POST;
+
+/// The pairing key is all that protects the collection, so short ones are
+/// refused.
+pub const MIN_PAIRING_KEY_LENGTH: usize = 8;
+/// Failed logins allowed before pairing is refused.
+pub(in crate::sync) const MAX_PAIRING_FAILURES: u32 = 10;
+
+pub struct PeerServer {
+    pairing_key: String,
+    pairing: Mutex<Pairing>,
+    state: Mutex<PeerState>,
+}
+
+#[derive(Default)]
+struct Pairing {
+    failures: u32,
+    /// The key handed out to each peer that has logged in. Logging in again
+    /// replaces it.
+    session_keys: HashMap<IpAddr, String>,
+}
+
+struct PeerState {
+    col_path: PathBuf,
+    col: Option<Collection>,
+    sync_state: Option<ServerSyncState>,
+    /// Where the collection is backed up before a peer replaces it.
+    backup_folder: PathBuf,
+}
+
+impl PeerServer {
+    /// Takes over the collection while hosting. It is closed when the server
+    /// stops, and the caller should re-open it afterwards.
+    pub fn new(
+        col: Collection,
+        pairing_key: impl Into<String>,
+        backup_folder: impl Into<PathBuf>,
+    ) -> Result<Self> {
+        let pairing_key = pairing_key.into();
+        if pairing_key.chars().count() < MIN_PAIRING_KEY_LENGTH {
+            invalid_input!("pairing key must be at least {MIN_PAIRING_KEY_LENGTH} characters");
+        }
+        let col_path = col.col_path.clone();
+        col.close(None)?;
+        let mut col = open_server_collection(&col_path)?;
+        col.transact_no_undo(|col| {
+            if !col.get_config_bool(BoolKey::SyncsWithPeers) {
+                // the changes marked as synced below would not be sent to a
+                // server, so a full sync is needed before using one again
+                col.set_schema_modified()?;
+                col.set_config_bool_inner(BoolKey::SyncsWithPeers, true)?;
+            }
+            let usn = col.usn()?;
+            col.storage.mark_pending_objects_synced(usn)
+        })?;
+        Ok(Self {
+            pairing_key,
+            pairing: Default::default(),
+            state: Mutex::new(PeerState {
+                col_path,
+                col: Some(col),
+                sync_state: None,
+                backup_folder: backup_folder.into(),
+            }),
+        })
+    }
+
+    /// Binds the address to host on. This is done before the collection is
+    /// taken over, so an address that is in use leaves it untouched.
+    pub fn bind(address: &str) -> error::Result<TcpListener, Whatever> {
+        TcpListener::bind(address).with_whatever_context(|_| format!("couldn't bind to {address}"))
+    }
+
+    /// Serves on the listener from [PeerServer::bind] until `shutdown`
+    /// resolves, and any open connections have closed.
+    pub fn make_server(
+        self,
+        listener: TcpListener,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> error::Result<(SocketAddr, ServerFuture), Whatever> {
+        let addr = listener.local_addr().unwrap();
+        let server = Router::new()
+            .nest("/sync", collection_sync_router())
+            .with_state(Arc::new(self))
+            .layer(DefaultBodyLimit::max(*MAXIMUM_SYNC_PAYLOAD_BYTES))
+            .into_make_service_with_connect_info::<SocketAddr>();
+        let future: ServerFuture = Box::pin(
+            axum::Server::from_tcp(listener)
+                .whatever_context("listen failed")?
+                .serve(server)
+                .with_graceful_shutdown(shutdown),
+        );
+        info!(%addr, "hosting peer sync");
+        Ok((addr, future))
+    }
+
+    /// Run op on a worker thread, if the request has the correct key.
+    async fn with_state<F, I, O>(self: &Arc<Self>, req: SyncRequest<I>, op: F) -> HttpResult<O>
+    where
+        F: FnOnce(&mut PeerState, SyncRequest<I>) -> HttpResult<O> + Send + 'static,
+        I: Send + 'static,
+        O: Send + 'static,
+    {
+        let valid = self
+            .pairing
+            .lock()
+            .unwrap()
+            .session_keys
+            .get(&req.ip)
+            .map_or(false, |key| secrets_match(&req.sync_key, key));
+        if !valid {
+            None.or_forbidden("invalid session key")?;
+        }
+        let server = self.clone();
+        spawn_blocking(move || op(&mut server.state.lock().unwrap(), req))
+            .await
+            .or_internal_err("sync op")?
+    }
+}
+
+impl HostedCollection for PeerState {
+    fn parts(&mut self) -> (&mut Option<Collection>, &mut Option<ServerSyncState>) {
+        (&mut self.col, &mut self.sync_state)
+    }
+
+    fn open_collection(&self) -> HttpResult<Collection> {
+        open_server_collection(&self.col_path).or_internal_err("open col")
+    }
+
+    /// The backup is complete before returning.
+    fn backup_before_upload(&mut self) -> HttpResult<()> {
+        self.ensure_col_open()?;
+        let col = self.col.as_mut().unwrap();
+        if !col
+            .storage
+            .have_at_least_one_card()
+            .or_internal_err("check for cards")?
+        {
+            return Ok(());
+        }
+        create_dir_all(&self.backup_folder).or_internal_err("creating backup folder")?;
+        if let Some(task) = col
+            .maybe_backup(self.backup_folder.clone(), true)
+            .or_internal_err("backup")?
+        {
+            task.join()
+                .ok()
+                .or_internal_err("backup panicked")?
+                .or_internal_err("backup")?;
+        }
+        Ok(())
+    }
+}
+
+fn open_server_collection(path: &Path) -> Result<Collection> {
+    CollectionBuilder::new(path).set_server(true).build()
+}
+
+fn unsupported<T>() -> HttpResult<T> {
+    None.or_http_err(StatusCode::NOT_IMPLEMENTED, "not supported by peers")
+}
+
+#[async_trait]
+impl SyncProtocol for Arc<PeerServer> {
+    async fn host_key(
+        &self,
+        req: SyncRequest<HostKeyRequest>,
+    ) -> HttpResult<SyncResponse<HostKeyResponse>> {
+        let password = req.json()?.password;
+        let mut pairing = self.pairing.lock().unwrap();
+        if pairing.failures >= MAX_PAIRING_FAILURES {
+            None.or_forbidden("too many failed logins; restart hosting to pair again")?;
+        }
+        if !secrets_match(&password, &self.pairing_key) {
+            pairing.failures += 1;
+            None.or_forbidden("invalid pairing key")?;
+        }
+        let key = new_host_key();
+        pairing.session_keys.insert(req.ip, key.clone());
+        SyncResponse::try_from_obj(HostKeyResponse { key })
+    }
+
+    async fn meta(&self, req: SyncRequest<MetaRequest>) -> HttpResult<SyncResponse<SyncMeta>> {
+        self.with_state(req, |state, req| {
+            let mut meta = state.handle_meta(req)?;
+            // peers are on the same network, so chunked transfers are not
+            // needed; single-request full syncs keep everything a chunked one
+            // would, including the revlog algorithms
+            meta.max_sync_version = SYNC_VERSION_11_DIRECT_POST;
+            SyncResponse::try_from_obj(meta)
+        })
+        .await
+    }
+
+    async fn start(&self, req: SyncRequest<StartRequest>) -> HttpResult<SyncResponse<Graves>> {
+        self.with_state(req, |state, req| state.handle_start(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
+    }
+
+    async fn apply_graves(
+        &self,
+        req: SyncRequest<ApplyGravesRequest>,
+    ) -> HttpResult<SyncResponse<()>> {
+        self.with_state(req, |state, req| state.handle_apply_graves(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
+    }
+
+    async fn apply_changes(
+        &self,
+        req: SyncRequest<ApplyChangesRequest>,
+    ) -> HttpResult<SyncResponse<UnchunkedChanges>> {
+        self.with_state(req, |state, req| state.handle_apply_changes(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
+    }
+
+    async fn chunk(&self, req: SyncRequest<EmptyInput>) -> HttpResult<SyncResponse<Chunk>> {
+        self.with_state(req, |state, req| state.handle_chunk(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
+    }
+
+    async fn apply_chunk(
+        &self,
+        req: SyncRequest<ApplyChunkRequest>,
+    ) -> HttpResult<SyncResponse<()>> {
+        self.with_state(req, |state, req| state.handle_apply_chunk(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
+    }
+
+    async fn sanity_check(
+        &self,
+        req: SyncRequest<SanityCheckRequest>,
+    ) -> HttpResult<SyncResponse<SanityCheckResponse>> {
+        self.with_state(req, |state, req| state.handle_sanity_check(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
+    }
+
+    async fn finish(
+        &self,
+        req: SyncRequest<EmptyInput>,
+    ) -> HttpResult<SyncResponse<TimestampMillis>> {
+        self.with_state(req, |state, req| state.handle_finish(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
+    }
+
+    async fn abort(&self, req: SyncRequest<EmptyInput>) -> HttpResult<SyncResponse<()>> {
+        self.with_state(req, |state, req| state.handle_abort(req))
+            .await
+            .and_then(SyncResponse::try_from_obj)
+    }
+
+    async fn upload(&self, req: SyncRequest<Vec<u8>>) -> HttpResult<SyncResponse<UploadResponse>> {
+        self.with_state(req, |state, req| state.handle_upload(req.data))
+            .await
+            .map(SyncResponse::from_upload_response)
+    }
+
+    async fn download(&self, req: SyncRequest<EmptyInput>) -> HttpResult<SyncResponse<Vec<u8>>> {
+        self.with_state(req, |state, req| state.handle_download(req))
+            .await
+            .map(SyncResponse::from_vec)
+    }
+
+    async fn upload_start(
+        &self,
+        _req: SyncRequest<TransferInfo>,
+    ) -> HttpResult<SyncResponse<UploadStartResponse>> {
+        unsupported()
+    }
+
+    async fn upload_chunk(&self, _req: SyncRequest<Vec<u8>>) -> HttpResult<SyncResponse<()>> {
+        unsupported()
+    }
+
+    async fn upload_finish(
+        &self,
+        _req: SyncRequest<EmptyInput>,
+    ) -> HttpResult<SyncResponse<UploadResponse>> {
+        unsupported()
+    }
+
+    async fn download_start(
+        &self,
+        _req: SyncRequest<DownloadStartRequest>,
+    ) -> HttpResult<SyncResponse<TransferInfo>> {
+        unsupported()
+    }
+
+    async fn download_chunk(
+        &self,
+        _req: SyncRequest<DownloadChunkRequest>,
+    ) -> HttpResult<SyncResponse<Vec<u8>>> {
+        unsupported()
+    }
+
+    async fn download_finish(&self, _req: SyncRequest<EmptyInput>) -> HttpResult<SyncResponse<()>> {
+        unsupported()
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+use std::time::Instant;
+
+use axum::http::StatusCode;
+use snafu::ResultExt;
+use snafu::Whatever;
+
+use crate::error;
+use crate::sync::error::HttpError;
+use crate::sync::error::HttpResult;
+use crate::sync::media::database::server::meta::StoreMetadata;
+
+pub(crate) const MB: u64 = 1024 * 1024;
+
+/// Storage and request limits for a user. A limit of None is unlimited.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Quota {
+    pub collection_bytes: Option<u64>,
+    pub media_bytes: Option<u64>,
+    pub media_files: Option<u64>,
+    /// Bursts of up to this many requests are allowed, after which requests
+    /// are accepted at this rate.
+    pub requests_per_minute: Option<u64>,
+}
+
+impl Quota {
+    /// The server-wide defaults, from the SYNC_QUOTA_COLLECTION_MB,
+    /// SYNC_QUOTA_MEDIA_MB, SYNC_QUOTA_MEDIA_FILES and
+    /// SYNC_QUOTA_REQUESTS_PER_MINUTE env vars.
+    pub(crate) fn from_env() -> error::Result<Self, Whatever> {
+        let var = |name: &str, multiplier: u64| -> error::Result<Option<u64>, Whatever> {
+            env::var(name)
+                .ok()
+                .map(|val| {
+                    val.parse::<u64>()
+                        .map(|val| val * multiplier)
+                        .with_whatever_context(|_| format!("{name} should be a number"))
+                })
+                .transpose()
+        };
+        Ok(Self {
+            collection_bytes: var("SYNC_QUOTA_COLLECTION_MB", MB)?,
+            media_bytes: var("SYNC_QUOTA_MEDIA_MB", MB)?,
+            media_files: var("SYNC_QUOTA_MEDIA_FILES", 1)?,
+            requests_per_minute: var("SYNC_QUOTA_REQUESTS_PER_MINUTE", 1)?,
+        })
+    }
+
+    /// Fill in any limits not set on self from `defaults`.
+    pub(crate) fn or(self, defaults: Quota) -> Quota {
+        Quota {
+            collection_bytes: self.collection_bytes.or(defaults.collection_bytes),
+            media_bytes: self.media_bytes.or(defaults.media_bytes),
+            media_files: self.media_files.or(defaults.media_files),
+            requests_per_minute: self.requests_per_minute.or(defaults.requests_per_minute),
+        }
+    }
+
+    pub(crate) fn check_collection(&self, size: u64) -> HttpResult<()> {
+        match self.collection_bytes {
+            Some(limit) if size > limit => {
+                Err(quota_exceeded(format!("collection {size} > {limit}")))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Changes that reduce usage are always allowed, so that a user over
+    /// their quota can get back under it.
+    pub(crate) fn check_media(&self, before: &StoreMetadata, after: &StoreMetadata) -> bool {
+        let over = |limit: Option<u64>, before: u64, after: u64| {
+            limit.map_or(false, |limit| after > limit && after > before)
+        };
+        !over(self.media_bytes, before.total_bytes, after.total_bytes)
+            && !over(
+                self.media_files,
+                before.total_nonempty_files as u64,
+                after.total_nonempty_files as u64,
+            )
+    }
+}
+
+/// Reported to clients as [crate::error::SyncErrorKind::QuotaExceeded].
+pub(crate) fn quota_exceeded(context: impl Into<String>) -> HttpError {
+    HttpError::new_without_source(StatusCode::INSUFFICIENT_STORAGE, context)
+}
+
+/// Once this many keys are tracked, those that have had a minute to refill
+/// are forgotten, so that logins with made-up usernames can't use up memory.
+const MAX_IDLE_BUCKETS: usize = 10_000;
+
+/// Tracks the requests made with each key, such as a host key or the
+/// username of a login attempt.
+#[derive(Debug, Default)]
+pub(crate) struct RateLimiter {
+    buckets: HashMap<String, RequestBucket>,
+}
+
+const REFILL_PERIOD: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct RequestBucket {
+    /// Each request uses up a share of the refill period, and the
+    /// available time builds back up to the full period as time passes.
+    available: Duration,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    /// Fails with [crate::error::SyncErrorKind::RateLimited] if the key has
+    /// made too many requests.
+    pub(crate) fn check(&mut self, key: &str, per_minute: Option<u64>) -> HttpResult<()> {
+        self.check_at(key, per_minute, Instant::now())
+    }
+
+    fn check_at(&mut self, key: &str, per_minute: Option<u64>, now: Instant) -> HttpResult<()> {
+        let Some(per_minute) = per_minute else {
+            return Ok(());
+        };
+        if self.buckets.len() >= MAX_IDLE_BUCKETS {
+            self.buckets
+                .retain(|_, bucket| now.duration_since(bucket.updated) < REFILL_PERIOD);
+        }
+        let cost = REFILL_PERIOD / per_minute.clamp(1, u32::MAX as u64) as u32;
+        let bucket = self
+            .buckets
+            .entry(key.to_string())
+            .or_insert(RequestBucket {
+                available: REFILL_PERIOD,
+                updated: now,
+            });
+        bucket.available =
+            (bucket.available + now.duration_since(bucket.updated)).min(REFILL_PERIOD);
+        bucket.updated = now;
+        if bucket.available < cost {
+            return Err(HttpError::new_without_source(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("more than {per_minute} requests per minute"),
+            ));
+        }
+        bucket.available -= cost;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::prelude::Usn;
+
+    #[test]
+    fn media_quota() {
+        let quota = Quota {
+            media_bytes: Some(100),
+            media_files: Some(2),
+            ..Default::default()
+        };
+        let meta = |bytes, files| StoreMetadata {
+            last_usn: Usn(0),
+            total_bytes: bytes,
+            total_nonempty_files: files,
+        };
+        assert!(quota.check_media(&meta(0, 0), &meta(100, 2)));
+        assert!(!quota.check_media(&meta(0, 0), &meta(101, 1)));
+        assert!(!quota.check_media(&meta(0, 0), &meta(10, 3)));
+        // already over the limit, but shrinking
+        assert!(quota.check_media(&meta(200, 5), &meta(150, 5)));
+        assert!(!quota.check_media(&meta(200, 5), &meta(210, 4)));
+        // unlimited
+        assert!(Quota::default().check_media(&meta(0, 0), &meta(u64::MAX, u32::MAX)));
+    }
+
+    #[test]
+    fn rate_limit() {
+        let mut limiter = RateLimiter::default();
+        let start = Instant::now();
+        let check = |limiter: &mut RateLimiter, key, secs| {
+            limiter
+                .check_at(key, Some(2), start + Duration::from_secs(secs))
+                .is_ok()
+        };
+        // a burst of up to the limit is allowed
+        assert!(check(&mut limiter, "a", 0));
+        assert!(check(&mut limiter, "a", 0));
+        assert!(!check(&mut limiter, "a", 0));
+        // other keys are unaffected
+        assert!(check(&mut limiter, "b", 0));
+        // one request becomes available every 30 seconds
+        assert!(!check(&mut limiter, "a", 20));
+        assert!(check(&mut limiter, "a", 30));
+        assert!(!check(&mut limiter, "a", 30));
+        // unlimited
+        assert!(limiter.check_at("a", None, start).is_ok());
+    }
+}
@@ -0,0 +1,228 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Chunked full syncs. The partial upload and the download snapshot are kept
+//! in the user's folder between requests, each with its size and checksum
+//! saved alongside it, so that a transfer can be continued after the server
+//! restarts. The download snapshot is kept until the client finishes with it,
+//! starts a new download, or the collection is changed. The collection's
+//! modification time and usn are saved with the snapshot, so a snapshot of an
+//! older collection is never resumed.
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+
+use axum::http::StatusCode;
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+
+use crate::io::open_file;
+use crate::io::read_file;
+use crate::io::write_file;
+use crate::prelude::*;
+use crate::storage::SchemaVersion;
+use crate::sync::collection::download::server_download;
+use crate::sync::collection::resumable::info_path;
+use crate::sync::collection::resumable::DownloadChunkRequest;
+use crate::sync::collection::resumable::TransferInfo;
+use crate::sync::collection::resumable::UploadChunkRequest;
+use crate::sync::collection::resumable::UploadStartResponse;
+use crate::sync::collection::upload::handle_received_upload;
+use crate::sync::collection::upload::UploadResponse;
+use crate::sync::error::HttpResult;
+use crate::sync::error::OrHttpErr;
+use crate::sync::http_server::hosted::HostedCollection;
+use crate::sync::http_server::user::User;
+use crate::sync::request::MAXIMUM_SYNC_PAYLOAD_BYTES_UNCOMPRESSED;
+
+const INCOMPLETE_UPLOAD_MESSAGE: &str = "Your upload did not match its checksum. Please try again.";
+
+/// The state of the collection when a download snapshot was taken.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct SnapshotOrigin {
+    modified: TimestampMillis,
+    usn: Usn,
+}
+
+impl SnapshotOrigin {
+    fn of(col: &mut Collection) -> Result<Self> {
+        Ok(Self {
+            modified: col.storage.get_collection_timestamps()?.collection_change,
+            usn: col.usn()?,
+        })
+    }
+}
+
+impl User {
+    /// Returns the amount of the upload already received, if a previous
+    /// attempt to upload the same file was interrupted.
+    pub(in crate::sync) fn start_upload(
+        &mut self,
+        info: TransferInfo,
+    ) -> HttpResult<UploadStartResponse> {
+        if info.size >= *MAXIMUM_SYNC_PAYLOAD_BYTES_UNCOMPRESSED {
+            None.or_http_err(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "collection exceeds size limit",
+            )?;
+        }
+        self.quota.check_collection(info.size)?;
+        let path = self.partial_upload_path();
+        let offset = if TransferInfo::load_for(&path).as_ref() == Some(&info) {
+            fs::metadata(&path)
+                .map(|meta| meta.len())
+                .unwrap_or_default()
+        } else {
+            write_file(&path, b"").or_internal_err("create partial upload")?;
+            info.save_for(&path)
+                .or_internal_err("save partial upload info")?;
+            0
+        };
+        Ok(UploadStartResponse { offset })
+    }
+
+    /// Chunks may be resent. Anything received after the chunk's offset is
+    /// replaced by it.
+    pub(in crate::sync) fn receive_upload_chunk(
+        &mut self,
+        chunk: UploadChunkRequest,
+    ) -> HttpResult<()> {
+        let path = self.partial_upload_path();
+        let info = TransferInfo::load_for(&path).or_conflict("no upload in progress")?;
+        let end = chunk
+            .offset
+            .checked_add(chunk.data.len() as u64)
+            .or_bad_request("invalid chunk offset")?;
+        if end > info.size {
+            None.or_bad_request("chunk exceeds upload size")?;
+        }
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .or_internal_err("open partial upload")?;
+        let received = file
+            .metadata()
+            .or_internal_err("stat partial upload")?
+            .len();
+        if chunk.offset > received {
+            None.or_conflict(format!("chunk at {} after end {received}", chunk.offset))?;
+        }
+        file.set_len(chunk.offset)
+            .and_then(|_| file.seek(SeekFrom::Start(chunk.offset)))
+            .and_then(|_| file.write_all(&chunk.data))
+            .or_internal_err("write partial upload")
+    }
+
+    /// Verifies the received file, and if valid, replaces the collection
+    /// with it.
+    pub(in crate::sync) fn finish_upload(&mut self) -> HttpResult<UploadResponse> {
+        let path = self.partial_upload_path();
+        let info = TransferInfo::load_for(&path).or_conflict("no upload in progress")?;
+        let data = read_file(&path).or_internal_err("read partial upload")?;
+        remove_transfer(&path)?;
+        if TransferInfo::of_data(&data) != info {
+            return Ok(UploadResponse::Err(INCOMPLETE_UPLOAD_MESSAGE.into()));
+        }
+        // a snapshot of the replaced collection can't be resumed
+        self.finish_download()?;
+        self.backup_before_upload()?;
+        handle_received_upload(&mut self.col, data)
+    }
+
+    /// Takes a snapshot of the collection for the client to fetch, unless
+    /// the client is resuming the download of the current one, and the
+    /// collection has not changed since it was taken.
+    pub(in crate::sync) fn start_download(
+        &mut self,
+        schema_version: SchemaVersion,
+        resume: Option<TransferInfo>,
+    ) -> HttpResult<TransferInfo> {
+        let path = self.download_snapshot_path();
+        self.ensure_col_open()?;
+        let origin = SnapshotOrigin::of(self.col.as_mut().unwrap())
+            .or_internal_err("get collection state")?;
+        if let Some(resume) = resume {
+            if path.exists()
+                && TransferInfo::load_for(&path).as_ref() == Some(&resume)
+                && self.snapshot_origin().as_ref() == Some(&origin)
+            {
+                return Ok(resume);
+            }
+        }
+        let data = server_download(&mut self.col, schema_version)?;
+        write_file(&path, &data).or_internal_err("write download snapshot")?;
+        let info = TransferInfo::of_data(&data);
+        info.save_for(&path)
+            .or_internal_err("save download snapshot info")?;
+        write_file(
+            self.snapshot_origin_path(),
+            serde_json::to_vec(&origin).or_internal_err("encode snapshot origin")?,
+        )
+        .or_internal_err("save snapshot origin")?;
+        Ok(info)
+    }
+
+    /// Removes the download snapshot, if there is one. Called when the
+    /// client has finished with it, and whenever the collection is replaced
+    /// or changed.
+    pub(in crate::sync) fn finish_download(&mut self) -> HttpResult<()> {
+        remove_transfer(&self.download_snapshot_path())?;
+        match fs::remove_file(self.snapshot_origin_path()) {
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+            res => res.or_internal_err("remove snapshot origin"),
+        }
+    }
+
+    /// Returns an empty chunk if the offset is at or past the end. Chunks
+    /// may be requested again until the download is finished.
+    pub(in crate::sync) fn download_chunk(
+        &mut self,
+        req: DownloadChunkRequest,
+    ) -> HttpResult<Vec<u8>> {
+        let mut file =
+            open_file(self.download_snapshot_path()).or_conflict("no download in progress")?;
+        file.seek(SeekFrom::Start(req.offset))
+            .or_internal_err("seek download snapshot")?;
+        let mut data = vec![];
+        (&mut file)
+            .take(req.length)
+            .read_to_end(&mut data)
+            .or_internal_err("read download snapshot")?;
+        Ok(data)
+    }
+
+    fn partial_upload_path(&self) -> PathBuf {
+        self.folder.join("collection.upload")
+    }
+
+    fn download_snapshot_path(&self) -> PathBuf {
+        self.folder.join("collection.download")
+    }
+
+    fn snapshot_origin_path(&self) -> PathBuf {
+        self.folder.join("collection.download.origin")
+    }
+
+    fn snapshot_origin(&self) -> Option<SnapshotOrigin> {
+        let data = read_file(self.snapshot_origin_path()).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+}
+
+/// Removes a partial transfer and its saved info, if present.
+fn remove_transfer(path: &Path) -> HttpResult<()> {
+    for path in [path.to_owned(), info_path(path)] {
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            res => res.or_internal_err("remove partial transfer")?,
+        }
+    }
+    Ok(())
+}
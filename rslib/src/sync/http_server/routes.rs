@@ -47,6 +47,12 @@ async fn sync_handler<P: SyncProtocol>(
         SyncMethod::Abort => sync_method!(server, request, abort),
         SyncMethod::Upload => sync_method!(server, request, upload),
         SyncMethod::Download => sync_method!(server, request, download),
+        SyncMethod::UploadStart => sync_method!(server, request, upload_start),
+        SyncMethod::UploadChunk => sync_method!(server, request, upload_chunk),
+        SyncMethod::UploadFinish => sync_method!(server, request, upload_finish),
+        SyncMethod::DownloadStart => sync_method!(server, request, download_start),
+        SyncMethod::DownloadChunk => sync_method!(server, request, download_chunk),
+        SyncMethod::DownloadFinish => sync_method!(server, request, download_finish),
     })
 }
 
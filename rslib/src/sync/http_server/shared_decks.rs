@@ -0,0 +1,351 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+//! Decks that one user publishes, and other users receive read-only copies
+//! of. When a publisher's sync changes the deck, it is exported to an .apkg
+//! in their folder. When a subscriber starts a sync, any newer export is
+//! merged into their collection, so the changes arrive as part of a normal
+//! sync. As with importing a package, the subscriber's scheduling is
+//! preserved. Edits the subscriber makes to the shared notes are reverted
+//! at the start of their next sync.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde_derive::Deserialize;
+use serde_derive::Serialize;
+use tracing::info;
+use tracing::warn;
+
+use crate::collection::CollectionBuilder;
+use crate::import_export::package::MediaIter;
+use crate::io::create_dir_all;
+use crate::io::read_dir_files;
+use crate::io::read_file;
+use crate::io::write_file;
+use crate::media::files::mtime_as_i64;
+use crate::media::files::sha1_of_data;
+use crate::media::MediaManager;
+use crate::prelude::*;
+use crate::search::SearchNode;
+use crate::sync::error::HttpResult;
+use crate::sync::error::OrHttpErr;
+use crate::sync::http_server::hosted::HostedCollection;
+use crate::sync::http_server::media_manager::store::MediaStore;
+use crate::sync::http_server::user::User;
+use crate::sync::http_server::user_store::SharedDeck;
+use crate::sync::http_server::user_store::Subscription;
+use crate::sync::http_server::SimpleServer;
+use crate::sync::media::database::client::MediaEntry;
+use crate::sync::media::zip::UploadedChange;
+use crate::sync::media::zip::UploadedChangeKind;
+
+/// Whether a shared deck needs exporting again.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+struct ExportState {
+    /// The publisher's collection usn when the deck was exported. Changes
+    /// made in later syncs have this usn or a higher one.
+    usn: Usn,
+    /// Catches notes that have been removed or moved out of the deck.
+    notes: usize,
+}
+
+impl SimpleServer {
+    /// Export the decks the user publishes, if they have changed since they
+    /// were last exported, or if `force` is true. Failures are logged, so
+    /// they don't cause the publisher's own sync to fail.
+    pub(in crate::sync) fn export_shared_decks(&self, user: &mut User, force: bool) {
+        let shares = self.state.lock().unwrap().store.shared_decks_of(&user.name);
+        match shares {
+            Ok(shares) => {
+                for share in shares {
+                    if let Err(err) = user.export_shared_deck(&share, force) {
+                        warn!(deck = share.deck, ?err, "exporting shared deck failed");
+                    }
+                }
+            }
+            Err(err) => warn!(?err, "listing shared decks failed"),
+        }
+    }
+
+    /// Merge any changes to the decks the user subscribes to into their
+    /// collection. Failures are logged, and retried on the next sync.
+    pub(in crate::sync) fn merge_subscribed_decks(&self, user: &mut User) {
+        let (subscriptions, base_folder) = {
+            let state = self.state.lock().unwrap();
+            (
+                state.store.subscriptions(&user.name),
+                state.base_folder.clone(),
+            )
+        };
+        let subscriptions = match subscriptions {
+            Ok(subscriptions) => subscriptions,
+            Err(err) => {
+                warn!(?err, "listing subscriptions failed");
+                return;
+            }
+        };
+        for sub in subscriptions {
+            let path = shared_deck_path(&base_folder.join(&sub.share.publisher), sub.share.id);
+            // not exported yet
+            let Some(modified) = modified_time(&path) else {
+                continue;
+            };
+            match user.shared_notes_edited(&sub) {
+                Ok(false) if modified == sub.merged => continue,
+                Ok(_) => (),
+                Err(err) => {
+                    warn!(deck = sub.share.deck, ?err, "checking for edits failed");
+                    continue;
+                }
+            }
+            let checked_usn = match user.merge_shared_deck(&path) {
+                Ok(usn) => usn,
+                Err(err) => {
+                    warn!(deck = sub.share.deck, ?err, "merging shared deck failed");
+                    continue;
+                }
+            };
+            info!(
+                publisher = sub.share.publisher,
+                deck = sub.share.deck,
+                "merged shared deck"
+            );
+            let state = self.state.lock().unwrap();
+            if let Err(err) =
+                state
+                    .store
+                    .set_merged(&user.name, sub.share.id, modified, checked_usn)
+            {
+                warn!(?err, "recording merge failed");
+            }
+        }
+    }
+}
+
+impl User {
+    fn export_shared_deck(&mut self, share: &SharedDeck, force: bool) -> HttpResult<()> {
+        let path = shared_deck_path(&self.folder, share.id);
+        let state_path = path.with_extension("json");
+        self.ensure_col_open()?;
+        let col = self.col.as_mut().unwrap();
+        if col
+            .get_deck_id(&share.deck)
+            .or_internal_err("get deck")?
+            .is_none()
+        {
+            // renamed or deleted; subscribers keep the copy they have
+            return Ok(());
+        }
+        let previous: Option<ExportState> = read_file(&state_path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok());
+        let (notes, changed) = deck_changed_since(
+            col,
+            &share.deck,
+            previous.as_ref().map(|p| p.usn).unwrap_or_default(),
+            DECK_CHANGED_SQL,
+        )
+        .or_internal_err("check for changes")?;
+        let state = ExportState {
+            usn: col.usn().or_internal_err("get usn")?,
+            notes,
+        };
+        if !force && path.exists() && !changed && previous.map_or(false, |p| p.notes == notes) {
+            return Ok(());
+        }
+        create_dir_all(path.parent().unwrap()).or_internal_err("create shared folder")?;
+        // the exporter reads media from a folder, so the files the deck uses
+        // are copied out of the media store into a temporary one
+        let temp_dir = tempfile::tempdir_in(&self.folder).or_internal_err("temp dir")?;
+        let media_folder = temp_dir.path().to_owned();
+        let store = self.media.store.clone();
+        let checksums = self
+            .media
+            .db
+            .nonempty_checksums()
+            .or_internal_err("get checksums")?;
+        col.export_apkg(
+            &path,
+            SearchNode::from_deck_name(&share.deck),
+            false,
+            true,
+            false,
+            Some(Box::new(move |files| {
+                copy_from_store(&*store, &checksums, &files, &media_folder);
+                MediaIter::from_file_list(files, media_folder)
+            })),
+            |_, _| true,
+        )
+        .or_internal_err("export shared deck")?;
+        write_file(
+            &state_path,
+            serde_json::to_vec(&state).or_internal_err("encode export state")?,
+        )
+        .or_internal_err("write export state")?;
+        Ok(())
+    }
+
+    /// True if the subscriber has changed the deck's notes since their copy
+    /// was last merged.
+    fn shared_notes_edited(&mut self, sub: &Subscription) -> HttpResult<bool> {
+        self.ensure_col_open()?;
+        let col = self.col.as_mut().unwrap();
+        // a sync that merges also sends the subscriber's edits, which are
+        // given the same usn as the merged notes, so a merge is checked
+        // again on the following sync
+        deck_changed_since(col, &sub.share.deck, sub.checked_usn, NOTES_CHANGED_SQL)
+            .map(|(_, changed)| changed)
+            .or_internal_err("check for edits")
+    }
+
+    /// Imports the deck, reverting any edits the subscriber has made to its
+    /// notes, and returns the usn the merged notes were given. If the media
+    /// it adds would exceed the subscriber's quota, the import is undone, so
+    /// the notes are not left without their media. Shared files never
+    /// replace the subscriber's own files; if a name is taken, the shared
+    /// file is renamed, as when importing a package.
+    fn merge_shared_deck(&mut self, apkg: &Path) -> HttpResult<Usn> {
+        self.abort_stateful_sync_if_active();
+        self.col = None;
+        // the importer expects a client-style media folder, so media is
+        // imported into a temporary one, and then added to the server's
+        let temp_dir = tempfile::tempdir_in(&self.folder).or_internal_err("temp dir")?;
+        let media_folder = temp_dir.path().join("media");
+        let mut col = CollectionBuilder::new(self.folder.join("collection.anki2"))
+            .set_server(true)
+            .set_media_paths(media_folder.clone(), temp_dir.path().join("media.db"))
+            .build()
+            .or_internal_err("open collection")?;
+        let checksums = self
+            .media
+            .db
+            .nonempty_checksums()
+            .or_internal_err("get checksums")?;
+        col.media()
+            .and_then(|mgr| register_existing_media(&mgr, checksums))
+            .or_internal_err("register existing media")?;
+        let usn = col.usn().or_internal_err("get usn")?;
+        col.import_apkg_reverting_edits(apkg, |_, _| true)
+            .or_internal_err("import shared deck")?;
+
+        let res = read_media_changes(&media_folder).and_then(|changes| {
+            if !changes.is_empty() {
+                self.media.apply_changes(changes, &self.quota)?;
+            }
+            Ok(())
+        });
+        if res.is_err() {
+            col.undo().or_internal_err("undo import")?;
+        }
+        col.close(None).or_internal_err("close collection")?;
+        res.map(|_| usn)
+    }
+}
+
+/// Notes or cards in the deck, or the notetypes of its notes.
+const DECK_CHANGED_SQL: &str = "select exists(select 1 from notes
+    where id in (select nid from search_nids) and usn >= ?1)
+    or exists(select 1 from cards
+    where nid in (select nid from search_nids) and usn >= ?1)
+    or exists(select 1 from notetypes where usn >= ?1 and id in
+    (select mid from notes where id in (select nid from search_nids)))";
+
+/// Notes in the deck, ignoring changes to the subscriber's own scheduling.
+const NOTES_CHANGED_SQL: &str = "select exists(select 1 from notes
+    where id in (select nid from search_nids) and usn >= ?1)";
+
+/// Returns the number of notes in the deck, and whether `sql` finds changes
+/// to it with a usn of `usn` or later.
+fn deck_changed_since(
+    col: &mut Collection,
+    deck: &str,
+    usn: Usn,
+    sql: &str,
+) -> Result<(usize, bool)> {
+    let guard = col.search_notes_into_table(SearchNode::from_deck_name(deck))?;
+    let changed = guard
+        .col
+        .storage
+        .db
+        .query_row(sql, [usn], |row| row.get(0))?;
+    Ok((guard.notes, changed))
+}
+
+/// Records the subscriber's media in the temporary media db, without the
+/// files being present. The importer then gives a shared file a new name if
+/// the subscriber has a different file with the same name, instead of
+/// replacing it.
+fn register_existing_media(mgr: &MediaManager, checksums: HashMap<String, Vec<u8>>) -> Result<()> {
+    mgr.db.transact(|db| {
+        for (fname, sha1) in checksums {
+            let Ok(sha1) = Sha1Hash::try_from(sha1) else {
+                continue;
+            };
+            db.set_entry(&MediaEntry {
+                fname,
+                sha1: Some(sha1),
+                mtime: 0,
+                sync_required: false,
+            })?;
+        }
+        // stop the folder from being scanned, which would drop the entries
+        let mut meta = db.get_meta()?;
+        meta.folder_mtime = mtime_as_i64(&mgr.media_folder)?;
+        db.set_meta(&meta)
+    })
+}
+
+fn read_media_changes(media_folder: &Path) -> HttpResult<Vec<UploadedChange>> {
+    if !media_folder.exists() {
+        return Ok(vec![]);
+    }
+    read_dir_files(media_folder)
+        .or_internal_err("read media")?
+        .map(|entry| {
+            let entry = entry.or_internal_err("read media")?;
+            let data = fs::read(entry.path()).or_internal_err("read media")?;
+            Ok(UploadedChange {
+                nfc_filename: entry.file_name().to_string_lossy().into(),
+                kind: UploadedChangeKind::AddOrReplace {
+                    sha1: sha1_of_data(&data).to_vec(),
+                    nonempty_data: data,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Files that can't be copied are logged, and left out of the export.
+fn copy_from_store(
+    store: &dyn MediaStore,
+    checksums: &HashMap<String, Vec<u8>>,
+    files: &HashSet<String>,
+    folder: &Path,
+) {
+    for fname in files {
+        let Some(sha1) = checksums.get(fname) else {
+            continue;
+        };
+        let res = store.read(fname, sha1).and_then(|data| match data {
+            Some(data) => write_file(folder.join(fname), data).map_err(Into::into),
+            None => Ok(()),
+        });
+        if let Err(err) = res {
+            warn!(fname, ?err, "unable to copy shared media");
+        }
+    }
+}
+
+fn shared_deck_path(user_folder: &Path, share_id: i64) -> PathBuf {
+    user_folder.join("shared").join(format!("{share_id}.apkg"))
+}
+
+fn modified_time(path: &Path) -> Option<TimestampMillis> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    let elapsed = modified.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Some(TimestampMillis(elapsed.as_millis() as i64))
+}
@@ -0,0 +1,308 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+
+use axum::extract::connect_info::Connected;
+use hyper::server::accept::from_stream;
+use hyper::server::accept::Accept;
+use rustls_pemfile::Item;
+use snafu::OptionExt;
+use snafu::ResultExt;
+use snafu::Whatever;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::ReadBuf;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::sync::Notify;
+use tokio::time::sleep;
+use tokio::time::timeout;
+use tokio_rustls::rustls::Certificate;
+use tokio_rustls::rustls::PrivateKey;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tracing::debug;
+use tracing::error;
+use tracing::info;
+
+use crate::error;
+use crate::sync::http_server::tls::TlsPaths;
+
+/// Clients that take longer than this to complete a handshake are dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Accepts TLS connections on the provided listener. Handshakes are done in
+/// the background, so a slow client does not hold up other connections.
+pub(in crate::sync::http_server) fn tls_incoming(
+    listener: std::net::TcpListener,
+    paths: TlsPaths,
+) -> error::Result<impl Accept<Conn = TlsConnection, Error = io::Error>, Whatever> {
+    tls_incoming_with_reload(listener, paths, Default::default())
+}
+
+/// As [tls_incoming], but the certificate is also reloaded each time `reload`
+/// is notified.
+fn tls_incoming_with_reload(
+    listener: std::net::TcpListener,
+    paths: TlsPaths,
+    reload: Arc<Notify>,
+) -> error::Result<impl Accept<Conn = TlsConnection, Error = io::Error>, Whatever> {
+    let config = load_config(&paths)?;
+    listener
+        .set_nonblocking(true)
+        .whatever_context("set nonblocking")?;
+    let listener = TcpListener::from_std(listener).whatever_context("listen failed")?;
+    // registered up front, so a SIGHUP straight after startup is not missed
+    let reload = ReloadSignal::new(reload)?;
+    let (tx, mut rx) = mpsc::channel(32);
+    tokio::spawn(accept_loop(listener, paths, config, reload, tx));
+    Ok(from_stream(futures::stream::poll_fn(move |cx| {
+        rx.poll_recv(cx).map(|conn| conn.map(Ok::<_, io::Error>))
+    })))
+}
+
+/// Runs until the server is dropped.
+async fn accept_loop(
+    listener: TcpListener,
+    paths: TlsPaths,
+    mut config: Arc<ServerConfig>,
+    mut reload: ReloadSignal,
+    tx: mpsc::Sender<TlsConnection>,
+) {
+    loop {
+        tokio::select! {
+            _ = tx.closed() => return,
+            _ = reload.recv() => match load_config(&paths) {
+                Ok(new_config) => {
+                    config = new_config;
+                    info!("reloaded TLS certificate");
+                }
+                Err(err) => error!(?err, "unable to reload TLS certificate; keeping the old one"),
+            },
+            accepted = listener.accept() => match accepted {
+                Ok((stream, remote)) => {
+                    let acceptor = TlsAcceptor::from(config.clone());
+                    let tx = tx.clone();
+                    tokio::spawn(async move {
+                        match timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                            Ok(Ok(stream)) => {
+                                let _ = tx.send(TlsConnection { stream, remote }).await;
+                            }
+                            Ok(Err(err)) => debug!(%remote, ?err, "TLS handshake failed"),
+                            Err(_) => debug!(%remote, "TLS handshake timed out"),
+                        }
+                    });
+                }
+                Err(err) => {
+                    // likely out of file descriptors; give existing connections a chance to close
+                    error!(?err, "accept failed");
+                    sleep(Duration::from_secs(1)).await;
+                }
+            },
+        }
+    }
+}
+
+fn load_config(paths: &TlsPaths) -> error::Result<Arc<ServerConfig>, Whatever> {
+    let open = |path: &Path| {
+        File::open(path)
+            .map(BufReader::new)
+            .with_whatever_context(|_| format!("opening {path:?}"))
+    };
+    let certs: Vec<_> = rustls_pemfile::certs(&mut open(&paths.cert)?)
+        .whatever_context("reading certificate")?
+        .into_iter()
+        .map(Certificate)
+        .collect();
+    let key = rustls_pemfile::read_all(&mut open(&paths.key)?)
+        .whatever_context("reading key")?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(key) | Item::RSAKey(key) | Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .with_whatever_context(|| format!("no private key in {:?}", paths.key))?;
+    let mut config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .whatever_context("invalid certificate or key")?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// Resolves each time the process receives SIGHUP, or the trigger is
+/// notified. Only the trigger is used on platforms without signals.
+struct ReloadSignal {
+    #[cfg(unix)]
+    hangup: tokio::signal::unix::Signal,
+    trigger: Arc<Notify>,
+}
+
+impl ReloadSignal {
+    fn new(trigger: Arc<Notify>) -> error::Result<Self, Whatever> {
+        Ok(Self {
+            #[cfg(unix)]
+            hangup: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                .whatever_context("listening for SIGHUP")?,
+            trigger,
+        })
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        tokio::select! {
+            Some(()) = self.hangup.recv() => (),
+            _ = self.trigger.notified() => (),
+        }
+        #[cfg(not(unix))]
+        self.trigger.notified().await;
+    }
+}
+
+/// An established TLS connection. Wraps the stream so the client's address
+/// is available to the router.
+pub(in crate::sync::http_server) struct TlsConnection {
+    stream: TlsStream<TcpStream>,
+    remote: SocketAddr,
+}
+
+impl Connected<&TlsConnection> for SocketAddr {
+    fn connect_info(target: &TlsConnection) -> Self {
+        target.remote
+    }
+}
+
+impl AsyncRead for TlsConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for TlsConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::prelude::*;
+    use crate::sync::http_server::SimpleServer;
+
+    /// Returns the new certificate, so a client can trust it.
+    fn write_self_signed_cert(paths: &TlsPaths) -> reqwest::Certificate {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let pem = cert.serialize_pem().unwrap();
+        std::fs::write(&paths.cert, &pem).unwrap();
+        std::fs::write(&paths.key, cert.serialize_private_key_pem()).unwrap();
+        reqwest::Certificate::from_pem(pem.as_bytes()).unwrap()
+    }
+
+    async fn connects(addr: SocketAddr, trusted: &reqwest::Certificate) -> bool {
+        reqwest::Client::builder()
+            .use_rustls_tls()
+            .add_root_certificate(trusted.clone())
+            .build()
+            .unwrap()
+            .get(format!("https://localhost:{}/", addr.port()))
+            .send()
+            .await
+            .is_ok()
+    }
+
+    #[tokio::test]
+    async fn https() -> Result<()> {
+        let dir = tempdir()?;
+        let paths = TlsPaths {
+            cert: dir.path().join("cert.pem"),
+            key: dir.path().join("key.pem"),
+        };
+        let first = write_self_signed_cert(&paths);
+        let (addr, server_fut) =
+            SimpleServer::make_server_with_tls(None, &dir.path().join("base"), Some(paths.clone()))
+                .unwrap();
+        tokio::spawn(server_fut);
+        assert!(connects(addr, &first).await);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn renewed_certificate_is_used_after_reload() -> Result<()> {
+        let dir = tempdir()?;
+        let paths = TlsPaths {
+            cert: dir.path().join("cert.pem"),
+            key: dir.path().join("key.pem"),
+        };
+        let first = write_self_signed_cert(&paths);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let reload = Arc::new(Notify::new());
+        let incoming = tls_incoming_with_reload(listener, paths.clone(), reload.clone()).unwrap();
+        tokio::spawn(
+            axum::Server::builder(incoming)
+                .serve(axum::Router::new().into_make_service_with_connect_info::<SocketAddr>()),
+        );
+        assert!(connects(addr, &first).await);
+
+        // a renewed certificate is not used until a reload is requested
+        let second = write_self_signed_cert(&paths);
+        assert!(!connects(addr, &second).await);
+        reload.notify_one();
+        let mut reloaded = false;
+        for _ in 0..50 {
+            if connects(addr, &second).await {
+                reloaded = true;
+                break;
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        assert!(reloaded);
+        assert!(!connects(addr, &first).await);
+
+        Ok(())
+    }
+}
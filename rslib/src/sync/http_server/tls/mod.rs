@@ -0,0 +1,40 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+#[cfg(feature = "rustls")]
+mod acceptor;
+
+use std::env;
+use std::path::PathBuf;
+
+#[cfg(feature = "rustls")]
+pub(super) use acceptor::tls_incoming;
+use snafu::whatever;
+use snafu::Whatever;
+
+use crate::error;
+
+/// PEM files for serving HTTPS. The files are read again when the server
+/// receives SIGHUP, so renewed certificates can be used without a restart.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "rustls"), allow(dead_code))]
+pub(in crate::sync) struct TlsPaths {
+    /// The certificate chain, starting with the server's certificate.
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl TlsPaths {
+    /// From the SYNC_TLS_CERT and SYNC_TLS_KEY env vars. If neither is set,
+    /// the server uses plain HTTP.
+    pub(super) fn from_env() -> error::Result<Option<Self>, Whatever> {
+        match (env::var_os("SYNC_TLS_CERT"), env::var_os("SYNC_TLS_KEY")) {
+            (Some(cert), Some(key)) => Ok(Some(Self {
+                cert: cert.into(),
+                key: key.into(),
+            })),
+            (None, None) => Ok(None),
+            _ => whatever!("SYNC_TLS_CERT and SYNC_TLS_KEY must be set together"),
+        }
+    }
+}
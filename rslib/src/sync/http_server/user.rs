@@ -1,17 +1,27 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use std::path::Path;
 use std::path::PathBuf;
+use std::thread::JoinHandle;
 
 use tracing::info;
+use tracing::warn;
 
+use crate::collection::backup::list_backups;
 use crate::collection::Collection;
 use crate::collection::CollectionBuilder;
 use crate::error;
+use crate::import_export::package::restore_collection_from_colpkg;
+use crate::io::create_dir_all;
+use crate::prelude::TimestampSecs;
 use crate::sync::collection::start::ServerSyncState;
 use crate::sync::error::HttpResult;
 use crate::sync::error::OrHttpErr;
+use crate::sync::http_server::hosted::HostedCollection;
+use crate::sync::http_server::media_manager::store::MediaStoreKind;
 use crate::sync::http_server::media_manager::ServerMediaManager;
+use crate::sync::http_server::quota::Quota;
 
 pub(in crate::sync) struct User {
     pub name: String,
@@ -19,76 +29,132 @@ pub(in crate::sync) struct User {
     pub sync_state: Option<ServerSyncState>,
     pub media: ServerMediaManager,
     pub folder: PathBuf,
+    /// When a sync last completed, if one has since the server started.
+    pub last_sync: Option<TimestampSecs>,
+    /// Refreshed on each request.
+    pub quota: Quota,
+    /// A backup being written in the background.
+    backup_task: Option<JoinHandle<error::Result<()>>>,
 }
 
 impl User {
-    /// Run op with access to the collection. If a sync is active, it's aborted.
-    pub(crate) fn with_col<F, T>(&mut self, op: F) -> HttpResult<T>
-    where
-        F: FnOnce(&mut Collection) -> HttpResult<T>,
-    {
-        self.abort_stateful_sync_if_active();
-        self.ensure_col_open()?;
-        op(self.col.as_mut().unwrap())
+    pub(crate) fn new(
+        base_folder: &Path,
+        name: &str,
+        media_store: MediaStoreKind,
+    ) -> HttpResult<Self> {
+        let folder = base_folder.join(name);
+        create_dir_all(&folder).or_internal_err("creating user folder")?;
+        let media = ServerMediaManager::new(&folder, media_store)?;
+        Ok(Self {
+            name: name.into(),
+            col: None,
+            sync_state: None,
+            media,
+            folder,
+            last_sync: None,
+            quota: Default::default(),
+            backup_task: None,
+        })
     }
 
-    /// Run op with the existing sync state created by start_new_sync(). If
-    /// there is no existing state, or the current state's key does not
-    /// match, abort the request with a conflict.
-    pub(crate) fn with_sync_state<F, T>(&mut self, skey: &str, op: F) -> HttpResult<T>
-    where
-        F: FnOnce(&mut Collection, &mut ServerSyncState) -> error::Result<T>,
-    {
-        match &self.sync_state {
-            None => None.or_conflict("no active sync")?,
-            Some(state) => {
-                if state.skey != skey {
-                    None.or_conflict("active sync with different key")?;
-                }
-            }
-        };
+    /// Back up the collection if it has changed since the last backup, using
+    /// the same schedule and limits as the client. The backup is written in
+    /// the background.
+    pub(crate) fn maybe_backup(&mut self) -> HttpResult<()> {
+        self.backup(false)
+    }
 
+    fn backup(&mut self, force: bool) -> HttpResult<()> {
+        self.wait_for_backup();
+        let folder = self.backup_folder();
+        create_dir_all(&folder).or_internal_err("creating backup folder")?;
         self.ensure_col_open()?;
-        let state = self.sync_state.as_mut().unwrap();
-        let col = self.col.as_mut().or_internal_err("open col")?;
-        // Failures in a sync op are usually caused by referential integrity issues (eg
-        // they've sent a note without sending its associated notetype).
-        // Returning HTTP 400 will inform the client that a DB check+full sync
-        // is required to fix the issue.
-        op(col, state)
-            .map_err(|e| {
-                self.col = None;
-                self.sync_state = None;
-                e
-            })
-            .or_bad_request("op failed in sync_state")
+        self.backup_task = self
+            .col
+            .as_mut()
+            .unwrap()
+            .maybe_backup(folder, force)
+            .or_internal_err("backup")?;
+        Ok(())
     }
 
-    pub(crate) fn abort_stateful_sync_if_active(&mut self) {
-        if self.sync_state.is_some() {
-            info!("aborting active sync");
-            self.sync_state = None;
-            self.col = None;
+    /// File names of the user's backups, newest first.
+    pub(crate) fn backups(&mut self) -> HttpResult<Vec<String>> {
+        self.wait_for_backup();
+        let folder = self.backup_folder();
+        if !folder.exists() {
+            return Ok(vec![]);
         }
+        Ok(list_backups(&folder)
+            .or_internal_err("listing backups")?
+            .into_iter()
+            .filter_map(|path| path.file_name()?.to_str().map(Into::into))
+            .collect())
     }
 
-    pub(crate) fn start_new_sync(&mut self, skey: &str) -> HttpResult<()> {
+    /// Replace the collection with one of the user's backups. Media is not
+    /// affected. As the schema is marked modified, the user's devices will
+    /// be asked for a one-way sync when they next connect, and should choose
+    /// to download.
+    pub(crate) fn restore_backup(&mut self, file_name: &str) -> HttpResult<()> {
+        if !self.backups()?.iter().any(|name| name == file_name) {
+            None.or_not_found("no such backup")?;
+        }
         self.abort_stateful_sync_if_active();
-        self.sync_state = Some(ServerSyncState::new(skey));
+        self.col = None;
+        self.finish_download()?;
+        restore_collection_from_colpkg(
+            &self.backup_folder().join(file_name),
+            &self.folder.join("collection.anki2"),
+        )
+        .or_internal_err("restoring backup")?;
+        info!(backup = file_name, "restored backup");
         Ok(())
     }
 
-    pub(crate) fn ensure_col_open(&mut self) -> HttpResult<()> {
-        if self.col.is_none() {
-            self.col = Some(self.open_collection()?);
+    fn wait_for_backup(&mut self) {
+        if let Some(task) = self.backup_task.take() {
+            match task.join() {
+                Ok(Ok(())) => (),
+                Ok(Err(err)) => warn!(?err, "backup failed"),
+                Err(_) => warn!("backup panicked"),
+            }
         }
-        Ok(())
     }
 
-    fn open_collection(&mut self) -> HttpResult<Collection> {
+    fn backup_folder(&self) -> PathBuf {
+        self.folder.join("backups")
+    }
+}
+
+impl HostedCollection for User {
+    fn parts(&mut self) -> (&mut Option<Collection>, &mut Option<ServerSyncState>) {
+        (&mut self.col, &mut self.sync_state)
+    }
+
+    fn open_collection(&self) -> HttpResult<Collection> {
         CollectionBuilder::new(self.folder.join("collection.anki2"))
             .set_server(true)
             .build()
             .or_internal_err("open collection")
     }
+
+    /// Back up the collection before a full upload replaces it, however
+    /// recently the last backup was made, so that an upload from a
+    /// corrupted client can be undone. An empty collection is not backed up.
+    fn backup_before_upload(&mut self) -> HttpResult<()> {
+        self.ensure_col_open()?;
+        if self
+            .col
+            .as_ref()
+            .unwrap()
+            .storage
+            .have_at_least_one_card()
+            .or_internal_err("check for cards")?
+        {
+            self.backup(true)?;
+        }
+        Ok(())
+    }
 }
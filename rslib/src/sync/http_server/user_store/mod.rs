@@ -0,0 +1,469 @@
+// Copyright: Ankitects Pty Ltd and contributors
+// License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
+
+use std::path::Path;
+use std::time::Duration;
+
+use argon2::password_hash::PasswordHash;
+use argon2::password_hash::PasswordHasher;
+use argon2::password_hash::PasswordVerifier;
+use argon2::password_hash::SaltString;
+use argon2::Argon2;
+use lazy_static::lazy_static;
+use rusqlite::params;
+use rusqlite::Connection;
+use rusqlite::OptionalExtension;
+use rusqlite::Row;
+
+use crate::prelude::*;
+use crate::sync::http_server::env_user_names;
+use crate::sync::http_server::quota::Quota;
+
+pub(super) const USER_DB_FILENAME: &str = "users.db";
+
+lazy_static! {
+    /// Checked when a user does not exist, so that how long a login takes
+    /// does not reveal which usernames are in use.
+    static ref DUMMY_HASH: String = hash_password("").unwrap();
+}
+
+/// Users stored in a database in the sync server's base folder. Unlike the
+/// users defined with SYNC_USER env vars, they can be modified with the
+/// `users` command while the server is running.
+pub(crate) struct UserStore {
+    db: Connection,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct StoredUser {
+    pub name: String,
+    pub created: TimestampSecs,
+    /// Limits that override the server defaults.
+    pub quota: Quota,
+}
+
+/// A deck that subscribers receive read-only copies of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SharedDeck {
+    pub id: i64,
+    pub publisher: String,
+    pub deck: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Subscription {
+    pub share: SharedDeck,
+    /// The modification time of the exported deck when the subscriber
+    /// last received it.
+    pub merged: TimestampMillis,
+    /// The subscriber's collection usn when their copy was last checked
+    /// for edits, which are reverted.
+    pub checked_usn: Usn,
+}
+
+impl UserStore {
+    pub(crate) fn open(base_folder: &Path) -> Result<Self> {
+        let db = Connection::open(base_folder.join(USER_DB_FILENAME))?;
+        // the server and the users command may access the file at the same time
+        db.busy_timeout(Duration::from_secs(5))?;
+        db.pragma_update(None, "journal_mode", "wal")?;
+        db.pragma_update(None, "foreign_keys", true)?;
+        let ver: u32 = db.query_row("select user_version from pragma_user_version", [], |r| {
+            r.get(0)
+        })?;
+        if ver < 1 {
+            db.execute_batch(include_str!("schema_v1.sql"))?;
+        }
+        if ver < 2 {
+            db.execute_batch(include_str!("schema_v2.sql"))?;
+        }
+        if ver < 3 {
+            db.execute_batch(include_str!("schema_v3.sql"))?;
+        }
+        if ver < 4 {
+            db.execute_batch(include_str!("schema_v4.sql"))?;
+        }
+        if ver < 5 {
+            db.execute_batch(include_str!("schema_v5.sql"))?;
+        }
+        Ok(Self { db })
+    }
+
+    pub(crate) fn add_user(&self, name: &str, password: &str) -> Result<()> {
+        validate_username(name)?;
+        require!(
+            self.hash_for_user(name)?.is_none(),
+            "user '{name}' already exists"
+        );
+        // env and database users share a folder namespace
+        require!(
+            !env_user_names().iter().any(|env_name| env_name == name),
+            "user '{name}' is already defined in a SYNC_USER env var"
+        );
+        self.db.execute(
+            "insert into users (name, hash, hkey, created) values (?, ?, ?, ?)",
+            params![
+                name,
+                hash_password(password)?,
+                new_host_key(),
+                TimestampSecs::now().0
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Changing the password also invalidates any existing logins.
+    pub(crate) fn set_password(&self, name: &str, password: &str) -> Result<()> {
+        let changed = self.db.execute(
+            "update users set hash = ?, hkey = ? where name = ?",
+            params![hash_password(password)?, new_host_key(), name],
+        )?;
+        require!(changed > 0, "user '{name}' does not exist");
+        Ok(())
+    }
+
+    /// The user's folder is not touched.
+    pub(crate) fn remove_user(&self, name: &str) -> Result<()> {
+        let changed = self
+            .db
+            .execute("delete from users where name = ?", [name])?;
+        require!(changed > 0, "user '{name}' does not exist");
+        Ok(())
+    }
+
+    pub(crate) fn list_users(&self) -> Result<Vec<StoredUser>> {
+        self.db
+            .prepare(
+                "select name, created, max_collection_bytes, max_media_bytes, max_media_files,
+                max_requests_per_minute from users order by name",
+            )?
+            .query_and_then([], |row| {
+                Ok(StoredUser {
+                    name: row.get(0)?,
+                    created: TimestampSecs(row.get(1)?),
+                    quota: quota_from_row(row, 2)?,
+                })
+            })?
+            .collect()
+    }
+
+    pub(crate) fn set_quota(&self, name: &str, quota: Quota) -> Result<()> {
+        let changed = self.db.execute(
+            "update users set max_collection_bytes = ?, max_media_bytes = ?, max_media_files = ?,
+            max_requests_per_minute = ? where name = ?",
+            params![
+                quota.collection_bytes,
+                quota.media_bytes,
+                quota.media_files,
+                quota.requests_per_minute,
+                name
+            ],
+        )?;
+        require!(changed > 0, "user '{name}' does not exist");
+        Ok(())
+    }
+
+    /// If the password matches, returns the user's host key.
+    pub(crate) fn check_password(&self, name: &str, password: &str) -> Result<Option<String>> {
+        check_login(password, self.hash_for_user(name)?)
+    }
+
+    /// Returns the user's name and quota overrides.
+    pub(crate) fn user_for_host_key(&self, hkey: &str) -> Result<Option<(String, Quota)>> {
+        self.db
+            .query_row(
+                "select name, max_collection_bytes, max_media_bytes, max_media_files,
+                max_requests_per_minute from users where hkey = ?",
+                [hkey],
+                |row| Ok((row.get(0)?, quota_from_row(row, 1)?)),
+            )
+            .optional()
+            .map_err(Into::into)
+    }
+
+    pub(crate) fn share_deck(&self, publisher: &str, deck: &str) -> Result<()> {
+        validate_username(publisher)?;
+        require!(!deck.is_empty(), "missing deck name");
+        require!(
+            self.shared_deck(publisher, deck)?.is_none(),
+            "'{deck}' is already shared"
+        );
+        self.db.execute(
+            "insert into shared_decks (publisher, deck) values (?, ?)",
+            [publisher, deck],
+        )?;
+        Ok(())
+    }
+
+    /// Also removes any subscriptions. Copies already received by
+    /// subscribers are not touched.
+    pub(crate) fn unshare_deck(&self, publisher: &str, deck: &str) -> Result<()> {
+        let changed = self.db.execute(
+            "delete from shared_decks where publisher = ? and deck = ?",
+            [publisher, deck],
+        )?;
+        require!(changed > 0, "'{deck}' is not shared by {publisher}");
+        Ok(())
+    }
+
+    pub(crate) fn shared_decks(&self) -> Result<Vec<SharedDeck>> {
+        self.db
+            .prepare("select id, publisher, deck from shared_decks order by publisher, deck")?
+            .query_and_then([], |row| shared_deck_from_row(row).map_err(Into::into))?
+            .collect()
+    }
+
+    pub(crate) fn shared_decks_of(&self, publisher: &str) -> Result<Vec<SharedDeck>> {
+        self.db
+            .prepare("select id, publisher, deck from shared_decks where publisher = ?")?
+            .query_and_then([publisher], |row| {
+                shared_deck_from_row(row).map_err(Into::into)
+            })?
+            .collect()
+    }
+
+    pub(crate) fn subscribe(&self, subscriber: &str, publisher: &str, deck: &str) -> Result<()> {
+        validate_username(subscriber)?;
+        require!(
+            subscriber != publisher,
+            "users can't subscribe to their own decks"
+        );
+        let share = self
+            .shared_deck(publisher, deck)?
+            .or_invalid(format!("'{deck}' is not shared by {publisher}"))?;
+        self.db.execute(
+            "insert or ignore into subscriptions (subscriber, share_id) values (?, ?)",
+            params![subscriber, share.id],
+        )?;
+        Ok(())
+    }
+
+    pub(crate) fn unsubscribe(&self, subscriber: &str, publisher: &str, deck: &str) -> Result<()> {
+        let changed = self.db.execute(
+            "delete from subscriptions where subscriber = ? and share_id =
+            (select id from shared_decks where publisher = ? and deck = ?)",
+            [subscriber, publisher, deck],
+        )?;
+        require!(changed > 0, "{subscriber} is not subscribed to '{deck}'");
+        Ok(())
+    }
+
+    pub(crate) fn subscriptions(&self, subscriber: &str) -> Result<Vec<Subscription>> {
+        self.db
+            .prepare(
+                "select s.id, s.publisher, s.deck, sub.merged, sub.checked_usn
+                from subscriptions sub join shared_decks s on s.id = sub.share_id
+                where sub.subscriber = ? order by s.publisher, s.deck",
+            )?
+            .query_and_then([subscriber], |row| {
+                Ok(Subscription {
+                    share: shared_deck_from_row(row)?,
+                    merged: TimestampMillis(row.get(3)?),
+                    checked_usn: row.get(4)?,
+                })
+            })?
+            .collect()
+    }
+
+    pub(crate) fn subscribers(&self, share_id: i64) -> Result<Vec<String>> {
+        self.db
+            .prepare("select subscriber from subscriptions where share_id = ? order by subscriber")?
+            .query_and_then([share_id], |row| row.get(0).map_err(Into::into))?
+            .collect()
+    }
+
+    pub(crate) fn set_merged(
+        &self,
+        subscriber: &str,
+        share_id: i64,
+        merged: TimestampMillis,
+        checked_usn: Usn,
+    ) -> Result<()> {
+        self.db.execute(
+            "update subscriptions set merged = ?, checked_usn = ?
+            where subscriber = ? and share_id = ?",
+            params![merged.0, checked_usn, subscriber, share_id],
+        )?;
+        Ok(())
+    }
+
+    fn shared_deck(&self, publisher: &str, deck: &str) -> Result<Option<SharedDeck>> {
+        self.db
+            .query_row(
+                "select id, publisher, deck from shared_decks where publisher = ? and deck = ?",
+                [publisher, deck],
+                shared_deck_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+    }
+
+    /// The user's password hash and host key, for check_login().
+    pub(crate) fn hash_for_user(&self, name: &str) -> Result<Option<(String, String)>> {
+        self.db
+            .query_row(
+                "select hash, hkey from users where name = ?",
+                [name],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(Into::into)
+    }
+}
+
+fn quota_from_row(row: &Row, first_column: usize) -> rusqlite::Result<Quota> {
+    Ok(Quota {
+        collection_bytes: row.get(first_column)?,
+        media_bytes: row.get(first_column + 1)?,
+        media_files: row.get(first_column + 2)?,
+        requests_per_minute: row.get(first_column + 3)?,
+    })
+}
+
+fn shared_deck_from_row(row: &Row) -> rusqlite::Result<SharedDeck> {
+    Ok(SharedDeck {
+        id: row.get(0)?,
+        publisher: row.get(1)?,
+        deck: row.get(2)?,
+    })
+}
+
+/// Usernames double as folder names, so must be safe to use in a path.
+fn validate_username(name: &str) -> Result<()> {
+    require!(
+        !name.is_empty()
+            && !name.starts_with('.')
+            // also covers the -wal and -shm files SQLite keeps alongside it
+            && !name.starts_with(USER_DB_FILENAME)
+            && !name.contains(['/', '\\', ':']),
+        "invalid username '{name}'"
+    );
+    Ok(())
+}
+
+fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::b64_encode(&rand::random::<[u8; 16]>()).or_invalid("salt")?;
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .or_invalid("hashing password")?
+        .to_string())
+}
+
+/// If the password matches the hash and host key from hash_for_user(),
+/// returns the host key. This is slow, so the server does it outside the
+/// lock on the user database.
+pub(crate) fn check_login(
+    password: &str,
+    hash_and_hkey: Option<(String, String)>,
+) -> Result<Option<String>> {
+    let Some((hash, hkey)) = hash_and_hkey else {
+        verify_password(password, &DUMMY_HASH)?;
+        return Ok(None);
+    };
+    Ok(verify_password(password, &hash)?.then_some(hkey))
+}
+
+fn verify_password(password: &str, hash: &str) -> Result<bool> {
+    let hash = PasswordHash::new(hash).or_invalid("corrupt password hash")?;
+    Ok(Argon2::default()
+        .verify_password(password.as_bytes(), &hash)
+        .is_ok())
+}
+
+pub(in crate::sync) fn new_host_key() -> String {
+    hex::encode(rand::random::<[u8; 20]>())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn user_store() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = UserStore::open(dir.path())?;
+        store.add_user("alice", "secret")?;
+        assert!(store.add_user("alice", "other").is_err());
+        assert!(store.add_user("../bob", "secret").is_err());
+        assert!(store.add_user("users.db-wal", "secret").is_err());
+        // names of env users are taken
+        std::env::set_var("SYNC_USER1", "user:pass");
+        assert!(store.add_user("user", "secret").is_err());
+
+        assert_eq!(store.check_password("alice", "wrong")?, None);
+        assert_eq!(store.check_password("bob", "secret")?, None);
+        let hkey = store.check_password("alice", "secret")?.unwrap();
+        assert_eq!(
+            store
+                .user_for_host_key(&hkey)?
+                .map(|(name, _)| name)
+                .as_deref(),
+            Some("alice")
+        );
+
+        // changing the password revokes the old host key
+        store.set_password("alice", "new")?;
+        assert_eq!(store.user_for_host_key(&hkey)?, None);
+        assert_eq!(store.check_password("alice", "secret")?, None);
+        let hkey = store.check_password("alice", "new")?.unwrap();
+
+        // changes are visible to other connections, such as the one the server holds
+        let other = UserStore::open(dir.path())?;
+        assert_eq!(
+            other
+                .user_for_host_key(&hkey)?
+                .map(|(name, _)| name)
+                .as_deref(),
+            Some("alice")
+        );
+        assert_eq!(other.list_users()?.len(), 1);
+
+        let quota = Quota {
+            media_files: Some(10),
+            ..Default::default()
+        };
+        store.set_quota("alice", quota)?;
+        assert_eq!(other.user_for_host_key(&hkey)?.unwrap().1, quota);
+        assert_eq!(other.list_users()?[0].quota, quota);
+
+        store.remove_user("alice")?;
+        assert!(store.remove_user("alice").is_err());
+        assert_eq!(other.user_for_host_key(&hkey)?, None);
+        assert!(other.list_users()?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn shared_decks() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = UserStore::open(dir.path())?;
+        assert!(store.subscribe("bob", "alice", "French").is_err());
+        store.share_deck("alice", "French")?;
+        assert!(store.share_deck("alice", "French").is_err());
+        assert!(store.subscribe("alice", "alice", "French").is_err());
+        store.subscribe("bob", "alice", "French")?;
+        // subscribing again is a no-op
+        store.subscribe("bob", "alice", "French")?;
+
+        let share = store.shared_decks_of("alice")?.remove(0);
+        assert_eq!(store.shared_decks()?, vec![share.clone()]);
+        assert_eq!(store.subscribers(share.id)?, vec!["bob".to_string()]);
+        store.set_merged("bob", share.id, TimestampMillis(123), Usn(4))?;
+        assert_eq!(
+            store.subscriptions("bob")?,
+            vec![Subscription {
+                share,
+                merged: TimestampMillis(123),
+                checked_usn: Usn(4),
+            }]
+        );
+
+        // unsharing removes subscriptions
+        store.unshare_deck("alice", "French")?;
+        assert!(store.subscriptions("bob")?.is_empty());
+        assert!(store.unsubscribe("bob", "alice", "French").is_err());
+
+        Ok(())
+    }
+}
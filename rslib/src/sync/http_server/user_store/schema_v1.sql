@@ -0,0 +1,9 @@
+CREATE TABLE IF NOT EXISTS users (
+  name text NOT NULL PRIMARY KEY,
+  -- argon2 hash in PHC string format
+  hash text NOT NULL,
+  -- regenerated whenever the password changes, so existing logins are revoked
+  hkey text NOT NULL UNIQUE,
+  created int NOT NULL
+);
+pragma user_version = 1;
@@ -0,0 +1,8 @@
+-- per-user storage limits; null uses the server default
+ALTER TABLE users
+ADD COLUMN max_collection_bytes int;
+ALTER TABLE users
+ADD COLUMN max_media_bytes int;
+ALTER TABLE users
+ADD COLUMN max_media_files int;
+pragma user_version = 2;
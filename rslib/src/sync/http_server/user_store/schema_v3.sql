@@ -0,0 +1,16 @@
+-- decks that a user shares with others. Users defined with env vars may also
+-- publish, so the publisher is not a foreign key.
+CREATE TABLE shared_decks (
+  id integer PRIMARY KEY,
+  publisher text NOT NULL,
+  deck text NOT NULL,
+  UNIQUE (publisher, deck)
+);
+CREATE TABLE subscriptions (
+  subscriber text NOT NULL,
+  share_id int NOT NULL REFERENCES shared_decks (id) ON DELETE CASCADE,
+  -- modification time of the exported deck when it was last merged
+  merged int NOT NULL DEFAULT 0,
+  PRIMARY KEY (subscriber, share_id)
+);
+pragma user_version = 3;
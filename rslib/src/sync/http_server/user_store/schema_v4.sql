@@ -0,0 +1,4 @@
+-- per-user request rate limit; null uses the server default
+ALTER TABLE users
+ADD COLUMN max_requests_per_minute int;
+pragma user_version = 4;
@@ -0,0 +1,4 @@
+-- the subscriber's collection usn when their copy was last checked for edits
+ALTER TABLE subscriptions
+ADD COLUMN checked_usn int NOT NULL DEFAULT 0;
+pragma user_version = 5;
@@ -1,6 +1,7 @@
 // Copyright: Ankitects Pty Ltd and contributors
 // License: GNU AGPL, version 3 or later; http://www.gnu.org/licenses/agpl.html
 
+use std::collections::HashMap;
 use std::mem;
 
 use rusqlite::params;
@@ -32,6 +33,22 @@ impl ServerMediaDatabase {
             .map_err(Into::into)
     }
 
+    /// True if any file that has not been deleted has the given checksum.
+    pub fn checksum_in_use(&self, sha1: &[u8]) -> error::Result<bool> {
+        self.db
+            .prepare_cached("select exists(select 1 from media where csum = ? and size > 0)")?
+            .query_row([sha1], |row| row.get(0))
+            .map_err(Into::into)
+    }
+
+    /// The checksum of every file that has not been deleted.
+    pub fn nonempty_checksums(&self) -> error::Result<HashMap<String, Vec<u8>>> {
+        self.db
+            .prepare("select fname, csum from media where size > 0")?
+            .query_and_then([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect()
+    }
+
     /// Saves entry to the DB, overwriting any existing entry. Does no
     /// validation on its own; caller is responsible for mutating meta
     /// (which will update mtime as well).
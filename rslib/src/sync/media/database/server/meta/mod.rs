@@ -10,7 +10,7 @@ use crate::prelude::Usn;
 use crate::sync::media::database::server::entry::MediaEntry;
 use crate::sync::media::database::server::ServerMediaDatabase;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct StoreMetadata {
     pub last_usn: Usn,
     pub total_bytes: u64,
@@ -7,6 +7,7 @@ pub mod meta;
 use std::path::Path;
 
 use rusqlite::Connection;
+use rusqlite::OpenFlags;
 
 use crate::prelude::*;
 
@@ -20,6 +21,14 @@ impl ServerMediaDatabase {
             db: open_or_create_db(path)?,
         })
     }
+
+    /// Open an existing database without upgrading or locking it, so that it
+    /// can be inspected while the user is not loaded.
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        let db = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        db.busy_timeout(std::time::Duration::from_secs(0))?;
+        Ok(Self { db })
+    }
 }
 
 fn open_or_create_db(path: &Path) -> Result<Connection> {
@@ -36,5 +45,8 @@ fn open_or_create_db(path: &Path) -> Result<Connection> {
     if ver < 4 {
         db.execute_batch(include_str!("schema_v4.sql"))?;
     }
+    if ver < 5 {
+        db.execute_batch(include_str!("schema_v5.sql"))?;
+    }
     Ok(db)
 }
@@ -0,0 +1,5 @@
+-- lets content-addressed stores tell if a checksum is still in use
+BEGIN exclusive;
+CREATE INDEX ix_csum ON media (csum);
+pragma user_version = 5;
+COMMIT;
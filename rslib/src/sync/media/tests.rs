@@ -139,7 +139,7 @@ fn ignore_progress(_progress: MediaSyncProgress) -> bool {
 }
 
 impl SyncTestContext {
-    fn media1(&self) -> MediaManager {
+    pub(in crate::sync) fn media1(&self) -> MediaManager {
         self.col1().media().unwrap()
     }
 
@@ -147,7 +147,7 @@ impl SyncTestContext {
         self.col2().media().unwrap()
     }
 
-    async fn sync_media1(&self) -> Result<()> {
+    pub(in crate::sync) async fn sync_media1(&self) -> Result<()> {
         let mut syncer =
             MediaSyncer::new(self.media1(), ignore_progress, self.client.partial_clone()).unwrap();
         syncer.sync().await
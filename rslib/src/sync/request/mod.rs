@@ -167,7 +167,7 @@ where
             json_output_type: PhantomData,
             ip: IpAddr::from([0, 0, 0, 0]),
             media_client_version: None,
-            sync_version: SyncVersion::latest(),
+            sync_version: SyncVersion::client_default(),
             client_version: sync_client_version_short().to_string(),
             // injected by client.request()
             sync_key: String::new(),
@@ -9,7 +9,12 @@ use crate::sync::error::HttpResult;
 use crate::sync::error::OrHttpErr;
 
 pub const SYNC_VERSION_MIN: u8 = SYNC_VERSION_08_SESSIONKEY;
-pub const SYNC_VERSION_MAX: u8 = SYNC_VERSION_11_DIRECT_POST;
+pub const SYNC_VERSION_MAX: u8 = SYNC_VERSION_12_RESUMABLE_FULL_SYNC;
+
+/// The version clients send by default. Newer versions are only used once the
+/// server has advertised them in its meta response, so that clients continue
+/// to work with servers that have not been updated.
+pub const SYNC_VERSION_CLIENT_DEFAULT: u8 = SYNC_VERSION_11_DIRECT_POST;
 
 /// Added in 2013. Introduced a session key to identify parallel attempts at
 /// syncing. At the end of 2022, only used by 0.045% of syncers. Half are
@@ -34,6 +39,13 @@ pub const SYNC_VERSION_10_V2_TIMEZONE: u8 = 10;
 /// been deprecated in favour of a redirect.
 pub const SYNC_VERSION_11_DIRECT_POST: u8 = 11;
 
+/// Full uploads and downloads can be transferred in chunks, so a dropped
+/// connection resumes from the last chunk instead of starting over. The whole
+/// file is verified against its checksum once transferred. The server reports
+/// the highest version it supports in its meta response, and clients only use
+/// the chunked methods when it is at least this version.
+pub const SYNC_VERSION_12_RESUMABLE_FULL_SYNC: u8 = 12;
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 #[repr(transparent)]
 pub struct SyncVersion(pub u8);
@@ -54,10 +66,25 @@ impl SyncVersion {
         Ok(())
     }
 
+    /// The chunked full sync methods are rejected from older clients.
+    pub fn ensure_resumable(&self) -> HttpResult<()> {
+        if !self.is_resumable() {
+            None.or_bad_request(format!(
+                "chunked full sync unsupported in version {}",
+                self.0
+            ))?;
+        }
+        Ok(())
+    }
+
     pub fn latest() -> Self {
         SyncVersion(SYNC_VERSION_MAX)
     }
 
+    pub fn client_default() -> Self {
+        SyncVersion(SYNC_VERSION_CLIENT_DEFAULT)
+    }
+
     pub fn multipart() -> Self {
         Self(SYNC_VERSION_10_V2_TIMEZONE)
     }
@@ -70,6 +97,10 @@ impl SyncVersion {
         self.0 >= SYNC_VERSION_11_DIRECT_POST
     }
 
+    pub fn is_resumable(&self) -> bool {
+        self.0 >= SYNC_VERSION_12_RESUMABLE_FULL_SYNC
+    }
+
     pub fn collection_schema(&self) -> SchemaVersion {
         if self.is_multipart() {
             SchemaVersion::V11
@@ -6,6 +6,7 @@ use crate::collection::undo::UndoableCollectionChange;
 use crate::config::undo::UndoableConfigChange;
 use crate::deckconfig::undo::UndoableDeckConfigChange;
 use crate::decks::undo::UndoableDeckChange;
+use crate::media::undo::UndoableMediaChange;
 use crate::notes::undo::UndoableNoteChange;
 use crate::notetype::undo::UndoableNotetypeChange;
 use crate::prelude::*;
@@ -25,6 +26,7 @@ pub(crate) enum UndoableChange {
     Config(UndoableConfigChange),
     Collection(UndoableCollectionChange),
     Notetype(UndoableNotetypeChange),
+    Media(UndoableMediaChange),
 }
 
 impl UndoableChange {
@@ -40,6 +42,7 @@ impl UndoableChange {
             UndoableChange::DeckConfig(c) => col.undo_deck_config_change(c),
             UndoableChange::Collection(c) => col.undo_collection_change(c),
             UndoableChange::Notetype(c) => col.undo_notetype_change(c),
+            UndoableChange::Media(c) => col.undo_media_change(c),
         }
     }
 }
@@ -103,3 +106,9 @@ impl From<UndoableNotetypeChange> for UndoableChange {
         UndoableChange::Notetype(c)
     }
 }
+
+impl From<UndoableMediaChange> for UndoableChange {
+    fn from(c: UndoableMediaChange) -> Self {
+        UndoableChange::Media(c)
+    }
+}
@@ -322,6 +322,7 @@ impl From<&[UndoableChange]> for StateChanges {
                 UndoableChange::DeckConfig(_) => out.deck_config = true,
                 UndoableChange::Collection(_) => {}
                 UndoableChange::Notetype(_) => out.notetype = true,
+                UndoableChange::Media(_) => {}
             }
         }
         out
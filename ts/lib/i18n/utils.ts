@@ -31,13 +31,13 @@ export function direction(): string {
     }
 }
 
-export function weekdayLabel(n: number): string {
+export function weekdayLabel(n: number, format: "narrow" | "long" = "narrow"): string {
     const firstLang = firstLanguage();
     const now = new Date();
     const daysFromToday = -now.getDay() + n;
     const desiredDay = new Date(now.getTime() + daysFromToday * 86_400_000);
     return desiredDay.toLocaleDateString(firstLang, {
-        weekday: "narrow",
+        weekday: format,
     });
 }
 